@@ -1,13 +1,13 @@
-mod config;
-
 use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::Write;
 use std::io::BufReader;
 use std::path::PathBuf;
 use failure::ResultExt;
 use crate::error::Error;
 use crate::filesystem::pathbuf;
 
-pub use self::config::Config;
+pub use crate::config::Config;
 
 const CONFIG_FILE_NAME: &str = "config.json";
 
@@ -20,6 +20,14 @@ pub fn load_config(bundle_dir: &str) -> Result<Config, Error> {
     Ok(config)
 }
 
+// Writes a new config into the bundle, never replacing an existing one.
+pub fn write_config(bundle_dir: &str, config: &Config) -> Result<(), Error> {
+    let config_path = PathBuf::from(bundle_dir).join(CONFIG_FILE_NAME);
+    let mut file = OpenOptions::new().write(true).create_new(true).open(&config_path).context(pathbuf::to_string(config_path.clone()))?;
+    file.write_all(config.to_json()?.as_bytes()).context(pathbuf::to_string(config_path))?;
+    Ok(())
+}
+
 fn canonical_bundle_path(bundle_dir: &str) -> Result<PathBuf, Error> {
     let bundle_path = PathBuf::from(bundle_dir);
     let path = bundle_path.canonicalize().context(pathbuf::to_string(bundle_path))?;
@@ -90,6 +98,18 @@ mod tests {
         assert!(result.is_err(), "expected {:?} to be err", &result);
     }
 
+    #[test]
+    fn bundle_write_config_does_not_replace_existing_config() {
+        let bundle = tempdir().unwrap();
+        let bundle_path = bundle.path().to_str().unwrap();
+
+        write_config(bundle_path, &Config::spec()).unwrap();
+        let result = write_config(bundle_path, &Config::spec());
+
+        assert!(result.is_err(), "expected {:?} to be err", &result);
+        assert_eq!(load_config(bundle_path).unwrap(), Config::spec());
+    }
+
     #[test]
     fn bundle_load_config_return_ok_with_a_valid_config_file() {
         let bundle = setup_bundle(Some("config.json"));
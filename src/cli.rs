@@ -1,13 +1,33 @@
 use crate::Error;
 use crate::filesystem;
+use crate::bundle;
+use crate::config::Config;
+use crate::config::linux::Seccomp;
 use crate::container::Container;
+use crate::libcontainer::linux::seccomp::Mode as SeccompMode;
+use crate::libcontainer::linux::seccomp::agent::Agent;
+use crate::libcontainer::linux::seccomp::agent::RulesPolicy;
+use failure::ResultExt;
+use std::fs::File;
+use std::path::PathBuf;
+use std::str::FromStr;
 
 pub fn run(matches: &clap::ArgMatches) -> Result<(), Error> {
     let cwd = filesystem::cwd();
     let container_id = matches.value_of("container-id").unwrap();
     let bundle_dir = matches.value_of("bundle").unwrap_or(&cwd);
 
-    Container::create(container_id, bundle_dir)?;
+    let seccomp = match matches.value_of("seccomp") {
+        Some(mode) => SeccompMode::from_str(mode)?,
+        None if matches.is_present("record-seccomp") => {
+            let profile_path = PathBuf::from(&cwd).join(matches.value_of("record-seccomp").unwrap());
+            SeccompMode::Record(profile_path.to_string_lossy().to_string())
+        }
+        None if matches.is_present("default-seccomp") => SeccompMode::Default,
+        None => SeccompMode::Config,
+    };
+
+    Container::create(container_id, bundle_dir, seccomp, matches.value_of("cgroup-parent"))?;
     Container::start(container_id)?;
 
     Ok(())
@@ -27,3 +47,64 @@ pub fn query(matches: &clap::ArgMatches) -> Result<(), Error> {
 
     Ok(())
 }
+
+pub fn stats(matches: &clap::ArgMatches) -> Result<(), Error> {
+    let container_id = matches.value_of("container-id").unwrap();
+    let stats = Container::stats(container_id)?;
+    if matches.is_present("json") {
+        let json = serde_json::to_string_pretty(&stats).context("cannot serialize container stats".to_string())?;
+        println!("{}", json);
+    } else {
+        println!("{}", stats);
+    }
+
+    Ok(())
+}
+
+pub fn events(matches: &clap::ArgMatches) -> Result<(), Error> {
+    let container_id = matches.value_of("container-id").unwrap();
+    for event in Container::events(container_id)? {
+        if matches.is_present("json") {
+            let json = serde_json::to_string(&event).context("cannot serialize container event".to_string())?;
+            println!("{}", json);
+        } else {
+            println!("{}", event);
+        }
+    }
+
+    Ok(())
+}
+
+pub fn gc(matches: &clap::ArgMatches) -> Result<(), Error> {
+    let dry_run = matches.is_present("dry-run");
+    let report = Container::gc(dry_run)?;
+    println!("{}", report);
+
+    Ok(())
+}
+
+pub fn spec(matches: &clap::ArgMatches) -> Result<(), Error> {
+    let cwd = filesystem::cwd();
+    let bundle_dir = matches.value_of("bundle").unwrap_or(&cwd);
+
+    let mut config = Config::spec();
+    if matches.is_present("seccomp-default") {
+        if let Some(linux) = config.linux_mut() {
+            linux.set_seccomp(Some(Seccomp::default_profile()));
+        }
+    }
+    bundle::write_config(bundle_dir, &config)?;
+
+    Ok(())
+}
+
+pub fn seccomp_agent(matches: &clap::ArgMatches) -> Result<(), Error> {
+    let socket = matches.value_of("socket").unwrap();
+    let policy = match matches.value_of("policy") {
+        Some(path) => RulesPolicy::load(File::open(path).context(format!("cannot open {}", path))?)?,
+        None => RulesPolicy::deny_all(),
+    };
+    Agent::new(socket, policy).run()?;
+
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use serde::Serialize;
+use serde::Deserialize;
+use serde_json::Map;
+use serde_json::Value;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Hooks {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prestart: Option<Vec<Hook>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    create_runtime: Option<Vec<Hook>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    create_container: Option<Vec<Hook>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start_container: Option<Vec<Hook>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    poststart: Option<Vec<Hook>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    poststop: Option<Vec<Hook>>,
+
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl Hooks {
+    pub fn prestart(&self) -> &[Hook] { as_slice(&self.prestart) }
+    pub fn create_runtime(&self) -> &[Hook] { as_slice(&self.create_runtime) }
+    pub fn create_container(&self) -> &[Hook] { as_slice(&self.create_container) }
+    pub fn start_container(&self) -> &[Hook] { as_slice(&self.start_container) }
+    pub fn poststart(&self) -> &[Hook] { as_slice(&self.poststart) }
+    pub fn poststop(&self) -> &[Hook] { as_slice(&self.poststop) }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Hook {
+    path: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    args: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    env: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timeout: Option<i64>,
+
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl Hook {
+    pub fn path(&self) -> &str { &self.path }
+    pub fn args(&self) -> &[String] { as_slice(&self.args) }
+    pub fn env(&self) -> &[String] { as_slice(&self.env) }
+    pub fn timeout(&self) -> Option<i64> { self.timeout }
+}
+
+fn as_slice<T>(list: &Option<Vec<T>>) -> &[T] {
+    match list {
+        Some(list) => list,
+        None => &[],
+    }
+}
@@ -0,0 +1,40 @@
+use serde::Serialize;
+use serde::Deserialize;
+use serde_json::Map;
+use serde_json::Value;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Device {
+    r#type: String,
+
+    path: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    major: Option<i64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    minor: Option<i64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file_mode: Option<u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uid: Option<u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gid: Option<u32>,
+
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl Device {
+    pub fn device_type(&self) -> &str { &self.r#type }
+    pub fn path(&self) -> &str { &self.path }
+    pub fn major(&self) -> Option<i64> { self.major }
+    pub fn minor(&self) -> Option<i64> { self.minor }
+    pub fn file_mode(&self) -> Option<u32> { self.file_mode }
+    pub fn uid(&self) -> Option<u32> { self.uid }
+    pub fn gid(&self) -> Option<u32> { self.gid }
+}
@@ -0,0 +1,25 @@
+use serde::Serialize;
+use serde::Deserialize;
+use serde_json::Map;
+use serde_json::Value;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IdMapping {
+    #[serde(rename = "containerID")]
+    container_id: u32,
+
+    #[serde(rename = "hostID")]
+    host_id: u32,
+
+    size: u32,
+
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl IdMapping {
+    pub fn container_id(&self) -> u32 { self.container_id }
+    pub fn host_id(&self) -> u32 { self.host_id }
+    pub fn size(&self) -> u32 { self.size }
+}
@@ -0,0 +1,31 @@
+use serde::Serialize;
+use serde::Deserialize;
+use serde_json::Map;
+use serde_json::Value;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryPolicy {
+    mode: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nodes: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    flags: Option<Vec<String>>,
+
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl MemoryPolicy {
+    pub fn mode(&self) -> &str { &self.mode }
+    pub fn nodes(&self) -> Option<&str> { self.nodes.as_deref() }
+
+    pub fn flags(&self) -> &[String] {
+        match &self.flags {
+            Some(flags) => flags,
+            None => &[],
+        }
+    }
+}
@@ -0,0 +1,118 @@
+pub mod device;
+pub mod id_mapping;
+pub mod memory_policy;
+pub mod namespace;
+pub mod resources;
+pub mod seccomp;
+
+pub use self::device::Device;
+pub use self::id_mapping::IdMapping;
+pub use self::memory_policy::MemoryPolicy;
+pub use self::namespace::Namespace;
+pub use self::resources::Resources;
+pub use self::seccomp::Seccomp;
+
+use serde::Serialize;
+use serde::Deserialize;
+use serde_json::Map;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Linux {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    namespaces: Option<Vec<Namespace>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uid_mappings: Option<Vec<IdMapping>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gid_mappings: Option<Vec<IdMapping>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sysctl: Option<BTreeMap<String, String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    masked_paths: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    readonly_paths: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    devices: Option<Vec<Device>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    resources: Option<Resources>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cgroups_path: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rootfs_propagation: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mount_label: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seccomp: Option<Seccomp>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    memory_policy: Option<MemoryPolicy>,
+
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl Linux {
+    pub fn mount_label(&self) -> Option<&str> { self.mount_label.as_deref() }
+    pub fn rootfs_propagation(&self) -> Option<&str> { self.rootfs_propagation.as_deref() }
+    pub fn resources(&self) -> Option<&Resources> { self.resources.as_ref() }
+    pub fn cgroups_path(&self) -> Option<&str> { self.cgroups_path.as_deref() }
+    pub fn seccomp(&self) -> Option<&Seccomp> { self.seccomp.as_ref() }
+    pub fn set_seccomp(&mut self, seccomp: Option<Seccomp>) { self.seccomp = seccomp; }
+    pub fn memory_policy(&self) -> Option<&MemoryPolicy> { self.memory_policy.as_ref() }
+    pub fn sysctl(&self) -> Option<&BTreeMap<String, String>> { self.sysctl.as_ref() }
+
+    pub fn namespaces(&self) -> &[Namespace] {
+        match &self.namespaces {
+            Some(namespaces) => namespaces,
+            None => &[],
+        }
+    }
+
+    pub fn uid_mappings(&self) -> &[IdMapping] {
+        match &self.uid_mappings {
+            Some(mappings) => mappings,
+            None => &[],
+        }
+    }
+
+    pub fn devices(&self) -> &[Device] {
+        match &self.devices {
+            Some(devices) => devices,
+            None => &[],
+        }
+    }
+
+    pub fn masked_paths(&self) -> &[String] {
+        match &self.masked_paths {
+            Some(paths) => paths,
+            None => &[],
+        }
+    }
+
+    pub fn readonly_paths(&self) -> &[String] {
+        match &self.readonly_paths {
+            Some(paths) => paths,
+            None => &[],
+        }
+    }
+
+    pub fn gid_mappings(&self) -> &[IdMapping] {
+        match &self.gid_mappings {
+            Some(mappings) => mappings,
+            None => &[],
+        }
+    }
+}
@@ -0,0 +1,21 @@
+use serde::Serialize;
+use serde::Deserialize;
+use serde_json::Map;
+use serde_json::Value;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Namespace {
+    r#type: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
+
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl Namespace {
+    pub fn ns_type(&self) -> &str { &self.r#type }
+    pub fn path(&self) -> Option<&str> { self.path.as_deref() }
+}
@@ -0,0 +1,349 @@
+use serde::Serialize;
+use serde::Deserialize;
+use serde_json::Map;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Resources {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    devices: Option<Vec<DeviceRule>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    memory: Option<Memory>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cpu: Option<Cpu>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pids: Option<Pids>,
+
+    #[serde(rename = "blockIO", skip_serializing_if = "Option::is_none")]
+    block_io: Option<BlockIo>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hugepage_limits: Option<Vec<HugepageLimit>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    network: Option<Network>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unified: Option<BTreeMap<String, String>>,
+
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl Resources {
+    pub fn memory(&self) -> Option<&Memory> { self.memory.as_ref() }
+    pub fn cpu(&self) -> Option<&Cpu> { self.cpu.as_ref() }
+    pub fn pids(&self) -> Option<&Pids> { self.pids.as_ref() }
+    pub fn block_io(&self) -> Option<&BlockIo> { self.block_io.as_ref() }
+    pub fn network(&self) -> Option<&Network> { self.network.as_ref() }
+    pub fn unified(&self) -> Option<&BTreeMap<String, String>> { self.unified.as_ref() }
+
+    pub fn devices(&self) -> &[DeviceRule] {
+        match &self.devices {
+            Some(devices) => devices,
+            None => &[],
+        }
+    }
+
+    pub fn hugepage_limits(&self) -> &[HugepageLimit] {
+        match &self.hugepage_limits {
+            Some(limits) => limits,
+            None => &[],
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceRule {
+    allow: bool,
+
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    device_type: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    major: Option<i64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    minor: Option<i64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    access: Option<String>,
+
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl DeviceRule {
+    pub fn allow(&self) -> bool { self.allow }
+    pub fn device_type(&self) -> Option<&str> { self.device_type.as_deref() }
+    pub fn major(&self) -> Option<i64> { self.major }
+    pub fn minor(&self) -> Option<i64> { self.minor }
+    pub fn access(&self) -> Option<&str> { self.access.as_deref() }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Memory {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    limit: Option<i64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reservation: Option<i64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    swap: Option<i64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kernel: Option<i64>,
+
+    #[serde(rename = "kernelTCP", skip_serializing_if = "Option::is_none")]
+    kernel_tcp: Option<i64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    swappiness: Option<u64>,
+
+    #[serde(rename = "disableOOMKiller", skip_serializing_if = "Option::is_none")]
+    disable_oom_killer: Option<bool>,
+
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl Memory {
+    pub fn limit(&self) -> Option<i64> { self.limit }
+    pub fn reservation(&self) -> Option<i64> { self.reservation }
+    pub fn swap(&self) -> Option<i64> { self.swap }
+    pub fn kernel(&self) -> Option<i64> { self.kernel }
+    pub fn kernel_tcp(&self) -> Option<i64> { self.kernel_tcp }
+    pub fn swappiness(&self) -> Option<u64> { self.swappiness }
+    pub fn disable_oom_killer(&self) -> Option<bool> { self.disable_oom_killer }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Cpu {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    shares: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    quota: Option<i64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    period: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    realtime_runtime: Option<i64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    realtime_period: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cpus: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mems: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    idle: Option<i64>,
+
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl Cpu {
+    pub fn shares(&self) -> Option<u64> { self.shares }
+    pub fn quota(&self) -> Option<i64> { self.quota }
+    pub fn period(&self) -> Option<u64> { self.period }
+    pub fn realtime_runtime(&self) -> Option<i64> { self.realtime_runtime }
+    pub fn realtime_period(&self) -> Option<u64> { self.realtime_period }
+    // An empty list means the cpuset is left as inherited.
+    pub fn cpus(&self) -> Option<&str> { self.cpus.as_deref().filter(|cpus| !cpus.trim().is_empty()) }
+    pub fn mems(&self) -> Option<&str> { self.mems.as_deref().filter(|mems| !mems.trim().is_empty()) }
+    pub fn idle(&self) -> Option<i64> { self.idle }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Pids {
+    limit: i64,
+
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl Pids {
+    pub fn limit(&self) -> i64 { self.limit }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockIo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    weight: Option<u16>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    leaf_weight: Option<u16>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    weight_device: Option<Vec<WeightDevice>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    throttle_read_bps_device: Option<Vec<ThrottleDevice>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    throttle_write_bps_device: Option<Vec<ThrottleDevice>>,
+
+    #[serde(rename = "throttleReadIOPSDevice", skip_serializing_if = "Option::is_none")]
+    throttle_read_iops_device: Option<Vec<ThrottleDevice>>,
+
+    #[serde(rename = "throttleWriteIOPSDevice", skip_serializing_if = "Option::is_none")]
+    throttle_write_iops_device: Option<Vec<ThrottleDevice>>,
+
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl BlockIo {
+    pub fn weight(&self) -> Option<u16> { self.weight }
+    pub fn leaf_weight(&self) -> Option<u16> { self.leaf_weight }
+
+    pub fn weight_device(&self) -> &[WeightDevice] {
+        match &self.weight_device {
+            Some(devices) => devices,
+            None => &[],
+        }
+    }
+
+    pub fn throttle_read_bps_device(&self) -> &[ThrottleDevice] {
+        match &self.throttle_read_bps_device {
+            Some(devices) => devices,
+            None => &[],
+        }
+    }
+
+    pub fn throttle_write_bps_device(&self) -> &[ThrottleDevice] {
+        match &self.throttle_write_bps_device {
+            Some(devices) => devices,
+            None => &[],
+        }
+    }
+
+    pub fn throttle_read_iops_device(&self) -> &[ThrottleDevice] {
+        match &self.throttle_read_iops_device {
+            Some(devices) => devices,
+            None => &[],
+        }
+    }
+
+    pub fn throttle_write_iops_device(&self) -> &[ThrottleDevice] {
+        match &self.throttle_write_iops_device {
+            Some(devices) => devices,
+            None => &[],
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WeightDevice {
+    major: i64,
+
+    minor: i64,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    weight: Option<u16>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    leaf_weight: Option<u16>,
+
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl WeightDevice {
+    pub fn major(&self) -> i64 { self.major }
+    pub fn minor(&self) -> i64 { self.minor }
+    pub fn weight(&self) -> Option<u16> { self.weight }
+    pub fn leaf_weight(&self) -> Option<u16> { self.leaf_weight }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThrottleDevice {
+    major: i64,
+
+    minor: i64,
+
+    rate: u64,
+
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl ThrottleDevice {
+    pub fn major(&self) -> i64 { self.major }
+    pub fn minor(&self) -> i64 { self.minor }
+    pub fn rate(&self) -> u64 { self.rate }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HugepageLimit {
+    page_size: String,
+
+    limit: u64,
+
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl HugepageLimit {
+    pub fn page_size(&self) -> &str { &self.page_size }
+    pub fn limit(&self) -> u64 { self.limit }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Network {
+    #[serde(rename = "classID", skip_serializing_if = "Option::is_none")]
+    class_id: Option<u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    priorities: Option<Vec<InterfacePriority>>,
+
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl Network {
+    pub fn class_id(&self) -> Option<u32> { self.class_id }
+
+    pub fn priorities(&self) -> &[InterfacePriority] {
+        match &self.priorities {
+            Some(priorities) => priorities,
+            None => &[],
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InterfacePriority {
+    name: String,
+
+    priority: u32,
+
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl InterfacePriority {
+    pub fn name(&self) -> &str { &self.name }
+    pub fn priority(&self) -> u32 { self.priority }
+}
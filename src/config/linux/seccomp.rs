@@ -0,0 +1,141 @@
+use serde::Serialize;
+use serde::Deserialize;
+use serde_json::Map;
+use serde_json::Value;
+
+// An allowlist along the lines of the Docker default profile, for the
+// default capability set.
+const DEFAULT_PROFILE: &str = include_str!("default-seccomp.json");
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Seccomp {
+    default_action: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    default_errno_ret: Option<u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    architectures: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    flags: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    syscalls: Option<Vec<Syscall>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    listener_path: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    listener_metadata: Option<String>,
+
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl Seccomp {
+    pub fn default_profile() -> Seccomp {
+        serde_json::from_str(DEFAULT_PROFILE).expect("invalid embedded seccomp profile")
+    }
+
+    // Allows exactly the given syscalls, anything else fails with EPERM.
+    pub fn allowlist(architectures: Vec<String>, names: Vec<String>) -> Seccomp {
+        let syscall = Syscall {
+            names,
+            action: "SCMP_ACT_ALLOW".to_string(),
+            errno_ret: None,
+            args: None,
+            extra: Map::new(),
+        };
+        Seccomp {
+            default_action: "SCMP_ACT_ERRNO".to_string(),
+            default_errno_ret: Some(libc::EPERM as u32),
+            architectures: Some(architectures),
+            flags: None,
+            syscalls: Some(vec![syscall]),
+            listener_path: None,
+            listener_metadata: None,
+            extra: Map::new(),
+        }
+    }
+
+    pub fn default_action(&self) -> &str { &self.default_action }
+    pub fn default_errno_ret(&self) -> Option<u32> { self.default_errno_ret }
+    pub fn listener_path(&self) -> Option<&str> { self.listener_path.as_deref() }
+    pub fn listener_metadata(&self) -> Option<&str> { self.listener_metadata.as_deref() }
+
+    pub fn architectures(&self) -> &[String] {
+        match &self.architectures {
+            Some(architectures) => architectures,
+            None => &[],
+        }
+    }
+
+    pub fn flags(&self) -> &[String] {
+        match &self.flags {
+            Some(flags) => flags,
+            None => &[],
+        }
+    }
+
+    pub fn syscalls(&self) -> &[Syscall] {
+        match &self.syscalls {
+            Some(syscalls) => syscalls,
+            None => &[],
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Syscall {
+    names: Vec<String>,
+
+    action: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    errno_ret: Option<u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    args: Option<Vec<SyscallArg>>,
+
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl Syscall {
+    pub fn names(&self) -> &[String] { &self.names }
+    pub fn action(&self) -> &str { &self.action }
+    pub fn errno_ret(&self) -> Option<u32> { self.errno_ret }
+
+    pub fn args(&self) -> &[SyscallArg] {
+        match &self.args {
+            Some(args) => args,
+            None => &[],
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyscallArg {
+    index: u32,
+
+    value: u64,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value_two: Option<u64>,
+
+    op: String,
+
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl SyscallArg {
+    pub fn index(&self) -> u32 { self.index }
+    pub fn value(&self) -> u64 { self.value }
+    pub fn value_two(&self) -> u64 { self.value_two.unwrap_or(0) }
+    pub fn op(&self) -> &str { &self.op }
+}
@@ -1,241 +1,355 @@
-mod mount;
-mod process;
-mod root;
-mod platform;
+pub mod hooks;
+pub mod mount;
+pub mod process;
+pub mod root;
 
+#[cfg(target_os = "linux")]
+pub mod linux;
+
+pub use self::hooks::Hooks;
+pub use self::hooks::Hook;
 pub use self::mount::Mount;
 pub use self::process::Process;
 pub use self::root::Root;
-pub use self::platform::Linux;
+
+#[cfg(target_os = "linux")]
+pub use self::linux::Linux;
+#[cfg(target_os = "linux")]
+pub use self::linux::Namespace;
+#[cfg(target_os = "linux")]
+pub use self::linux::IdMapping;
 
 use crate::Error;
-use crate::spec::Spec;
 use failure::ResultExt;
 use serde::Serialize;
 use serde::Deserialize;
+use serde_json::Map;
+use serde_json::Value;
+use std::collections::BTreeMap;
 use std::io::Read;
 
-#[derive(Debug, Serialize, Deserialize)]
-struct Config {
+const SPEC_TEMPLATE: &str = include_str!("spec.json");
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Config {
+    oci_version: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    root: Option<Root>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
     mounts: Option<Vec<Mount>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
     process: Option<Process>,
-    root: Option<Root>,
 
-    #[cfg(target_os = "linux")]
-    linux: Option<Linux>
-}
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hostname: Option<String>,
 
-impl Config {
-    fn read_json<R: Read>(reader: R) -> Result<Config, Error> {
-        let spec: Config =
-            serde_json::from_reader(reader)
-                .context("error reading config file".to_string())?;
-        Ok(spec)
-    }
-}
+    #[serde(skip_serializing_if = "Option::is_none")]
+    domainname: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hooks: Option<Hooks>,
 
-impl Spec for Config {
-    type Mount = Mount;
-    type Root = Root;
-    type Process = Process;
+    #[serde(skip_serializing_if = "Option::is_none")]
+    annotations: Option<BTreeMap<String, String>>,
 
     #[cfg(target_os = "linux")]
-    type Linux = Linux;
+    #[serde(skip_serializing_if = "Option::is_none")]
+    linux: Option<Linux>,
 
-    fn get_root(&self) -> Option<&Self::Root> {
-        self.root.as_ref()
-    }
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
 
-    fn get_root_clone(&self) -> Option<Self::Root> {
-        self.get_root().cloned()
+impl Config {
+    pub fn load<R: Read>(reader: R) -> Result<Config, Error> {
+        let config: Config = serde_json::from_reader(reader).context("error reading config file".to_string())?;
+        Ok(config)
     }
 
-    fn get_mounts(&self) -> Option<&Vec<Self::Mount>> {
-        self.mounts.as_ref()
+    // The starting point `plankton spec` writes for new bundles.
+    pub fn spec() -> Config {
+        serde_json::from_str(SPEC_TEMPLATE).expect("invalid embedded spec template")
     }
 
-    fn get_mounts_clone(&self) -> Option<Vec<Self::Mount>> {
-        self.get_mounts().cloned()
+    pub fn to_json(&self) -> Result<String, Error> {
+        let json = serde_json::to_string_pretty(self).context("error serializing config".to_string())?;
+        Ok(json)
     }
 
-    fn get_process(&self) -> Option<&Self::Process> {
-        self.process.as_ref()
+    pub fn oci_version(&self) -> &str { &self.oci_version }
+    pub fn root(&self) -> Option<&Root> { self.root.as_ref() }
+    pub fn process(&self) -> Option<&Process> { self.process.as_ref() }
+    pub fn hostname(&self) -> Option<&str> { self.hostname.as_deref() }
+    pub fn domainname(&self) -> Option<&str> { self.domainname.as_deref() }
+    pub fn hooks(&self) -> Option<&Hooks> { self.hooks.as_ref() }
+    pub fn annotations(&self) -> Option<&BTreeMap<String, String>> { self.annotations.as_ref() }
+    pub fn extra(&self) -> &Map<String, Value> { &self.extra }
+
+    pub fn mounts(&self) -> &[Mount] {
+        match &self.mounts {
+            Some(mounts) => mounts,
+            None => &[],
+        }
     }
 
-    fn get_process_clone(&self) -> Option<Self::Process> {
-        self.get_process().cloned()
-    }
+    #[cfg(target_os = "linux")]
+    pub fn linux(&self) -> Option<&Linux> { self.linux.as_ref() }
 
-    fn get_linux(&self) -> Option<&Self::Linux> {
-        self.linux.as_ref()
-    }
+    #[cfg(target_os = "linux")]
+    pub fn linux_mut(&mut self) -> Option<&mut Linux> { self.linux.as_mut() }
 
-    fn get_linux_clone(&self) -> Option<Self::Linux> {
-        self.get_linux().cloned()
+    #[cfg(target_os = "linux")]
+    pub fn namespaces(&self) -> &[Namespace] {
+        match &self.linux {
+            Some(linux) => linux.namespaces(),
+            None => &[],
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use serde_json::json;
+
+    fn round_trip(original: &str) {
+        let expected: Value = serde_json::from_str(original).unwrap();
+        let config = Config::load(original.as_bytes());
+        assert!(config.is_ok(), "expect {:?} to be ok", config);
+        let result = serde_json::to_value(config.unwrap()).unwrap();
+        assert_eq!(result, expected);
+    }
 
     #[test]
-    fn get_root_some() {
-        let input = r#"{"root": {"path": "/"}}"#;
-        let config = Config::read_json(input.as_bytes()).unwrap();
-        let result = config.get_root();
-        assert!(result.is_some(), "expect {:?} to be some", &result);
+    fn config_load_with_syntax_error() {
+        let data = r#"{ociVersion: "1.0.1-dev"}"#;
+        let config = Config::load(data.as_bytes());
+        assert!(config.is_err(), "expect {:?} to be err", config);
     }
 
     #[test]
-    fn get_root_clone_some() {
-        let input = r#"{"root": {"path": "/"}}"#;
-        let config = Config::read_json(input.as_bytes()).unwrap();
-        let result = config.get_root_clone();
-        assert!(result.is_some(), "expect {:?} to be some", &result);
+    fn config_load_returns_err_when_json_has_no_oci_version() {
+        let data = r#"{"hostname":"hostname","root":{"path":"rootfs"}}"#;
+        let config = Config::load(data.as_bytes());
+        assert!(config.is_err(), "expect {:?} to be err", config);
     }
 
     #[test]
-    fn get_mounts_some() {
-        let input = r#"{"mounts": []}"#;
-        let config = Config::read_json(input.as_bytes()).unwrap();
-        let result = config.get_mounts();
-        assert!(result.is_some(), "expect {:?} to be some", &result);
+    fn config_load_returns_ok_with_only_oci_version() {
+        let config = Config::load(r#"{"ociVersion":"1.0.2"}"#.as_bytes());
+        assert!(config.is_ok(), "expect {:?} to be ok", config);
+
+        let config = config.unwrap();
+        assert_eq!(config.oci_version(), "1.0.2");
+        assert!(config.root().is_none());
+        assert!(config.process().is_none());
+        assert!(config.mounts().is_empty());
+        assert!(config.namespaces().is_empty());
     }
 
     #[test]
-    fn get_mounts_clone_some() {
-        let input = r#"{"mounts": []}"#;
-        let config = Config::read_json(input.as_bytes()).unwrap();
-        let result = config.get_mounts_clone();
-        assert!(result.is_some(), "expect {:?} to be some", &result);
+    fn config_load_returns_err_on_invalid_sections() {
+        let table = vec![
+            r#"{"ociVersion":"1.0.2","root":{}}"#,
+            r#"{"ociVersion":"1.0.2","process":{}}"#,
+            r#"{"ociVersion":"1.0.2","mounts":[{}]}"#,
+            r#"{"ociVersion":"1.0.2","linux":{"namespaces":[{}]}}"#,
+            r#"{"ociVersion":"1.0.2","process":[ }"#,
+        ];
+        for data in table {
+            let config = Config::load(data.as_bytes());
+            assert!(config.is_err(), "expect {:?} to be err for {}", config, data);
+        }
     }
 
     #[test]
-    fn get_process_some() {
-        let input = r#"{"process": {"cwd": "/"}}"#;
-        let config = Config::read_json(input.as_bytes()).unwrap();
-        let result = config.get_process();
-        assert!(result.is_some(), "expect {:?} to be some", &result);
+    fn config_load_parses_camel_case_fields() {
+        let data = json!({
+            "ociVersion": "1.0.2",
+            "process": {"cwd": "/", "consoleSize": {"height": 25, "width": 80}, "user": {"uid": 1, "gid": 2, "additionalGids": [3]}},
+        });
+        let config: Config = serde_json::from_value(data).unwrap();
+        let process = config.process().unwrap();
+        let console_size = process.console_size().unwrap();
+        assert_eq!((console_size.height(), console_size.width()), (25, 80));
+        assert_eq!(process.user().unwrap().additional_gids(), &[3]);
     }
 
     #[test]
-    fn get_process_clone_some() {
-        let input = r#"{"process": {"cwd": "/"}}"#;
-        let config = Config::read_json(input.as_bytes()).unwrap();
-        let result = config.get_process_clone();
-        assert!(result.is_some(), "expect {:?} to be some", &result);
+    fn config_load_parses_username_without_ids() {
+        let data = json!({
+            "ociVersion": "1.0.2",
+            "process": {"cwd": "/", "user": {"username": "nginx"}},
+        });
+        let config: Config = serde_json::from_value(data).unwrap();
+        let user = config.process().unwrap().user().unwrap();
+        assert_eq!(user.username(), Some("nginx"));
+        assert_eq!((user.uid(), user.gid()), (None, None));
     }
 
     #[test]
-    fn get_linux_some() {
-        let input = r#"{"linux": {"namespaces": []}}"#;
-        let config = Config::read_json(input.as_bytes()).unwrap();
-        let result = config.get_linux();
-        assert!(result.is_some(), "expect {:?} to be some", &result);
+    fn config_load_parses_hooks() {
+        let data = json!({
+            "ociVersion": "1.0.2",
+            "hooks": {
+                "createRuntime": [{"path": "/usr/bin/setup-network", "args": ["setup-network", "eth0"], "timeout": 5}],
+                "poststop": [{"path": "/usr/bin/cleanup"}],
+            },
+        });
+        let config: Config = serde_json::from_value(data).unwrap();
+        let hooks = config.hooks().unwrap();
+        assert!(hooks.prestart().is_empty());
+        assert_eq!(hooks.create_runtime()[0].path(), "/usr/bin/setup-network");
+        assert_eq!(hooks.create_runtime()[0].args(), &["setup-network", "eth0"]);
+        assert_eq!(hooks.create_runtime()[0].timeout(), Some(5));
+        assert_eq!(hooks.poststop()[0].timeout(), None);
     }
 
     #[test]
-    fn get_linux_clone_some() {
-        let input = r#"{"linux": {"namespaces": []}}"#;
-        let config = Config::read_json(input.as_bytes()).unwrap();
-        let result = config.get_linux_clone();
-        assert!(result.is_some(), "expect {:?} to be some", &result);
+    fn config_load_parses_seccomp() {
+        let data = json!({
+            "ociVersion": "1.0.2",
+            "linux": {
+                "seccomp": {
+                    "defaultAction": "SCMP_ACT_ERRNO",
+                    "architectures": ["SCMP_ARCH_X86_64"],
+                    "listenerPath": "/run/agent.sock",
+                    "syscalls": [
+                        {"names": ["read", "write"], "action": "SCMP_ACT_ALLOW"},
+                        {"names": ["personality"], "action": "SCMP_ACT_ALLOW", "args": [{"index": 0, "value": 8, "op": "SCMP_CMP_EQ"}]},
+                    ],
+                },
+            },
+        });
+        let config: Config = serde_json::from_value(data).unwrap();
+        let seccomp = config.linux().unwrap().seccomp().unwrap();
+        assert_eq!(seccomp.default_action(), "SCMP_ACT_ERRNO");
+        assert_eq!(seccomp.architectures(), &["SCMP_ARCH_X86_64"]);
+        assert_eq!((seccomp.listener_path(), seccomp.listener_metadata()), (Some("/run/agent.sock"), None));
+        assert_eq!(seccomp.syscalls()[0].names(), &["read", "write"]);
+        assert!(seccomp.syscalls()[0].args().is_empty());
+        let arg = &seccomp.syscalls()[1].args()[0];
+        assert_eq!((arg.index(), arg.value(), arg.value_two(), arg.op()), (0, 8, 0, "SCMP_CMP_EQ"));
     }
 
     #[test]
-    fn get_root_none() {
-        let input = r#"{}"#;
-        let config = Config::read_json(input.as_bytes()).unwrap();
-        let result = config.get_root();
-        assert!(result.is_none(), "expect {:?} to be some", &result);
+    fn config_load_parses_resources() {
+        let data = json!({
+            "ociVersion": "1.0.2",
+            "linux": {
+                "cgroupsPath": "/tenants/a/c1",
+                "resources": {
+                    "devices": [{"allow": false, "access": "rwm"}, {"allow": true, "type": "c", "major": 10, "minor": 229, "access": "rw"}],
+                    "memory": {"limit": 536870912, "kernelTCP": -1, "disableOOMKiller": false},
+                    "blockIO": {"throttleReadIOPSDevice": [{"major": 8, "minor": 0, "rate": 300}]},
+                    "unified": {"memory.high": "1G"},
+                },
+            },
+        });
+        let config: Config = serde_json::from_value(data).unwrap();
+        let resources = config.linux().unwrap().resources().unwrap();
+        let memory = resources.memory().unwrap();
+        assert_eq!((memory.limit(), memory.kernel_tcp(), memory.disable_oom_killer()), (Some(536870912), Some(-1), Some(false)));
+        assert_eq!(resources.block_io().unwrap().throttle_read_iops_device()[0].rate(), 300);
+        assert_eq!(resources.unified().unwrap()["memory.high"], "1G");
+        assert!(resources.cpu().is_none());
+        let devices = resources.devices();
+        assert_eq!((devices[0].allow(), devices[0].device_type(), devices[0].major()), (false, None, None));
+        assert_eq!((devices[1].device_type(), devices[1].minor(), devices[1].access()), (Some("c"), Some(229), Some("rw")));
+        assert_eq!(config.linux().unwrap().cgroups_path(), Some("/tenants/a/c1"));
     }
 
     #[test]
-    fn get_root_clone_none() {
-        let input = r#"{}"#;
-        let config = Config::read_json(input.as_bytes()).unwrap();
-        let result = config.get_root();
-        assert!(result.is_none(), "expect {:?} to be some", &result);
+    fn config_load_leaves_empty_cpuset_lists_unset() {
+        let data = json!({
+            "ociVersion": "1.0.2",
+            "linux": {"resources": {"cpu": {"shares": 1024, "cpus": "", "mems": " "}}},
+        });
+        let config: Config = serde_json::from_value(data).unwrap();
+        let cpu = config.linux().unwrap().resources().unwrap().cpu().unwrap();
+        assert_eq!((cpu.shares(), cpu.cpus(), cpu.mems()), (Some(1024), None, None));
     }
 
     #[test]
-    fn get_mounts_none() {
-        let input = r#"{}"#;
-        let config = Config::read_json(input.as_bytes()).unwrap();
-        let result = config.get_process();
-        assert!(result.is_none(), "expect {:?} to be some", &result);
+    fn config_load_parses_memory_policy_and_cpu_affinity() {
+        let data = json!({
+            "ociVersion": "1.0.2",
+            "process": {"cwd": "/", "execCPUAffinity": {"initial": "7", "final": "0-3,7"}},
+            "linux": {"memoryPolicy": {"mode": "MPOL_BIND", "nodes": "0", "flags": ["MPOL_F_STATIC_NODES"]}},
+        });
+        let config: Config = serde_json::from_value(data.clone()).unwrap();
+        let affinity = config.process().unwrap().exec_cpu_affinity().unwrap();
+        assert_eq!((affinity.initial(), affinity.final_cpus()), (Some("7"), Some("0-3,7")));
+        let policy = config.linux().unwrap().memory_policy().unwrap();
+        assert_eq!((policy.mode(), policy.nodes(), policy.flags()), ("MPOL_BIND", Some("0"), &["MPOL_F_STATIC_NODES".to_string()][..]));
+        assert_eq!(serde_json::to_value(&config).unwrap(), data);
     }
 
     #[test]
-    fn get_mounts_clone_none() {
-        let input = r#"{}"#;
-        let config = Config::read_json(input.as_bytes()).unwrap();
-        let result = config.get_process();
-        assert!(result.is_none(), "expect {:?} to be some", &result);
+    fn config_load_parses_scheduler_and_io_priority() {
+        let data = json!({
+            "ociVersion": "1.0.2",
+            "process": {
+                "cwd": "/",
+                "scheduler": {"policy": "SCHED_DEADLINE", "flags": ["SCHED_FLAG_RESET_ON_FORK"], "runtime": 10000000, "deadline": 30000000},
+                "ioPriority": {"class": "IOPRIO_CLASS_IDLE"},
+            },
+        });
+        let config: Config = serde_json::from_value(data.clone()).unwrap();
+        let process = config.process().unwrap();
+        let scheduler = process.scheduler().unwrap();
+        assert_eq!((scheduler.policy(), scheduler.nice(), scheduler.priority()), ("SCHED_DEADLINE", 0, 0));
+        assert_eq!((scheduler.runtime(), scheduler.deadline(), scheduler.period()), (10000000, 30000000, 0));
+        assert_eq!(scheduler.flags(), &["SCHED_FLAG_RESET_ON_FORK"]);
+        let io_priority = process.io_priority().unwrap();
+        assert_eq!((io_priority.class(), io_priority.priority()), ("IOPRIO_CLASS_IDLE", 0));
+        assert_eq!(serde_json::to_value(&config).unwrap(), data);
     }
 
     #[test]
-    fn get_process_none() {
-        let input = r#"{}"#;
-        let config = Config::read_json(input.as_bytes()).unwrap();
-        let result = config.get_process();
-        assert!(result.is_none(), "expect {:?} to be some", &result);
+    fn config_load_parses_id_mappings() {
+        let data = json!({
+            "ociVersion": "1.0.2",
+            "linux": {
+                "uidMappings": [{"containerID": 0, "hostID": 1000, "size": 32000}],
+                "gidMappings": [{"containerID": 0, "hostID": 2000, "size": 1}],
+            },
+        });
+        let config: Config = serde_json::from_value(data).unwrap();
+        let linux = config.linux().unwrap();
+        let uid_mapping = &linux.uid_mappings()[0];
+        assert_eq!((uid_mapping.container_id(), uid_mapping.host_id(), uid_mapping.size()), (0, 1000, 32000));
+        assert_eq!(linux.gid_mappings()[0].host_id(), 2000);
     }
 
     #[test]
-    fn get_process_clone_none() {
-        let input = r#"{}"#;
-        let config = Config::read_json(input.as_bytes()).unwrap();
-        let result = config.get_process();
-        assert!(result.is_none(), "expect {:?} to be some", &result);
+    fn config_keeps_unknown_fields() {
+        let data = json!({
+            "ociVersion": "1.0.2",
+            "vm": {"hypervisor": {"path": "/usr/bin/qemu"}},
+            "process": {"cwd": "/", "futureProcessField": true},
+        });
+        let config: Config = serde_json::from_value(data.clone()).unwrap();
+        assert_eq!(config.extra().get("vm"), data.get("vm"));
+        assert_eq!(serde_json::to_value(&config).unwrap(), data);
     }
 
     #[test]
-    fn get_linux_none() {
-        let input = r#"{}"#;
-        let config = Config::read_json(input.as_bytes()).unwrap();
-        let result = config.get_linux();
-        assert!(result.is_none(), "expect {:?} to be some", &result);
+    fn config_round_trips_runtime_spec_example() {
+        round_trip(include_str!("examples/runtime-spec.json"));
     }
 
     #[test]
-    fn get_linux_clone_none() {
-        let input = r#"{}"#;
-        let config = Config::read_json(input.as_bytes()).unwrap();
-        let result = config.get_linux();
-        assert!(result.is_none(), "expect {:?} to be some", &result);
+    fn config_round_trips_runc_spec_example() {
+        round_trip(include_str!("examples/runc-spec.json"));
     }
 
-    mod read_json {
-        use crate::config::Config;
-
-        #[test]
-        fn returns_error_on_invalid_root() {
-            let input = r#"{"root": {}}"#;
-            let result = Config::read_json(input.as_bytes());
-            assert!(result.is_err(), "expect {:?} to be err", &result)
-        }
-
-        #[test]
-        fn returns_error_on_invalid_process() {
-            let input = r#"{"process": {}}"#;
-            let result = Config::read_json(input.as_bytes());
-            assert!(result.is_err(), "expect {:?} to be err", &result)
-        }
-
-        #[test]
-        fn returns_error_on_invalid_mounts() {
-            let input = r#"{"mounts": [{}]}"#;
-            let result = Config::read_json(input.as_bytes());
-            assert!(result.is_err(), "expect {:?} to be err", &result)
-        }
-
-        #[test]
-        fn returns_error_on_json_syntax() {
-            let input = r#"{"process": [ }"#;
-            let result = Config::read_json(input.as_bytes());
-            assert!(result.is_err(), "expect {:?} to be err", &result)
-        }
+    #[test]
+    fn config_round_trips_runc_rootless_spec_example() {
+        round_trip(include_str!("examples/runc-spec-rootless.json"));
     }
 }
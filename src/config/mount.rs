@@ -1,50 +1,32 @@
-use crate::spec::MountSpec;
 use serde::Serialize;
 use serde::Deserialize;
-use std::path::PathBuf;
+use serde_json::Map;
+use serde_json::Value;
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Mount {
-    destination: PathBuf,
+    destination: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
     source: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
     options: Option<Vec<String>>,
 
     #[cfg(target_os = "linux")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     r#type: Option<String>,
-}
-
-impl MountSpec for Mount {
-    fn get_destination(&self) -> &PathBuf {
-        &self.destination
-    }
-
-    fn get_destination_clone(&self) -> PathBuf {
-        self.destination.clone()
-    }
 
-    fn get_source(&self) -> Option<&String> {
-        self.source.as_ref()
-    }
-
-    fn get_source_clone(&self) -> Option<String> {
-        self.source.as_ref().cloned()
-    }
-
-    fn get_options(&self) -> Option<&Vec<String>> {
-        self.options.as_ref()
-    }
-
-    fn get_options_clone(&self) -> Option<Vec<String>> {
-        self.options.as_ref().cloned()
-    }
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
 
-    #[cfg(target_os = "linux")]
-    fn get_type(&self) -> Option<&String> {
-        self.r#type.as_ref()
-    }
+impl Mount {
+    pub fn destination(&self) -> &str { &self.destination }
+    pub fn source(&self) -> Option<&str> { self.source.as_deref() }
+    pub fn options(&self) -> Option<&Vec<String>> { self.options.as_ref() }
 
     #[cfg(target_os = "linux")]
-    fn get_type_clone(&self) -> Option<String> {
-        self.r#type.as_ref().cloned()
-    }
+    pub fn filesystem_type(&self) -> Option<&str> { self.r#type.as_deref() }
 }
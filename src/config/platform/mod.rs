@@ -1,3 +0,0 @@
-mod linux;
-pub use self::linux::Linux;
-pub use self::linux::Namespace;
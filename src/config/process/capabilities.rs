@@ -0,0 +1,41 @@
+use serde::Serialize;
+use serde::Deserialize;
+use serde_json::Map;
+use serde_json::Value;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Capabilities {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bounding: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    effective: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    inheritable: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    permitted: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ambient: Option<Vec<String>>,
+
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl Capabilities {
+    pub fn bounding(&self) -> &[String] { as_slice(&self.bounding) }
+    pub fn effective(&self) -> &[String] { as_slice(&self.effective) }
+    pub fn inheritable(&self) -> &[String] { as_slice(&self.inheritable) }
+    pub fn permitted(&self) -> &[String] { as_slice(&self.permitted) }
+    pub fn ambient(&self) -> &[String] { as_slice(&self.ambient) }
+}
+
+fn as_slice(list: &Option<Vec<String>>) -> &[String] {
+    match list {
+        Some(list) => list,
+        None => &[],
+    }
+}
@@ -0,0 +1,21 @@
+use serde::Serialize;
+use serde::Deserialize;
+use serde_json::Map;
+use serde_json::Value;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IoPriority {
+    class: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    priority: Option<i32>,
+
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl IoPriority {
+    pub fn class(&self) -> &str { &self.class }
+    pub fn priority(&self) -> i32 { self.priority.unwrap_or(0) }
+}
@@ -0,0 +1,183 @@
+#[cfg(target_os = "linux")]
+pub mod capabilities;
+#[cfg(target_os = "linux")]
+pub mod io_priority;
+#[cfg(target_os = "linux")]
+pub mod rlimit;
+#[cfg(target_os = "linux")]
+pub mod scheduler;
+#[cfg(target_os = "linux")]
+pub mod user;
+
+#[cfg(target_os = "linux")]
+pub use self::capabilities::Capabilities;
+#[cfg(target_os = "linux")]
+pub use self::io_priority::IoPriority;
+#[cfg(target_os = "linux")]
+pub use self::rlimit::Rlimit;
+#[cfg(target_os = "linux")]
+pub use self::scheduler::Scheduler;
+#[cfg(target_os = "linux")]
+pub use self::user::User;
+
+use serde::Serialize;
+use serde::Deserialize;
+use serde_json::Map;
+use serde_json::Value;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Process {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    terminal: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    console_size: Option<ConsoleSize>,
+
+    cwd: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    env: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    args: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    command_line: Option<String>,
+
+    #[cfg(target_os = "linux")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    capabilities: Option<Capabilities>,
+
+    #[cfg(target_os = "linux")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    no_new_privileges: Option<bool>,
+
+    #[cfg(target_os = "linux")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    oom_score_adj: Option<i32>,
+
+    #[cfg(target_os = "linux")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rlimits: Option<Vec<Rlimit>>,
+
+    #[cfg(target_os = "linux")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    apparmor_profile: Option<String>,
+
+    #[cfg(target_os = "linux")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    selinux_label: Option<String>,
+
+    #[cfg(target_os = "linux")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user: Option<User>,
+
+    #[cfg(target_os = "linux")]
+    #[serde(rename = "execCPUAffinity", skip_serializing_if = "Option::is_none")]
+    exec_cpu_affinity: Option<ExecCpuAffinity>,
+
+    #[cfg(target_os = "linux")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scheduler: Option<Scheduler>,
+
+    #[cfg(target_os = "linux")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    io_priority: Option<IoPriority>,
+
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl Process {
+    pub fn terminal(&self) -> bool { self.terminal.unwrap_or(false) }
+    pub fn console_size(&self) -> Option<&ConsoleSize> { self.console_size.as_ref() }
+    pub fn cwd(&self) -> &str { &self.cwd }
+    pub fn command_line(&self) -> Option<&str> { self.command_line.as_deref() }
+
+    pub fn env(&self) -> &[String] {
+        match &self.env {
+            Some(env) => env,
+            None => &[],
+        }
+    }
+
+    pub fn args(&self) -> &[String] {
+        match &self.args {
+            Some(args) => args,
+            None => &[],
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn rlimits(&self) -> &[Rlimit] {
+        match &self.rlimits {
+            Some(rlimits) => rlimits,
+            None => &[],
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn capabilities(&self) -> Option<&Capabilities> { self.capabilities.as_ref() }
+
+    #[cfg(target_os = "linux")]
+    pub fn no_new_privileges(&self) -> bool { self.no_new_privileges.unwrap_or(false) }
+
+    #[cfg(target_os = "linux")]
+    pub fn oom_score_adj(&self) -> Option<i32> { self.oom_score_adj }
+
+    #[cfg(target_os = "linux")]
+    pub fn apparmor_profile(&self) -> Option<&str> { self.apparmor_profile.as_deref() }
+
+    #[cfg(target_os = "linux")]
+    pub fn selinux_label(&self) -> Option<&str> { self.selinux_label.as_deref() }
+
+    #[cfg(target_os = "linux")]
+    pub fn user(&self) -> Option<&User> { self.user.as_ref() }
+
+    #[cfg(target_os = "linux")]
+    pub fn exec_cpu_affinity(&self) -> Option<&ExecCpuAffinity> { self.exec_cpu_affinity.as_ref() }
+
+    #[cfg(target_os = "linux")]
+    pub fn scheduler(&self) -> Option<&Scheduler> { self.scheduler.as_ref() }
+
+    #[cfg(target_os = "linux")]
+    pub fn io_priority(&self) -> Option<&IoPriority> { self.io_priority.as_ref() }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsoleSize {
+    height: u32,
+    width: u32,
+
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl ConsoleSize {
+    pub fn height(&self) -> u32 { self.height }
+    pub fn width(&self) -> u32 { self.width }
+}
+
+// The cpus the runtime runs the container process on before it joins the
+// container cgroup, and the ones it execs on.
+#[cfg(target_os = "linux")]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecCpuAffinity {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    initial: Option<String>,
+
+    #[serde(rename = "final", skip_serializing_if = "Option::is_none")]
+    final_cpus: Option<String>,
+
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+#[cfg(target_os = "linux")]
+impl ExecCpuAffinity {
+    pub fn initial(&self) -> Option<&str> { self.initial.as_deref() }
+    pub fn final_cpus(&self) -> Option<&str> { self.final_cpus.as_deref() }
+}
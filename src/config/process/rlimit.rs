@@ -1,11 +1,17 @@
-use serde::{Serialize, Deserialize};
+use serde::Serialize;
+use serde::Deserialize;
+use serde_json::Map;
+use serde_json::Value;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Rlimit {
     r#type: String,
     soft: u64,
     hard: u64,
+
+    #[serde(flatten)]
+    extra: Map<String, Value>,
 }
 
 impl Rlimit {
@@ -0,0 +1,47 @@
+use serde::Serialize;
+use serde::Deserialize;
+use serde_json::Map;
+use serde_json::Value;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Scheduler {
+    policy: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nice: Option<i32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    priority: Option<i32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    flags: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    runtime: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deadline: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    period: Option<u64>,
+
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl Scheduler {
+    pub fn policy(&self) -> &str { &self.policy }
+    pub fn nice(&self) -> i32 { self.nice.unwrap_or(0) }
+    pub fn priority(&self) -> i32 { self.priority.unwrap_or(0) }
+    pub fn runtime(&self) -> u64 { self.runtime.unwrap_or(0) }
+    pub fn deadline(&self) -> u64 { self.deadline.unwrap_or(0) }
+    pub fn period(&self) -> u64 { self.period.unwrap_or(0) }
+
+    pub fn flags(&self) -> &[String] {
+        match &self.flags {
+            Some(flags) => flags,
+            None => &[],
+        }
+    }
+}
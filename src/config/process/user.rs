@@ -0,0 +1,41 @@
+use serde::Serialize;
+use serde::Deserialize;
+use serde_json::Map;
+use serde_json::Value;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct User {
+    // Required by the spec, but may be left out when username is given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uid: Option<u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gid: Option<u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    username: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    umask: Option<u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    additional_gids: Option<Vec<u32>>,
+
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl User {
+    pub fn uid(&self) -> Option<u32> { self.uid }
+    pub fn gid(&self) -> Option<u32> { self.gid }
+    pub fn username(&self) -> Option<&str> { self.username.as_deref() }
+    pub fn umask(&self) -> Option<u32> { self.umask }
+
+    pub fn additional_gids(&self) -> &[u32] {
+        match &self.additional_gids {
+            Some(gids) => gids,
+            None => &[],
+        }
+    }
+}
@@ -1,28 +1,21 @@
-use crate::spec::RootSpec;
 use serde::Serialize;
 use serde::Deserialize;
-use std::path::PathBuf;
+use serde_json::Map;
+use serde_json::Value;
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Root {
-    path: PathBuf,
-    readonly: Option<bool>,
-}
-
-impl RootSpec for Root {
-    fn get_path(&self) -> &PathBuf {
-        &self.path
-    }
+    path: String,
 
-    fn get_path_clone(&self) -> PathBuf {
-        self.path.clone()
-    }
+    #[serde(skip_serializing_if = "Option::is_none")]
+    readonly: Option<bool>,
 
-    fn get_readonly(&self) -> Option<&bool> {
-        self.readonly.as_ref()
-    }
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
 
-    fn get_readonly_clone(&self) -> Option<bool> {
-        self.readonly.as_ref().cloned()
-    }
+impl Root {
+    pub fn path(&self) -> &str { &self.path }
+    pub fn readonly(&self) -> bool { self.readonly.unwrap_or(false) }
 }
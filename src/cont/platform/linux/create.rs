@@ -1,5 +0,0 @@
-use crate::Error;
-use crate::spec::Spec;
-use crate::cont::ContainerCreate;
-use super::*;
-
@@ -0,0 +1,126 @@
+use crate::Error;
+use failure::ResultExt;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::Map;
+use serde_json::Value;
+use std::fmt;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+// Something that happened to a container while it ran. Events are kept one
+// JSON object per line in the events file of the container.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub id: String,
+    pub time: u64,
+
+    #[serde(flatten)]
+    pub data: Map<String, Value>,
+}
+
+impl Event {
+    pub fn new(event_type: &str, container_id: &str) -> Event {
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0);
+        Event {
+            event_type: event_type.to_string(),
+            id: container_id.to_string(),
+            time,
+            data: Map::new(),
+        }
+    }
+
+    pub fn oom(container_id: &str, oom_kills: u64) -> Event {
+        let mut event = Event::new("oom", container_id);
+        event.data.insert("oomKills".to_string(), Value::from(oom_kills));
+        event
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {}", self.time, self.event_type, self.id)?;
+        for (key, value) in &self.data {
+            write!(f, " {}={}", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn append(path: &Path, event: &Event) -> Result<(), Error> {
+    let mut line = serde_json::to_string(event).context("cannot serialize container event".to_string())?;
+    line.push('\n');
+    let mut file = OpenOptions::new().create(true).append(true).open(path).context(format!("cannot open events file {:?}", path))?;
+    file.write_all(line.as_bytes()).context(format!("cannot write events file {:?}", path))?;
+    Ok(())
+}
+
+pub fn load(path: &Path) -> Result<Vec<Event>, Error> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => Err(err).context(format!("cannot read events file {:?}", path))?,
+    };
+    let mut events = Vec::new();
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        let event = serde_json::from_str(line).context(format!("invalid event in {:?}", path))?;
+        events.push(event);
+    }
+    Ok(events)
+}
+
+// The OOM watcher and the runtime both report kills they see, whichever
+// gets to a new count first logs it.
+pub fn report_oom(path: &Path, container_id: &str, reported: &AtomicU64, oom_kills: u64) {
+    if reported.fetch_max(oom_kills, Ordering::SeqCst) >= oom_kills {
+        return;
+    }
+    eprintln!("Warning: container {} ran out of memory, the OOM killer killed {} process(es)", container_id, oom_kills);
+    if let Err(err) = append(path, &Event::oom(container_id, oom_kills)) {
+        eprintln!("Warning: {}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn append_and_load_keep_events_in_order() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("c1.events");
+        append(&path, &Event::new("start", "c1")).unwrap();
+        append(&path, &Event::oom("c1", 2)).unwrap();
+
+        let events = load(&path).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].event_type, "oom");
+        assert_eq!(events[1].data["oomKills"], 2);
+        assert!(load(&dir.path().join("c2.events")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn report_oom_only_reports_new_kills() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("c1.events");
+        let reported = AtomicU64::new(0);
+
+        report_oom(&path, "c1", &reported, 1);
+        report_oom(&path, "c1", &reported, 1);
+        report_oom(&path, "c1", &reported, 0);
+        report_oom(&path, "c1", &reported, 3);
+
+        let kills: Vec<Value> = load(&path).unwrap().into_iter().map(|event| event.data["oomKills"].clone()).collect();
+        assert_eq!(kills, vec![Value::from(1), Value::from(3)]);
+    }
+}
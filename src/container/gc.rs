@@ -0,0 +1,372 @@
+use crate::Error;
+use failure::ResultExt;
+use nix::errno::Errno;
+use nix::sys::signal;
+use nix::unistd::Pid;
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io::BufReader;
+use std::os::unix::fs::FileTypeExt;
+use std::path::Path;
+use std::path::PathBuf;
+use super::Container;
+use super::Status;
+use super::CONTAINER_DIR;
+use crate::libcontainer::linux::cgroup;
+
+#[derive(Debug)]
+pub struct Report {
+    dry_run: bool,
+    stale_containers: Vec<(String, i32)>,
+    orphaned_cgroups: Vec<PathBuf>,
+    leftover_files: Vec<PathBuf>,
+}
+
+impl Report {
+    pub fn stale_containers(&self) -> &Vec<(String, i32)> { &self.stale_containers }
+    pub fn orphaned_cgroups(&self) -> &Vec<PathBuf> { &self.orphaned_cgroups }
+    pub fn leftover_files(&self) -> &Vec<PathBuf> { &self.leftover_files }
+
+    pub fn is_empty(&self) -> bool {
+        self.stale_containers.is_empty() && self.orphaned_cgroups.is_empty() && self.leftover_files.is_empty()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "nothing to collect");
+        }
+
+        let mut lines = Vec::new();
+        let action = if self.dry_run { "would mark" } else { "marked" };
+        for (id, pid) in &self.stale_containers {
+            lines.push(format!("{} container {} as stopped (init {} no longer exists)", action, id, pid));
+        }
+
+        let action = if self.dry_run { "would remove" } else { "removed" };
+        for cgroup in &self.orphaned_cgroups {
+            lines.push(format!("{} orphaned cgroup {:?}", action, cgroup));
+        }
+        for file in &self.leftover_files {
+            lines.push(format!("{} leftover file {:?}", action, file));
+        }
+
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+impl Container {
+    pub fn gc(dry_run: bool) -> Result<Report, Error> {
+        collect(Path::new(CONTAINER_DIR), &cgroup::root_dirs()?, dry_run)
+    }
+}
+
+// Only cgroups of containers the state dir knows about are collected. The
+// dirs above them may be parents shared with other containers, with limits
+// of their own, so they are left alone.
+fn collect(state_dir: &Path, cgroup_roots: &[PathBuf], dry_run: bool) -> Result<Report, Error> {
+    let mut report = Report {
+        dry_run,
+        stale_containers: Vec::new(),
+        orphaned_cgroups: Vec::new(),
+        leftover_files: Vec::new(),
+    };
+
+    if !state_dir.exists() {
+        return Ok(report);
+    }
+
+    let mut live = HashSet::new();
+    let mut live_cgroups = HashSet::new();
+    let mut dead_cgroups = Vec::new();
+    for (path, mut container) in load_all(state_dir)? {
+        let cgroup_path = match &container.cgroup_path {
+            Some(cgroup_path) => cgroup_path.clone(),
+            None => Path::new(container.cgroup_parent.as_deref().unwrap_or(cgroup::CGROUP_PARENT)).join(&container.id),
+        };
+        if container.status == Status::Stopped {
+            dead_cgroups.push(cgroup_path);
+            continue;
+        }
+        match container.pid {
+            Some(pid) if !is_alive(pid) => {
+                report.stale_containers.push((container.id.clone(), pid));
+                if !dry_run {
+                    container.status = Status::Stopped;
+                    let json = serde_json::to_string(&container).context("cannot save container state".to_string())?;
+                    fs::write(&path, json).context(format!("cannot save container state to file {:?}", &path))?;
+                }
+                dead_cgroups.push(cgroup_path);
+            },
+            _ => {
+                live.insert(container.id.clone());
+                live_cgroups.insert(cgroup_path);
+            },
+        }
+    }
+
+    for entry in fs::read_dir(state_dir).context(format!("cannot read state dir {:?}", state_dir))? {
+        let entry = entry.context(format!("cannot read state dir {:?}", state_dir))?;
+        let file_type = entry.file_type().context(format!("{:?}", entry.path()))?;
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        let leftover = match (file_type.is_fifo() || file_type.is_socket(), name.strip_suffix(".events")) {
+            (true, _) => !owned_by(&name, &live),
+            (false, Some(id)) => !state_dir.join(format!("{}.json", id)).exists(),
+            (false, None) => false,
+        };
+        if !leftover {
+            continue;
+        }
+        if !dry_run {
+            fs::remove_file(entry.path()).context(format!("cannot remove leftover file {:?}", entry.path()))?;
+        }
+        report.leftover_files.push(entry.path());
+    }
+
+    for cgroup_path in dead_cgroups.iter().filter(|cgroup_path| !live_cgroups.contains(*cgroup_path)) {
+        for root in cgroup_roots {
+            let path = root.join(cgroup_path);
+            if !path.is_dir() || has_processes(&path)? {
+                continue;
+            }
+            if !dry_run {
+                remove_cgroup(&path)?;
+            }
+            report.orphaned_cgroups.push(path);
+        }
+    }
+
+    Ok(report)
+}
+
+fn load_all(state_dir: &Path) -> Result<Vec<(PathBuf, Container)>, Error> {
+    let mut containers = Vec::new();
+    for entry in fs::read_dir(state_dir).context(format!("cannot read state dir {:?}", state_dir))? {
+        let path = entry.context(format!("cannot read state dir {:?}", state_dir))?.path();
+        if !path.is_file() || path.extension() != Some(OsStr::new("json")) {
+            continue;
+        }
+        let file = File::open(&path).context(format!("cannot open container state file {:?}", &path))?;
+        let container: Container = serde_json::from_reader(BufReader::new(file)).context(format!("error loading container state {:?}", &path))?;
+        containers.push((path, container));
+    }
+    Ok(containers)
+}
+
+// Files in the state dir are named after their container, as in
+// "<id>.fifo". Ids may have dots of their own, so the name is matched
+// against every id rather than split.
+fn owned_by(name: &str, ids: &HashSet<String>) -> bool {
+    ids.iter().any(|id| name.strip_prefix(id.as_str()).is_some_and(|suffix| suffix.starts_with('.')))
+}
+
+fn is_alive(pid: i32) -> bool {
+    match signal::kill(Pid::from_raw(pid), None) {
+        Ok(_) => true,
+        Err(err) => err.as_errno() != Some(Errno::ESRCH),
+    }
+}
+
+fn has_processes(cgroup: &Path) -> Result<bool, Error> {
+    let procs = cgroup.join("cgroup.procs");
+    if procs.exists() {
+        let content = fs::read_to_string(&procs).context(format!("cannot read {:?}", &procs))?;
+        if !content.trim().is_empty() {
+            return Ok(true);
+        }
+    }
+
+    for entry in fs::read_dir(cgroup).context(format!("cannot read cgroup dir {:?}", cgroup))? {
+        let path = entry.context(format!("cannot read cgroup dir {:?}", cgroup))?.path();
+        if path.is_dir() && has_processes(&path)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn remove_cgroup(cgroup: &Path) -> Result<(), Error> {
+    for entry in fs::read_dir(cgroup).context(format!("cannot read cgroup dir {:?}", cgroup))? {
+        let path = entry.context(format!("cannot read cgroup dir {:?}", cgroup))?.path();
+        if path.is_dir() {
+            remove_cgroup(&path)?;
+        }
+    }
+    fs::remove_dir(cgroup).context(format!("cannot remove cgroup {:?}", cgroup))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nix::sys::stat::Mode;
+    use nix::unistd;
+    use tempfile::tempdir;
+
+    const DEAD_PID: i32 = 2147483647;
+
+    fn write_state(dir: &Path, id: &str, pid: Option<i32>, status: Status) {
+        write_state_with_cgroup(dir, id, pid, status, None);
+    }
+
+    fn write_state_with_cgroup(dir: &Path, id: &str, pid: Option<i32>, status: Status, cgroup_path: Option<&str>) {
+        let container = Container {
+            id: id.to_string(),
+            bundle: "/containers/mycontainer".to_string(),
+            pid,
+            status,
+            seccomp: Default::default(),
+            cgroup_parent: None,
+            cgroup_path: cgroup_path.map(PathBuf::from),
+            oom_killed: false,
+            oom_kills: None,
+        };
+        let path = dir.join(format!("{}.json", id));
+        fs::write(path, serde_json::to_string(&container).unwrap()).unwrap();
+    }
+
+    fn read_status(dir: &Path, id: &str) -> Status {
+        let file = File::open(dir.join(format!("{}.json", id))).unwrap();
+        let container: Container = serde_json::from_reader(BufReader::new(file)).unwrap();
+        container.status
+    }
+
+    #[test]
+    fn collect_marks_containers_with_dead_init_as_stopped() {
+        let state_dir = tempdir().unwrap();
+        let cgroup_root = tempdir().unwrap();
+        write_state(state_dir.path(), "dead", Some(DEAD_PID), Status::Running);
+
+        let report = collect(state_dir.path(), &[cgroup_root.path().to_path_buf()], false).unwrap();
+
+        assert_eq!(report.stale_containers(), &vec![("dead".to_string(), DEAD_PID)]);
+        assert_eq!(read_status(state_dir.path(), "dead"), Status::Stopped);
+    }
+
+    #[test]
+    fn collect_keeps_containers_with_live_init() {
+        let state_dir = tempdir().unwrap();
+        let cgroup_root = tempdir().unwrap();
+        write_state(state_dir.path(), "alive", Some(std::process::id() as i32), Status::Running);
+        write_state(state_dir.path(), "created", None, Status::Creating);
+
+        let report = collect(state_dir.path(), &[cgroup_root.path().to_path_buf()], false).unwrap();
+
+        assert!(report.is_empty(), "expect {:?} to be empty", report);
+        assert_eq!(read_status(state_dir.path(), "alive"), Status::Running);
+        assert_eq!(read_status(state_dir.path(), "created"), Status::Creating);
+    }
+
+    #[test]
+    fn collect_removes_leftover_fifos_of_dead_containers() {
+        let state_dir = tempdir().unwrap();
+        let cgroup_root = tempdir().unwrap();
+        write_state(state_dir.path(), "alive", Some(std::process::id() as i32), Status::Running);
+        let orphan = state_dir.path().join("dead.fifo");
+        let owned = state_dir.path().join("alive.fifo");
+        unistd::mkfifo(&orphan, Mode::S_IRWXU).unwrap();
+        unistd::mkfifo(&owned, Mode::S_IRWXU).unwrap();
+
+        let report = collect(state_dir.path(), &[cgroup_root.path().to_path_buf()], false).unwrap();
+
+        assert_eq!(report.leftover_files(), &vec![orphan.clone()]);
+        assert!(!orphan.exists());
+        assert!(owned.exists());
+    }
+
+    #[test]
+    fn collect_keeps_files_of_live_containers_with_dotted_ids() {
+        let state_dir = tempdir().unwrap();
+        let cgroup_root = tempdir().unwrap();
+        write_state(state_dir.path(), "web.1", Some(std::process::id() as i32), Status::Running);
+        let owned = state_dir.path().join("web.1.fifo");
+        let orphan = state_dir.path().join("web.fifo");
+        unistd::mkfifo(&owned, Mode::S_IRWXU).unwrap();
+        unistd::mkfifo(&orphan, Mode::S_IRWXU).unwrap();
+        fs::write(state_dir.path().join("web.1.events"), "").unwrap();
+        fs::create_dir_all(cgroup_root.path().join("plankton/web.1")).unwrap();
+
+        let report = collect(state_dir.path(), &[cgroup_root.path().to_path_buf()], false).unwrap();
+
+        assert_eq!(report.leftover_files(), &vec![orphan]);
+        assert!(report.orphaned_cgroups().is_empty(), "expect {:?} to be empty", report.orphaned_cgroups());
+        assert!(owned.exists());
+    }
+
+    #[test]
+    fn collect_removes_events_of_deleted_containers_only() {
+        let state_dir = tempdir().unwrap();
+        let cgroup_root = tempdir().unwrap();
+        write_state(state_dir.path(), "stopped", None, Status::Stopped);
+        let orphan = state_dir.path().join("deleted.events");
+        let owned = state_dir.path().join("stopped.events");
+        fs::write(&orphan, "").unwrap();
+        fs::write(&owned, "").unwrap();
+
+        let report = collect(state_dir.path(), &[cgroup_root.path().to_path_buf()], false).unwrap();
+
+        assert_eq!(report.leftover_files(), &vec![orphan.clone()]);
+        assert!(owned.exists());
+    }
+
+    #[test]
+    fn collect_removes_empty_cgroups_of_stopped_containers() {
+        let state_dir = tempdir().unwrap();
+        let cgroup_root = tempdir().unwrap();
+        write_state(state_dir.path(), "alive", Some(std::process::id() as i32), Status::Running);
+        write_state(state_dir.path(), "stopped", None, Status::Stopped);
+        let orphan = cgroup_root.path().join("plankton/stopped");
+        let owned = cgroup_root.path().join("plankton/alive");
+        let unknown = cgroup_root.path().join("plankton/unknown");
+        fs::create_dir_all(orphan.join("nested")).unwrap();
+        fs::create_dir_all(&owned).unwrap();
+        fs::create_dir_all(&unknown).unwrap();
+
+        let report = collect(state_dir.path(), &[cgroup_root.path().to_path_buf()], false).unwrap();
+
+        assert_eq!(report.orphaned_cgroups(), &vec![orphan.clone()]);
+        assert!(!orphan.exists());
+        assert!(owned.exists());
+        assert!(unknown.exists());
+    }
+
+    #[test]
+    fn collect_keeps_parents_of_nested_cgroups() {
+        let state_dir = tempdir().unwrap();
+        let cgroup_root = tempdir().unwrap();
+        write_state_with_cgroup(state_dir.path(), "c1", None, Status::Stopped, Some("plankton/tenant-a/c1"));
+        let orphan = cgroup_root.path().join("plankton/tenant-a/c1");
+        fs::create_dir_all(&orphan).unwrap();
+
+        let report = collect(state_dir.path(), &[cgroup_root.path().to_path_buf()], false).unwrap();
+
+        assert_eq!(report.orphaned_cgroups(), &vec![orphan.clone()]);
+        assert!(!orphan.exists());
+        assert!(cgroup_root.path().join("plankton/tenant-a").exists());
+    }
+
+    #[test]
+    fn collect_with_dry_run_does_not_change_anything() {
+        let state_dir = tempdir().unwrap();
+        let cgroup_root = tempdir().unwrap();
+        write_state(state_dir.path(), "dead", Some(DEAD_PID), Status::Running);
+        let fifo = state_dir.path().join("dead.fifo");
+        unistd::mkfifo(&fifo, Mode::S_IRWXU).unwrap();
+        let cgroup = cgroup_root.path().join("plankton/dead");
+        fs::create_dir_all(&cgroup).unwrap();
+
+        let report = collect(state_dir.path(), &[cgroup_root.path().to_path_buf()], true).unwrap();
+
+        assert_eq!(report.stale_containers().len(), 1);
+        assert_eq!(report.leftover_files().len(), 1);
+        assert_eq!(report.orphaned_cgroups().len(), 1);
+        assert_eq!(read_status(state_dir.path(), "dead"), Status::Running);
+        assert!(fifo.exists());
+        assert!(cgroup.exists());
+    }
+}
@@ -1,18 +1,38 @@
+pub mod events;
+pub mod gc;
+pub mod rollback;
 pub mod state;
 pub mod status;
 
+pub use self::events::Event;
+pub use self::gc::Report;
+pub use self::rollback::Rollback;
 pub use self::state::State;
+pub use self::state::OCI_VERSION;
 pub use self::status::Status;
 
 use crate::Error;
 use crate::libcontainer::Environment;
+use crate::libcontainer::linux::cgroup;
+use crate::libcontainer::linux::cgroup::CgroupManager;
+use crate::libcontainer::linux::cgroup::oom::OomWatch;
+use crate::libcontainer::linux::cgroup::stats::Stats;
+use crate::libcontainer::linux::hook::HookType;
+use crate::libcontainer::linux::seccomp::Mode as SeccompMode;
+use crate::libcontainer::linux::seccomp::record::Recorder;
 use failure::ResultExt;
 use serde::Serialize;
 use serde::Deserialize;
+use std::collections::BTreeMap;
 use std::fs;
 use std::fs::File;
 use std::io::BufReader;
+use std::path::Path;
 use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::AtomicU64;
+use std::thread;
 
 const CONTAINER_DIR: &str = "/run/plankton";
 
@@ -22,10 +42,25 @@ pub struct Container {
     id: String,
     pid: Option<i32>,
     status: Status,
+
+    #[serde(default, skip_serializing_if = "SeccompMode::is_config")]
+    seccomp: SeccompMode,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cgroup_parent: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cgroup_path: Option<PathBuf>,
+
+    #[serde(rename = "oomKilled", default, skip_serializing_if = "std::ops::Not::not")]
+    oom_killed: bool,
+
+    #[serde(rename = "oomKills", default, skip_serializing_if = "Option::is_none")]
+    oom_kills: Option<u64>,
 }
 
 impl Container {
-    pub fn create(container_id: &str, bundle_dir: &str) -> Result<(), Error> {
+    pub fn create(container_id: &str, bundle_dir: &str, seccomp: SeccompMode, cgroup_parent: Option<&str>) -> Result<(), Error> {
         if Container::file_path(container_id).exists() {
             Err(Error::from("container id already taken".to_string())).context(container_id.to_string())?;
         }
@@ -35,8 +70,14 @@ impl Container {
             bundle: String::from(bundle_dir),
             status: Status::Creating,
             pid: None,
+            seccomp,
+            cgroup_parent: cgroup_parent.map(String::from),
+            cgroup_path: None,
+            oom_killed: false,
+            oom_kills: None,
         };
         container.save()?;
+        let _ = fs::remove_file(Container::events_path(container_id));
 
         Ok(())
     }
@@ -47,25 +88,120 @@ impl Container {
         if container.status != Status::Creating && container.status != Status::Stopped {
             Err(Error::from("cannot start a non stopped container".to_string())).context(container_id.to_string())?;
         }
-        let environment = Environment::build(&container.bundle)?;
 
-        let spawn_result = environment.spawn_process();
-        if spawn_result.is_err() {
-            Container::delete(container_id)?;
-        }
+        container.oom_killed = false;
+        container.oom_kills = None;
+
+        let mut rollback = Rollback::new();
+        let id = container_id.to_string();
+        rollback.push("delete container state", move || Container::delete(&id));
 
-        container.update_status(Status::Created)?;
+        let mut environment = rollback.check(Environment::build(&container.bundle, container.seccomp.clone()))?;
+        let mut recorder = match container.seccomp {
+            SeccompMode::Record(_) => Some(rollback.check(Recorder::listen())?),
+            _ => None,
+        };
+        if let Some(recorder) = &recorder {
+            rollback.check(environment.set_seccomp(recorder.seccomp()))?;
+        }
+        let environment = Rc::new(environment);
+
+        let cgroup_parent = container.cgroup_parent.as_deref().unwrap_or(cgroup::CGROUP_PARENT);
+        let cgroup_path = rollback.check(cgroup::container_path(environment.cgroups_path(), cgroup_parent, &container.id))?;
+        let cgroup: Option<Rc<dyn CgroupManager>> = rollback.check(cgroup::for_container(&cgroup_path, environment.resources()))?.map(Rc::from);
+        let shared_cgroup = environment.annotations().get(cgroup::SHARED_ANNOTATION).map(String::as_str) == Some("true");
+        if let Some(cgroup) = &cgroup {
+            if !shared_cgroup {
+                rollback.check(check_cgroup_unused(cgroup.as_ref(), &cgroup_path))?;
+            }
+            rollback.check(cgroup.create(environment.resources()))?;
+            if !shared_cgroup {
+                let cgroup = cgroup.clone();
+                rollback.push("remove container cgroup", move || cgroup.remove());
+            }
+            rollback.check(cgroup::set_devices(cgroup.as_ref(), environment.resources()))?;
+            if environment.annotations().get(cgroup::OOM_GROUP_ANNOTATION).map(String::as_str) == Some("true") {
+                rollback.check(cgroup.set_oom_group())?;
+            }
+            container.cgroup_path = Some(cgroup_path.clone());
+        }
 
-        let init_pid = spawn_result.ok().unwrap();
+        let mut init = rollback.check(environment.spawn_process())?;
+        if let Some(recorder) = &mut recorder {
+            recorder.start();
+        }
+        let init_pid = init.pid();
         container.pid = Some(init_pid);
 
-        container.update_status(Status::Running)?;
+        let env = environment.clone();
+        let stopped_state = rollback.check(container.oci_state(Status::Stopped, environment.annotations()).to_json())?;
+        rollback.push("run poststop hooks", move || env.run_hooks(HookType::Poststop, &stopped_state));
+        let env = environment.clone();
+        rollback.push("unmount container mount points", move || env.unmount_points());
+        rollback.push("kill container init process", move || Environment::kill_process(init_pid));
+        if let Some(cgroup) = &cgroup {
+            rollback.check(cgroup.add_process(init_pid))?;
+        }
+
+        let oom_reported = Arc::new(AtomicU64::new(0));
+        let oom_watch = match cgroup.as_ref().map(|cgroup| cgroup.watch_oom()) {
+            Some(Ok(watch)) => watch,
+            Some(Err(err)) => {
+                eprintln!("Warning: {}", err);
+                None
+            },
+            None => None,
+        };
+        if let Some(watch) = &oom_watch {
+            match watch.try_clone() {
+                Ok(watch) => spawn_oom_watcher(watch, &container.id, oom_reported.clone()),
+                Err(err) => eprintln!("Warning: {}", err),
+            }
+        }
+
+        rollback.check(container.update_status(Status::Created))?;
+        let created_state = rollback.check(container.oci_state(Status::Created, environment.annotations()).to_json())?;
+        rollback.check(environment.run_hooks(HookType::Prestart, &created_state))?;
+        rollback.check(environment.run_hooks(HookType::CreateRuntime, &created_state))?;
+        rollback.check(init.start(&created_state))?;
+
+        rollback.check(container.update_status(Status::Running))?;
+        let running_state = rollback.check(container.oci_state(Status::Running, environment.annotations()).to_json())?;
+        if let Err(err) = environment.run_hooks(HookType::Poststart, &running_state) {
+            eprintln!("Warning: {}", err);
+        }
+
+        rollback.check(Environment::wait_process(init_pid))?;
+        rollback.commit();
+
+        if let Some(watch) = &oom_watch {
+            match watch.kills() {
+                Ok(kills) => {
+                    events::report_oom(&Container::events_path(&container.id), &container.id, &oom_reported, kills);
+                    container.oom_killed = kills > 0;
+                    container.oom_kills = Some(kills);
+                },
+                Err(err) => eprintln!("Warning: {}", err),
+            }
+        }
 
-        let wait_result = Environment::wait_process(init_pid);
-        if wait_result.is_err() {
-            Container::delete(container_id)?;
+        match &cgroup {
+            Some(cgroup) if !shared_cgroup => {
+                if let Err(err) = cgroup.remove() {
+                    eprintln!("Warning: {}", err);
+                }
+            },
+            _ => (),
         }
+
         container.update_status(Status::Stopped)?;
+        if let (SeccompMode::Record(profile_path), Some(recorder)) = (&container.seccomp, &recorder) {
+            recorder.write_profile(profile_path)?;
+        }
+        let stopped_state = container.oci_state(Status::Stopped, environment.annotations()).to_json()?;
+        if let Err(err) = environment.run_hooks(HookType::Poststop, &stopped_state) {
+            eprintln!("Warning: {}", err);
+        }
 
         Ok(())
     }
@@ -76,6 +212,34 @@ impl Container {
         Ok(json)
     }
 
+    pub fn stats(container_id: &str) -> Result<Stats, Error> {
+        let container = Container::load(container_id)?;
+        if container.status != Status::Created && container.status != Status::Running {
+            Err(Error::from("container is not running".to_string())).context(container_id.to_string())?;
+        }
+        let cgroup_path = match &container.cgroup_path {
+            Some(cgroup_path) => cgroup_path,
+            None => Err(Error::from("container has no cgroup".to_string())).context(container_id.to_string())?,
+        };
+        cgroup::load(cgroup_path)?.stats()
+    }
+
+    pub fn events(container_id: &str) -> Result<Vec<Event>, Error> {
+        Container::load(container_id)?;
+        events::load(&Container::events_path(container_id))
+    }
+
+    fn oci_state(&self, status: Status, annotations: &BTreeMap<String, String>) -> State {
+        State {
+            bundle: self.bundle.clone(),
+            id: self.id.clone(),
+            oci_version: OCI_VERSION.to_string(),
+            pid: self.pid,
+            status: format!("{:?}", status),
+            annotations: annotations.clone(),
+        }
+    }
+
     fn update_status(&mut self, status: Status) -> Result<(), Error> {
         self.status = status;
         self.save()?;
@@ -113,6 +277,35 @@ impl Container {
         let path = format!("{}/{}.json", CONTAINER_DIR, container_id);
         PathBuf::from(path)
     }
+
+    fn events_path(container_id: &str) -> PathBuf {
+        let path = format!("{}/{}.events", CONTAINER_DIR, container_id);
+        PathBuf::from(path)
+    }
+}
+
+// Started once the init process is cloned, like the seccomp recorder. The
+// thread ends when the cgroup is removed and the watch fails.
+fn spawn_oom_watcher(watch: OomWatch, container_id: &str, reported: Arc<AtomicU64>) {
+    let container_id = container_id.to_string();
+    thread::spawn(move || {
+        let events_path = Container::events_path(&container_id);
+        while watch.wait().is_ok() {
+            match watch.kills() {
+                Ok(kills) => events::report_oom(&events_path, &container_id, &reported, kills),
+                Err(_) => return,
+            }
+        }
+    });
+}
+
+// Containers sharing a cgroup share its limits and get killed together,
+// so that has to be asked for with the shared annotation.
+fn check_cgroup_unused(cgroup: &dyn CgroupManager, path: &Path) -> Result<(), Error> {
+    if cgroup.has_processes()? {
+        Err(Error::from("cgroup is already in use".to_string())).context(format!("{:?}", path))?;
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -164,12 +357,17 @@ mod tests {
             bundle: String::from("/containers/mycontainer"),
             status: Status::Creating,
             pid: Some(5327),
+            seccomp: SeccompMode::Config,
+            cgroup_parent: None,
+            cgroup_path: None,
+            oom_killed: false,
+            oom_kills: None,
         };
         container.save().unwrap();
 
         let bundle = setup_bundle(Some("config.json"));
         let bundle_path = bundle.path().to_str().unwrap();
-        let result = Container::create(container_id, bundle_path);
+        let result = Container::create(container_id, bundle_path, SeccompMode::Config, None);
         assert!(result.is_err());
         Container::delete(container_id).unwrap();
     }
@@ -179,10 +377,31 @@ mod tests {
         let container_id = "my-container-id";
         let bundle = setup_bundle(Some("config.json"));
         let bundle_path = bundle.path().to_str().unwrap();
-        let result = Container::create(container_id, bundle_path);
+        let result = Container::create(container_id, bundle_path, SeccompMode::Config, None);
         assert!(result.is_ok(), "expected {:?} to be ok", &result);
     }
 
+    #[test]
+    fn container_stats_returns_error_when_container_is_not_running() {
+        let container_id = "stopped-container-id";
+        let container = Container {
+            id: String::from(container_id),
+            bundle: String::from("/containers/mycontainer"),
+            status: Status::Stopped,
+            pid: Some(5327),
+            seccomp: SeccompMode::Config,
+            cgroup_parent: None,
+            cgroup_path: Some(PathBuf::from("plankton/stopped-container-id")),
+            oom_killed: false,
+            oom_kills: None,
+        };
+        container.save().unwrap();
+
+        let result = Container::stats(container_id);
+        assert!(result.is_err(), "expect {:?} to be err", result);
+        Container::delete(container_id).unwrap();
+    }
+
     #[test]
     fn container_state_returns_error_when_container_is_not_found() {
         let result = Container::state("unexistent-containter");
@@ -197,6 +416,11 @@ mod tests {
             bundle: String::from("/containers/mycontainer"),
             status: Status::Creating,
             pid: Some(5327),
+            seccomp: SeccompMode::Config,
+            cgroup_parent: None,
+            cgroup_path: None,
+            oom_killed: false,
+            oom_kills: None,
         };
 
         container.save().unwrap();
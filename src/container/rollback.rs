@@ -0,0 +1,146 @@
+use crate::Error;
+
+type Action = Box<dyn FnMut() -> Result<(), Error>>;
+
+pub struct Rollback {
+    actions: Vec<(String, Action)>,
+}
+
+impl Rollback {
+    pub fn new() -> Rollback {
+        Rollback {
+            actions: Vec::new(),
+        }
+    }
+
+    pub fn push<F>(&mut self, description: &str, action: F) where F: FnMut() -> Result<(), Error> + 'static {
+        self.actions.push((description.to_string(), Box::new(action)));
+    }
+
+    pub fn len(&self) -> usize {
+        self.actions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+
+    pub fn check<T>(&mut self, result: Result<T, Error>) -> Result<T, Error> {
+        match result {
+            Ok(value) => Ok(value),
+            Err(cause) => Err(self.run(cause)),
+        }
+    }
+
+    pub fn run(&mut self, cause: Error) -> Error {
+        let mut failures = Vec::new();
+        while let Some((description, mut action)) = self.actions.pop() {
+            if let Err(err) = action() {
+                failures.push(format!("cleanup step '{}' failed: {}", description, err));
+            }
+        }
+
+        if failures.is_empty() {
+            return cause;
+        }
+        Error::from(format!("{}\n{}", cause, failures.join("\n")))
+    }
+
+    pub fn commit(&mut self) {
+        self.actions.clear();
+    }
+}
+
+impl Default for Rollback {
+    fn default() -> Rollback {
+        Rollback::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn rollback_run_executes_actions_in_reverse_order() {
+        let executed = Rc::new(RefCell::new(Vec::new()));
+        let mut rollback = Rollback::new();
+        for step in 0..3 {
+            let executed = executed.clone();
+            rollback.push(&format!("step {}", step), move || {
+                executed.borrow_mut().push(step);
+                Ok(())
+            });
+        }
+
+        rollback.run(Error::from("setup failed"));
+
+        assert_eq!(*executed.borrow(), vec![2, 1, 0]);
+        assert!(rollback.is_empty());
+    }
+
+    #[test]
+    fn rollback_run_returns_original_error_when_cleanup_succeeds() {
+        let mut rollback = Rollback::new();
+        rollback.push("noop", || Ok(()));
+
+        let err = rollback.run(Error::from("setup failed"));
+
+        assert_eq!(format!("{}", err), "setup failed");
+    }
+
+    #[test]
+    fn rollback_run_reports_cleanup_failures_along_with_original_error() {
+        let executed = Rc::new(RefCell::new(false));
+        let mut rollback = Rollback::new();
+        {
+            let executed = executed.clone();
+            rollback.push("first", move || {
+                *executed.borrow_mut() = true;
+                Ok(())
+            });
+        }
+        rollback.push("second", || Err(Error::from("cannot undo")));
+
+        let message = format!("{}", rollback.run(Error::from("setup failed")));
+
+        assert!(*executed.borrow(), "expect remaining actions to run after a failure");
+        assert!(message.starts_with("setup failed"), "unexpected message {:?}", message);
+        assert!(message.contains("cleanup step 'second' failed: cannot undo"), "unexpected message {:?}", message);
+    }
+
+    #[test]
+    fn rollback_check_runs_actions_only_on_error() {
+        let executed = Rc::new(RefCell::new(0));
+        let mut rollback = Rollback::new();
+        {
+            let executed = executed.clone();
+            rollback.push("count", move || {
+                *executed.borrow_mut() += 1;
+                Ok(())
+            });
+        }
+
+        let ok = rollback.check(Ok::<i32, Error>(1));
+        assert!(ok.is_ok(), "expect {:?} to be ok", ok);
+        assert_eq!(*executed.borrow(), 0);
+        assert_eq!(rollback.len(), 1);
+
+        let err = rollback.check(Err::<i32, Error>(Error::from("boom")));
+        assert!(err.is_err(), "expect {:?} to be err", err);
+        assert_eq!(*executed.borrow(), 1);
+    }
+
+    #[test]
+    fn rollback_commit_discards_actions() {
+        let mut rollback = Rollback::new();
+        rollback.push("fail", || Err(Error::from("should not run")));
+
+        rollback.commit();
+        let err = rollback.run(Error::from("setup failed"));
+
+        assert_eq!(format!("{}", err), "setup failed");
+    }
+}
@@ -1,14 +1,20 @@
 use crate::Error;
 use serde::Serialize;
 use failure::ResultExt;
+use std::collections::BTreeMap;
+
+pub const OCI_VERSION: &str = "1.0.2";
 
 #[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct State {
     pub bundle: String,
     pub id: String,
     pub oci_version: String,
     pub pid: Option<i32>,
     pub status: String,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, String>,
 }
 
 impl State {
@@ -33,10 +39,11 @@ mod tests {
             oci_version: "1.0.1-beta1".to_string(),
             pid: Some(5327),
             status: "creating".to_string(),
+            annotations: BTreeMap::new(),
         };
 
         let json_state = json!({
-            "oci_version": "1.0.1-beta1",
+            "ociVersion": "1.0.1-beta1",
             "id": "my-container-id",
             "status": "creating",
             "pid": Some(5327),
@@ -46,4 +53,21 @@ mod tests {
         assert!(state.to_json().is_ok());
         assert_eq!(state.to_json().unwrap(), serde_json::to_string_pretty(&json_state).unwrap());
     }
+
+    #[test]
+    fn state_to_json_includes_annotations_when_present() {
+        let mut annotations = BTreeMap::new();
+        annotations.insert("com.example.key".to_string(), "value".to_string());
+        let state = State {
+            bundle: "/containers/my-container-id".to_string(),
+            id: "my-container-id".to_string(),
+            oci_version: OCI_VERSION.to_string(),
+            pid: None,
+            status: "created".to_string(),
+            annotations,
+        };
+
+        let json: serde_json::Value = serde_json::from_str(&state.to_json().unwrap()).unwrap();
+        assert_eq!(json["annotations"]["com.example.key"], "value");
+    }
 }
@@ -1,6 +1,7 @@
 use std::env;
 
 pub mod pathbuf;
+pub mod securejoin;
 
 const DEFAULT_PATH: &str = ".";
 
@@ -0,0 +1,86 @@
+use crate::Error;
+use std::fs;
+use failure::ResultExt;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+
+const MAX_SYMLINKS: usize = 255;
+
+// Joins a container path to the rootfs resolving symlinks as if `root` was
+// "/", so neither ".." nor absolute symlinks can point outside of it.
+// Components that do not exist yet are appended as they are.
+pub fn secure_join(root: &Path, path: &Path) -> Result<PathBuf, Error> {
+    let mut pending: Vec<PathBuf> = path.components().rev().map(|c| PathBuf::from(c.as_os_str())).collect();
+    let mut resolved = PathBuf::new();
+    let mut followed = 0;
+
+    while let Some(next) = pending.pop() {
+        match next.components().next() {
+            Some(Component::ParentDir) => {
+                resolved.pop();
+            },
+            Some(Component::Normal(name)) => {
+                let candidate = resolved.join(name);
+                let full_path = root.join(&candidate);
+                match fs::symlink_metadata(&full_path) {
+                    Ok(ref metadata) if metadata.file_type().is_symlink() => {
+                        followed += 1;
+                        if followed > MAX_SYMLINKS {
+                            Err(Error::from(format!("too many symlinks resolving {:?}", path)))?;
+                        }
+                        let target = fs::read_link(&full_path).context(format!("{:?}", full_path))?;
+                        if target.is_absolute() {
+                            resolved = PathBuf::new();
+                        }
+                        pending.extend(target.components().rev().map(|c| PathBuf::from(c.as_os_str())));
+                    },
+                    _ => resolved = candidate,
+                }
+            },
+            _ => {},
+        }
+    }
+
+    Ok(root.join(resolved))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+    use tempfile::tempdir;
+
+    #[test]
+    fn secure_join_appends_plain_paths() {
+        let root = tempdir().unwrap();
+        let result = secure_join(root.path(), Path::new("/dev/null")).unwrap();
+        assert_eq!(result, root.path().join("dev/null"));
+    }
+
+    #[test]
+    fn secure_join_keeps_parent_dirs_inside_root() {
+        let root = tempdir().unwrap();
+        let result = secure_join(root.path(), Path::new("/../../etc/passwd")).unwrap();
+        assert_eq!(result, root.path().join("etc/passwd"));
+    }
+
+    #[test]
+    fn secure_join_resolves_symlinks_inside_root() {
+        let root = tempdir().unwrap();
+        fs::create_dir(root.path().join("run")).unwrap();
+        symlink("/etc", root.path().join("run/etc")).unwrap();
+        symlink("../../..", root.path().join("up")).unwrap();
+
+        assert_eq!(secure_join(root.path(), Path::new("/run/etc/shadow")).unwrap(), root.path().join("etc/shadow"));
+        assert_eq!(secure_join(root.path(), Path::new("/up/etc")).unwrap(), root.path().join("etc"));
+    }
+
+    #[test]
+    fn secure_join_returns_error_on_symlink_loops() {
+        let root = tempdir().unwrap();
+        symlink("/loop", root.path().join("loop")).unwrap();
+        let result = secure_join(root.path(), Path::new("/loop"));
+        assert!(result.is_err(), "expect {:?} to be err", result);
+    }
+}
@@ -0,0 +1,220 @@
+use crate::Error;
+use serde::Deserialize;
+use serde::Serialize;
+use std::fs;
+use std::io;
+use failure::ResultExt;
+
+const CAP_LAST_CAP_FILE: &str = "/proc/sys/kernel/cap_last_cap";
+const LINUX_CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+
+const CAPABILITIES: &[&str] = &[
+    "CAP_CHOWN",
+    "CAP_DAC_OVERRIDE",
+    "CAP_DAC_READ_SEARCH",
+    "CAP_FOWNER",
+    "CAP_FSETID",
+    "CAP_KILL",
+    "CAP_SETGID",
+    "CAP_SETUID",
+    "CAP_SETPCAP",
+    "CAP_LINUX_IMMUTABLE",
+    "CAP_NET_BIND_SERVICE",
+    "CAP_NET_BROADCAST",
+    "CAP_NET_ADMIN",
+    "CAP_NET_RAW",
+    "CAP_IPC_LOCK",
+    "CAP_IPC_OWNER",
+    "CAP_SYS_MODULE",
+    "CAP_SYS_RAWIO",
+    "CAP_SYS_CHROOT",
+    "CAP_SYS_PTRACE",
+    "CAP_SYS_PACCT",
+    "CAP_SYS_ADMIN",
+    "CAP_SYS_BOOT",
+    "CAP_SYS_NICE",
+    "CAP_SYS_RESOURCE",
+    "CAP_SYS_TIME",
+    "CAP_SYS_TTY_CONFIG",
+    "CAP_MKNOD",
+    "CAP_LEASE",
+    "CAP_AUDIT_WRITE",
+    "CAP_AUDIT_CONTROL",
+    "CAP_SETFCAP",
+    "CAP_MAC_OVERRIDE",
+    "CAP_MAC_ADMIN",
+    "CAP_SYSLOG",
+    "CAP_WAKE_ALARM",
+    "CAP_BLOCK_SUSPEND",
+    "CAP_AUDIT_READ",
+    "CAP_PERFMON",
+    "CAP_BPF",
+    "CAP_CHECKPOINT_RESTORE",
+];
+
+#[repr(C)]
+struct CapUserHeader {
+    version: u32,
+    pid: i32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct CapUserData {
+    effective: u32,
+    permitted: u32,
+    inheritable: u32,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Capabilities {
+    bounding: u64,
+    effective: u64,
+    inheritable: u64,
+    permitted: u64,
+    ambient: u64,
+}
+
+impl Capabilities {
+    pub fn set_bounding(&mut self, names: &[String]) -> Result<(), Error> {
+        self.bounding = to_mask(names).context("invalid bounding capabilities".to_string())?;
+        Ok(())
+    }
+
+    pub fn set_effective(&mut self, names: &[String]) -> Result<(), Error> {
+        self.effective = to_mask(names).context("invalid effective capabilities".to_string())?;
+        Ok(())
+    }
+
+    pub fn set_inheritable(&mut self, names: &[String]) -> Result<(), Error> {
+        self.inheritable = to_mask(names).context("invalid inheritable capabilities".to_string())?;
+        Ok(())
+    }
+
+    pub fn set_permitted(&mut self, names: &[String]) -> Result<(), Error> {
+        self.permitted = to_mask(names).context("invalid permitted capabilities".to_string())?;
+        Ok(())
+    }
+
+    pub fn set_ambient(&mut self, names: &[String]) -> Result<(), Error> {
+        self.ambient = to_mask(names).context("invalid ambient capabilities".to_string())?;
+        Ok(())
+    }
+
+    // Needs CAP_SETPCAP, so it has to happen before changing user.
+    pub fn drop_bounding(&self) -> Result<(), Error> {
+        for cap in 0..=last_cap() {
+            if self.bounding & (1 << cap) != 0 {
+                continue;
+            }
+            if unsafe { libc::prctl(libc::PR_CAPBSET_DROP, libc::c_ulong::from(cap), 0, 0, 0) } != 0 {
+                Err(io::Error::last_os_error()).context(format!("cannot drop {} from the bounding set", name(cap)))?;
+            }
+        }
+        Ok(())
+    }
+
+    // Keeps the permitted set across the setuid call that changes to the
+    // container user, which would otherwise clear it.
+    pub fn keep_on_setuid(keep: bool) -> Result<(), Error> {
+        if unsafe { libc::prctl(libc::PR_SET_KEEPCAPS, libc::c_ulong::from(keep), 0, 0, 0) } != 0 {
+            Err(io::Error::last_os_error()).context("cannot set keep capabilities flag".to_string())?;
+        }
+        Ok(())
+    }
+
+    pub fn apply(&self) -> Result<(), Error> {
+        let mut header = CapUserHeader { version: LINUX_CAPABILITY_VERSION_3, pid: 0 };
+        let mut data = [CapUserData::default(); 2];
+        for (index, chunk) in data.iter_mut().enumerate() {
+            let shift = 32 * index;
+            chunk.effective = (self.effective >> shift) as u32;
+            chunk.permitted = (self.permitted >> shift) as u32;
+            chunk.inheritable = (self.inheritable >> shift) as u32;
+        }
+        if unsafe { libc::syscall(libc::SYS_capset, &mut header as *mut CapUserHeader, data.as_mut_ptr()) } != 0 {
+            Err(io::Error::last_os_error()).context("cannot set process capabilities".to_string())?;
+        }
+
+        for cap in 0..=last_cap() {
+            if self.ambient & (1 << cap) == 0 {
+                continue;
+            }
+            let result = unsafe {
+                libc::prctl(libc::PR_CAP_AMBIENT, libc::PR_CAP_AMBIENT_RAISE as libc::c_ulong, libc::c_ulong::from(cap), 0, 0)
+            };
+            if result != 0 {
+                Err(io::Error::last_os_error()).context(format!("cannot raise ambient capability {}", name(cap)))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn to_mask(names: &[String]) -> Result<u64, Error> {
+    let mut mask = 0;
+    for name in names {
+        match CAPABILITIES.iter().position(|cap| cap == name) {
+            Some(cap) => mask |= 1 << cap,
+            None => Err(Error::from("unknown capability".to_string())).context(name.to_string())?,
+        }
+    }
+    Ok(mask)
+}
+
+fn name(cap: u32) -> String {
+    match CAPABILITIES.get(cap as usize) {
+        Some(name) => name.to_string(),
+        None => format!("capability {}", cap),
+    }
+}
+
+// Capabilities unknown to the running kernel cannot be dropped or raised.
+fn last_cap() -> u32 {
+    let max = (CAPABILITIES.len() - 1) as u32;
+    match fs::read_to_string(CAP_LAST_CAP_FILE) {
+        Ok(contents) => contents.trim().parse().map(|last: u32| last.min(max)).unwrap_or(max),
+        Err(_) => max,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(list: &[&str]) -> Vec<String> {
+        list.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn capabilities_set_builds_masks_from_names() {
+        let mut capabilities = Capabilities::default();
+
+        capabilities.set_bounding(&names(&["CAP_CHOWN", "CAP_KILL", "CAP_NET_BIND_SERVICE"])).unwrap();
+        capabilities.set_ambient(&names(&["CAP_CHECKPOINT_RESTORE"])).unwrap();
+
+        assert_eq!(capabilities.bounding, 1 | 1 << 5 | 1 << 10);
+        assert_eq!(capabilities.ambient, 1 << 40);
+    }
+
+    #[test]
+    fn capabilities_set_returns_error_with_unknown_name() {
+        let mut capabilities = Capabilities::default();
+
+        let result = capabilities.set_effective(&names(&["CAP_KILL", "CAP_FLY"]));
+
+        assert!(result.is_err(), "expect {:?} to be err", result);
+        assert!(format!("{}", result.unwrap_err()).contains("CAP_FLY"));
+    }
+
+    #[test]
+    fn last_cap_is_never_beyond_known_capabilities() {
+        let max = (CAPABILITIES.len() - 1) as u32;
+        let expected = match fs::read_to_string(CAP_LAST_CAP_FILE) {
+            Ok(contents) => contents.trim().parse::<u32>().unwrap().min(max),
+            Err(_) => max,
+        };
+
+        assert_eq!(last_cap(), expected);
+    }
+}
@@ -0,0 +1,422 @@
+use crate::Error;
+use crate::config::linux::Resources;
+use crate::config::linux::resources::DeviceRule;
+use std::fs::File;
+use std::io;
+use std::mem;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::io::FromRawFd;
+use std::path::Path;
+use failure::ResultExt;
+
+// Access bits and device types as the kernel passes them to cgroup device
+// programs, in struct bpf_cgroup_dev_ctx.
+const ACCESS_MKNOD: u32 = 1;
+const ACCESS_READ: u32 = 2;
+const ACCESS_WRITE: u32 = 4;
+const ACCESS_ALL: u32 = ACCESS_MKNOD | ACCESS_READ | ACCESS_WRITE;
+const DEV_BLOCK: i32 = 1;
+const DEV_CHAR: i32 = 2;
+
+const BPF_PROG_LOAD: libc::c_long = 5;
+const BPF_PROG_ATTACH: libc::c_long = 8;
+const BPF_PROG_DETACH: libc::c_long = 9;
+const BPF_PROG_GET_FD_BY_ID: libc::c_long = 13;
+const BPF_PROG_QUERY: libc::c_long = 16;
+const BPF_PROG_TYPE_CGROUP_DEVICE: u32 = 15;
+const BPF_CGROUP_DEVICE: u32 = 6;
+const BPF_F_ALLOW_MULTI: u32 = 2;
+const LICENSE: &[u8] = b"GPL\0";
+const MAX_ATTACHED_PROGRAMS: usize = 64;
+
+const BPF_LDX_MEM_W: u8 = 0x61;
+const BPF_ALU_AND_K: u8 = 0x54;
+const BPF_ALU_RSH_K: u8 = 0x74;
+const BPF_ALU_MOV_X: u8 = 0xbc;
+const BPF_ALU64_MOV_K: u8 = 0xb7;
+const BPF_JMP_JNE_K: u8 = 0x55;
+const BPF_JMP_JNE_X: u8 = 0x5d;
+const BPF_JMP_EXIT: u8 = 0x95;
+
+// Devices every container can use whatever its config says, the same set
+// runc allows: creating any node, the usual character devices, and
+// terminals.
+const DEFAULT_RULES: &[(Kind, Option<u32>, Option<u32>, u32)] = &[
+    (Kind::Char, None, None, ACCESS_MKNOD),
+    (Kind::Block, None, None, ACCESS_MKNOD),
+    (Kind::Char, Some(1), Some(3), ACCESS_ALL),
+    (Kind::Char, Some(1), Some(5), ACCESS_ALL),
+    (Kind::Char, Some(1), Some(7), ACCESS_ALL),
+    (Kind::Char, Some(1), Some(8), ACCESS_ALL),
+    (Kind::Char, Some(1), Some(9), ACCESS_ALL),
+    (Kind::Char, Some(5), Some(0), ACCESS_ALL),
+    (Kind::Char, Some(5), Some(2), ACCESS_ALL),
+    (Kind::Char, Some(136), None, ACCESS_ALL),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    All,
+    Char,
+    Block,
+}
+
+// One entry of the device access list. Rules are checked in order and the
+// last one matching a device decides; unset numbers match any device.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    allow: bool,
+    kind: Kind,
+    major: Option<u32>,
+    minor: Option<u32>,
+    access: u32,
+}
+
+impl Rule {
+    fn from_config(config: &DeviceRule) -> Result<Rule, Error> {
+        let kind = match config.device_type().unwrap_or("a") {
+            "a" => Kind::All,
+            "c" => Kind::Char,
+            "b" => Kind::Block,
+            other => Err(Error::from("invalid device rule type".to_string())).context(other.to_string())?,
+        };
+        let mut access = 0;
+        for c in config.access().unwrap_or("rwm").chars() {
+            access |= match c {
+                'r' => ACCESS_READ,
+                'w' => ACCESS_WRITE,
+                'm' => ACCESS_MKNOD,
+                _ => Err(Error::from("invalid device rule access".to_string())).context(c.to_string())?,
+            };
+        }
+        Ok(Rule {
+            allow: config.allow(),
+            kind,
+            major: number(config.major())?,
+            minor: number(config.minor())?,
+            access,
+        })
+    }
+
+    fn new(allow: bool, kind: Kind, major: Option<u32>, minor: Option<u32>, access: u32) -> Rule {
+        Rule { allow, kind, major, minor, access }
+    }
+
+    fn matches_everything(&self) -> bool {
+        self.kind == Kind::All && self.major.is_none() && self.minor.is_none() && self.access == ACCESS_ALL
+    }
+
+    // v1 reads "a" alone as every device with every access.
+    pub fn to_v1(&self) -> (String, String) {
+        let file = match self.allow {
+            true => "devices.allow",
+            false => "devices.deny",
+        };
+        if self.matches_everything() {
+            return (file.to_string(), "a".to_string());
+        }
+
+        let kind = match self.kind {
+            Kind::All => "a",
+            Kind::Char => "c",
+            Kind::Block => "b",
+        };
+        let number = |n: Option<u32>| n.map_or("*".to_string(), |n| n.to_string());
+        let access: String = [(ACCESS_READ, 'r'), (ACCESS_WRITE, 'w'), (ACCESS_MKNOD, 'm')].iter()
+            .filter(|(bit, _)| self.access & bit != 0)
+            .map(|(_, c)| *c)
+            .collect();
+        (file.to_string(), format!("{} {}:{} {}", kind, number(self.major), number(self.minor), access))
+    }
+}
+
+// OCI leaves wildcards out, runc also writes them as -1.
+fn number(value: Option<i64>) -> Result<Option<u32>, Error> {
+    match value {
+        Some(n) if n < 0 => Ok(None),
+        Some(n) if n > i64::from(u32::MAX) => Err(Error::from("invalid device number".to_string())).context(n.to_string())?,
+        Some(n) => Ok(Some(n as u32)),
+        None => Ok(None),
+    }
+}
+
+// Everything is denied unless allowed, then the config rules apply and the
+// default devices come last so they cannot be taken away.
+pub fn rules(resources: &Resources) -> Result<Vec<Rule>, Error> {
+    let mut rules = vec![Rule::new(false, Kind::All, None, None, ACCESS_ALL)];
+    for config in resources.devices() {
+        rules.push(Rule::from_config(config)?);
+    }
+    for (kind, major, minor, access) in DEFAULT_RULES {
+        rules.push(Rule::new(true, *kind, *major, *minor, *access));
+    }
+    Ok(rules)
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Insn {
+    code: u8,
+    regs: u8,
+    off: i16,
+    imm: i32,
+}
+
+fn insn(code: u8, dst: u8, src: u8, off: i16, imm: i32) -> Insn {
+    Insn { code, regs: (src << 4) | dst, off, imm }
+}
+
+// Builds a BPF_PROG_TYPE_CGROUP_DEVICE program out of the rules. Rules are
+// checked from the last one, each as a block that jumps past itself when
+// the device doesn't match. A rule matching everything ends the program,
+// the verifier rejects code that cannot be reached.
+pub fn program(rules: &[Rule]) -> Vec<Insn> {
+    let mut program = vec![
+        insn(BPF_LDX_MEM_W, 2, 1, 0, 0),
+        insn(BPF_ALU_AND_K, 2, 0, 0, 0xffff),
+        insn(BPF_LDX_MEM_W, 3, 1, 0, 0),
+        insn(BPF_ALU_RSH_K, 3, 0, 0, 16),
+        insn(BPF_LDX_MEM_W, 4, 1, 4, 0),
+        insn(BPF_LDX_MEM_W, 5, 1, 8, 0),
+    ];
+
+    for rule in rules.iter().rev() {
+        let mut checks = Vec::new();
+        match rule.kind {
+            Kind::All => (),
+            Kind::Char => checks.push(insn(BPF_JMP_JNE_K, 2, 0, 0, DEV_CHAR)),
+            Kind::Block => checks.push(insn(BPF_JMP_JNE_K, 2, 0, 0, DEV_BLOCK)),
+        }
+        if rule.access != ACCESS_ALL {
+            checks.push(insn(BPF_ALU_MOV_X, 1, 3, 0, 0));
+            checks.push(insn(BPF_ALU_AND_K, 1, 0, 0, rule.access as i32));
+            checks.push(insn(BPF_JMP_JNE_X, 1, 3, 0, 0));
+        }
+        if let Some(major) = rule.major {
+            checks.push(insn(BPF_JMP_JNE_K, 4, 0, 0, major as i32));
+        }
+        if let Some(minor) = rule.minor {
+            checks.push(insn(BPF_JMP_JNE_K, 5, 0, 0, minor as i32));
+        }
+
+        let unconditional = checks.is_empty();
+        let len = checks.len() + 2;
+        for (i, mut check) in checks.into_iter().enumerate() {
+            if check.code == BPF_JMP_JNE_K || check.code == BPF_JMP_JNE_X {
+                check.off = (len - i - 1) as i16;
+            }
+            program.push(check);
+        }
+        program.push(insn(BPF_ALU64_MOV_K, 0, 0, 0, rule.allow as i32));
+        program.push(insn(BPF_JMP_EXIT, 0, 0, 0, 0));
+        if unconditional {
+            return program;
+        }
+    }
+
+    program.push(insn(BPF_ALU64_MOV_K, 0, 0, 0, 0));
+    program.push(insn(BPF_JMP_EXIT, 0, 0, 0, 0));
+    program
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct ProgLoadAttr {
+    prog_type: u32,
+    insn_cnt: u32,
+    insns: u64,
+    license: u64,
+    log_level: u32,
+    log_size: u32,
+    log_buf: u64,
+    kern_version: u32,
+    prog_flags: u32,
+    prog_name: [u8; 16],
+    prog_ifindex: u32,
+    expected_attach_type: u32,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct ProgAttachAttr {
+    target_fd: u32,
+    attach_bpf_fd: u32,
+    attach_type: u32,
+    attach_flags: u32,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct ProgQueryAttr {
+    target_fd: u32,
+    attach_type: u32,
+    query_flags: u32,
+    attach_flags: u32,
+    prog_ids: u64,
+    prog_cnt: u32,
+    padding: u32,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct ProgGetFdByIdAttr {
+    prog_id: u32,
+    next_id: u32,
+    open_flags: u32,
+}
+
+fn bpf<T>(cmd: libc::c_long, attr: &mut T) -> io::Result<libc::c_long> {
+    let result = unsafe {
+        libc::syscall(libc::SYS_bpf, cmd, attr as *mut T, mem::size_of::<T>() as libc::c_uint)
+    };
+    match result {
+        result if result < 0 => Err(io::Error::last_os_error()),
+        result => Ok(result),
+    }
+}
+
+// The device programs attached to the cgroup itself, not the ones it
+// inherits.
+fn attached_programs(cgroup: &File) -> io::Result<Vec<File>> {
+    let mut ids = [0u32; MAX_ATTACHED_PROGRAMS];
+    let mut query = ProgQueryAttr {
+        target_fd: cgroup.as_raw_fd() as u32,
+        attach_type: BPF_CGROUP_DEVICE,
+        prog_ids: ids.as_mut_ptr() as u64,
+        prog_cnt: ids.len() as u32,
+        ..Default::default()
+    };
+    bpf(BPF_PROG_QUERY, &mut query)?;
+
+    ids[..query.prog_cnt as usize].iter().map(|id| {
+        let mut get_fd = ProgGetFdByIdAttr { prog_id: *id, ..Default::default() };
+        let fd = bpf(BPF_PROG_GET_FD_BY_ID, &mut get_fd)?;
+        Ok(unsafe { File::from_raw_fd(fd as i32) })
+    }).collect()
+}
+
+// cgroup v2 has no device files, access is checked by a program attached to
+// the cgroup. The attached program stays alive with the cgroup, so its fd
+// is closed right away. Programs of an earlier container in a shared cgroup
+// would still deny what the new rules allow, so they are detached once the
+// new one is in place.
+pub fn attach(cgroup_dir: &Path, rules: &[Rule]) -> Result<(), Error> {
+    let program = program(rules);
+    let mut load = ProgLoadAttr {
+        prog_type: BPF_PROG_TYPE_CGROUP_DEVICE,
+        insn_cnt: program.len() as u32,
+        insns: program.as_ptr() as u64,
+        license: LICENSE.as_ptr() as u64,
+        ..Default::default()
+    };
+    let program_fd = bpf(BPF_PROG_LOAD, &mut load).context("cannot load cgroup device program".to_string())?;
+    let program_fd = unsafe { File::from_raw_fd(program_fd as i32) };
+
+    let cgroup = File::open(cgroup_dir).context(format!("cannot open cgroup {:?}", cgroup_dir))?;
+    let previous = attached_programs(&cgroup).context(format!("cannot query device programs of cgroup {:?}", cgroup_dir))?;
+    let mut attach = ProgAttachAttr {
+        target_fd: cgroup.as_raw_fd() as u32,
+        attach_bpf_fd: program_fd.as_raw_fd() as u32,
+        attach_type: BPF_CGROUP_DEVICE,
+        attach_flags: BPF_F_ALLOW_MULTI,
+    };
+    bpf(BPF_PROG_ATTACH, &mut attach).context(format!("cannot attach device program to cgroup {:?}", cgroup_dir))?;
+
+    for program in previous {
+        let mut detach = ProgAttachAttr {
+            target_fd: cgroup.as_raw_fd() as u32,
+            attach_bpf_fd: program.as_raw_fd() as u32,
+            attach_type: BPF_CGROUP_DEVICE,
+            ..Default::default()
+        };
+        bpf(BPF_PROG_DETACH, &mut detach).context(format!("cannot detach previous device program from cgroup {:?}", cgroup_dir))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn resources(value: serde_json::Value) -> Resources {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn v1(rules: &[Rule]) -> Vec<String> {
+        rules.iter().map(|rule| {
+            let (file, value) = rule.to_v1();
+            format!("{} {}", file, value)
+        }).collect()
+    }
+
+    #[test]
+    fn rules_deny_everything_then_apply_config_and_defaults() {
+        let rules = rules(&resources(json!({"devices": [
+            {"allow": true, "type": "b", "major": 8, "minor": -1, "access": "r"},
+            {"allow": false, "type": "c", "major": 10, "minor": 229},
+        ]}))).unwrap();
+        let lines = v1(&rules);
+
+        assert_eq!(&lines[..3], &["devices.deny a", "devices.allow b 8:* r", "devices.deny c 10:229 rwm"]);
+        assert_eq!(&lines[3..5], &["devices.allow c *:* m", "devices.allow b *:* m"]);
+        assert!(lines.contains(&"devices.allow c 1:3 rwm".to_string()));
+        assert!(lines.contains(&"devices.allow c 136:* rwm".to_string()));
+    }
+
+    #[test]
+    fn rules_returns_error_with_invalid_type_or_access() {
+        assert!(rules(&resources(json!({"devices": [{"allow": true, "type": "p"}]}))).is_err());
+        assert!(rules(&resources(json!({"devices": [{"allow": true, "access": "rx"}]}))).is_err());
+    }
+
+    #[test]
+    fn program_checks_rules_from_the_last_one() {
+        let rules = vec![
+            Rule::new(false, Kind::All, None, None, ACCESS_ALL),
+            Rule::new(true, Kind::Char, Some(1), Some(3), ACCESS_READ | ACCESS_WRITE),
+        ];
+        let program = program(&rules);
+
+        assert_eq!(program.len(), 6 + 8 + 2);
+        assert_eq!(program[6], insn(BPF_JMP_JNE_K, 2, 0, 7, DEV_CHAR));
+        assert_eq!(program[9], insn(BPF_JMP_JNE_X, 1, 3, 4, 0));
+        assert_eq!(program[12], insn(BPF_ALU64_MOV_K, 0, 0, 0, 1));
+        assert_eq!(&program[14..], &[insn(BPF_ALU64_MOV_K, 0, 0, 0, 0), insn(BPF_JMP_EXIT, 0, 0, 0, 0)]);
+    }
+
+    #[test]
+    fn program_stops_at_a_rule_matching_everything() {
+        let rules = vec![
+            Rule::new(true, Kind::Char, Some(1), Some(3), ACCESS_ALL),
+            Rule::new(true, Kind::All, None, None, ACCESS_ALL),
+        ];
+        let program = program(&rules);
+
+        assert_eq!(program.len(), 6 + 2);
+        assert_eq!(program[6], insn(BPF_ALU64_MOV_K, 0, 0, 0, 1));
+    }
+
+    #[test]
+    fn attach_replaces_the_program_of_an_earlier_start() {
+        let root = ["/sys/fs/cgroup/unified", "/sys/fs/cgroup"].iter()
+            .map(Path::new)
+            .find(|root| root.join("cgroup.controllers").exists());
+        let root = match root {
+            Some(root) => root,
+            None => return,
+        };
+        let dir = root.join(format!("plankton-devices-test-{}", std::process::id()));
+        std::fs::create_dir(&dir).unwrap();
+
+        let attached = |rules: &[Rule]| {
+            attach(&dir, rules).unwrap();
+            attached_programs(&File::open(&dir).unwrap()).unwrap().len()
+        };
+        let first = attached(&rules(&Resources::default()).unwrap());
+        let second = attached(&rules(&resources(json!({"devices": [{"allow": true, "type": "c", "major": 10, "minor": 200}]}))).unwrap());
+        std::fs::remove_dir(&dir).unwrap();
+
+        assert_eq!((first, second), (1, 1));
+    }
+}
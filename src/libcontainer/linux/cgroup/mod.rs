@@ -0,0 +1,287 @@
+pub mod devices;
+pub mod oom;
+pub mod stats;
+pub mod v1;
+pub mod v2;
+
+use crate::Error;
+use crate::config::linux::Resources;
+use serde::Deserialize;
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+use failure::ResultExt;
+
+pub const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+pub const CGROUP_PARENT: &str = "plankton";
+
+// Containers with this annotation set to "true" may join a cgroup that
+// already has processes, and leave it in place when they exit.
+pub const SHARED_ANNOTATION: &str = "org.plankton.cgroup.shared";
+
+// Set to "true" to have the OOM killer take every process of the container
+// at once instead of picking one, through memory.oom.group.
+pub const OOM_GROUP_ANNOTATION: &str = "org.plankton.cgroup.oom-group";
+
+const MOUNTINFO: &str = "/proc/self/mountinfo";
+
+// Options of v1 mounts that are not controllers. Options with a value, like
+// name=systemd, are left out too.
+const MOUNT_OPTIONS: &[&str] = &["rw", "ro", "xattr", "noprefix", "clone_children", "cpuset_v2_mode", "favordynmods"];
+
+const REMOVE_RETRIES: u32 = 50;
+const REMOVE_RETRY_DELAY: Duration = Duration::from_millis(10);
+
+// What the rest of the runtime needs from a container cgroup, whichever
+// hierarchy the host uses.
+pub trait CgroupManager: fmt::Debug {
+    fn create(&self, resources: &Resources) -> Result<(), Error>;
+    fn set_devices(&self, rules: &[devices::Rule]) -> Result<(), Error>;
+    fn set_oom_group(&self) -> Result<(), Error>;
+    fn add_process(&self, pid: i32) -> Result<(), Error>;
+    fn has_processes(&self) -> Result<bool, Error>;
+    fn stats(&self) -> Result<stats::Stats, Error>;
+    fn watch_oom(&self) -> Result<Option<oom::OomWatch>, Error>;
+    fn remove(&self) -> Result<(), Error>;
+}
+
+// Unified hosts only mount cgroup v2 at the root. Hybrid hosts mount the v1
+// controllers there and cgroup v2 without controllers below it, legacy
+// hosts only have v1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    Unified,
+    Hybrid,
+    Legacy,
+}
+
+// A mounted cgroup hierarchy along with the v1 controllers bound to it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Hierarchy {
+    mount_point: PathBuf,
+    unified: bool,
+    controllers: Vec<String>,
+}
+
+impl Hierarchy {
+    pub fn mount_point(&self) -> &Path { &self.mount_point }
+    pub fn is_unified(&self) -> bool { self.unified }
+
+    pub fn has_controller(&self, controller: &str) -> bool {
+        self.controllers.iter().any(|c| c == controller)
+    }
+}
+
+pub fn mode(hierarchies: &[Hierarchy], root: &Path) -> Option<Mode> {
+    if hierarchies.iter().any(|hierarchy| hierarchy.unified && hierarchy.mount_point == root) {
+        return Some(Mode::Unified);
+    }
+    match (hierarchies.iter().any(|h| !h.unified), hierarchies.iter().any(|h| h.unified)) {
+        (true, true) => Some(Mode::Hybrid),
+        (true, false) => Some(Mode::Legacy),
+        _ => None,
+    }
+}
+
+// The cgroup mounts at or below `root`, in mount order.
+pub fn hierarchies(mountinfo: &str, root: &Path) -> Vec<Hierarchy> {
+    let mut hierarchies = Vec::new();
+    for line in mountinfo.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let separator = match fields.iter().position(|field| *field == "-") {
+            Some(separator) if fields.len() > separator + 3 && separator > 4 => separator,
+            _ => continue,
+        };
+        let mount_point = PathBuf::from(fields[4]);
+        let unified = match fields[separator + 1] {
+            "cgroup2" => true,
+            "cgroup" => false,
+            _ => continue,
+        };
+        if !mount_point.starts_with(root) {
+            continue;
+        }
+        let controllers = match unified {
+            true => Vec::new(),
+            false => fields[separator + 3].split(',')
+                .filter(|option| !MOUNT_OPTIONS.contains(option) && !option.contains('='))
+                .map(String::from)
+                .collect(),
+        };
+        hierarchies.retain(|hierarchy: &Hierarchy| hierarchy.mount_point != mount_point);
+        hierarchies.push(Hierarchy { mount_point, unified, controllers });
+    }
+    hierarchies
+}
+
+fn host_hierarchies() -> Result<Vec<Hierarchy>, Error> {
+    let mountinfo = fs::read_to_string(MOUNTINFO).context(format!("cannot read {}", MOUNTINFO))?;
+    Ok(hierarchies(&mountinfo, Path::new(CGROUP_ROOT)))
+}
+
+// Where a container cgroup goes, relative to the cgroup root. Absolute
+// cgroupsPath values start at the root and relative ones at the runtime
+// parent, which also holds the cgroups of containers without one.
+pub fn container_path(cgroups_path: Option<&str>, parent: &str, container_id: &str) -> Result<PathBuf, Error> {
+    let path = match cgroups_path {
+        Some(cgroups_path) if cgroups_path.starts_with('/') => PathBuf::from(cgroups_path),
+        Some(cgroups_path) => Path::new(parent).join(cgroups_path),
+        None => Path::new(parent).join(container_id),
+    };
+
+    let mut relative = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::RootDir | Component::CurDir => continue,
+            Component::Normal(name) => relative.push(name),
+            _ => Err(Error::from("cgroup path cannot leave the cgroup root".to_string())).context(format!("{:?}", path))?,
+        }
+    }
+    if relative.as_os_str().is_empty() {
+        Err(Error::from("cgroup path cannot be the cgroup root".to_string())).context(format!("{:?}", path))?;
+    }
+    Ok(relative)
+}
+
+// Hosts without cgroups can still run containers that ask for no resource
+// limits, they just don't get a cgroup.
+pub fn for_container(path: &Path, resources: &Resources) -> Result<Option<Box<dyn CgroupManager>>, Error> {
+    match manager(path)? {
+        None if *resources != Resources::default() => {
+            Err(Error::from("linux.resources needs cgroups mounted".to_string())).context(CGROUP_ROOT.to_string())?
+        },
+        manager => Ok(manager),
+    }
+}
+
+// Without device rules in the config only the defaults are at stake, so
+// hosts that cannot restrict devices, v1 without the devices hierarchy or
+// v2 where loading BPF programs is not allowed, still run the container.
+pub fn set_devices(cgroup: &dyn CgroupManager, resources: &Resources) -> Result<(), Error> {
+    let rules = devices::rules(resources)?;
+    match cgroup.set_devices(&rules) {
+        Err(err) if resources.devices().is_empty() => {
+            eprintln!("Warning: default device rules not applied: {}", err);
+            Ok(())
+        },
+        result => result,
+    }
+}
+
+// The cgroup of a container that is already running.
+pub fn load(path: &Path) -> Result<Box<dyn CgroupManager>, Error> {
+    match manager(path)? {
+        Some(manager) => Ok(manager),
+        None => Err(Error::from("no cgroups mounted".to_string())).context(CGROUP_ROOT.to_string())?,
+    }
+}
+
+fn manager(path: &Path) -> Result<Option<Box<dyn CgroupManager>>, Error> {
+    let root = Path::new(CGROUP_ROOT);
+    let hierarchies = host_hierarchies()?;
+    match mode(&hierarchies, root) {
+        Some(Mode::Unified) => Ok(Some(Box::new(v2::Cgroup::new(root, &root.join(path))))),
+        Some(Mode::Hybrid) | Some(Mode::Legacy) => Ok(Some(Box::new(v1::Cgroup::new(hierarchies, path)))),
+        None => Ok(None),
+    }
+}
+
+// The roots container cgroup paths are relative to, one per hierarchy in use.
+pub fn root_dirs() -> Result<Vec<PathBuf>, Error> {
+    let root = Path::new(CGROUP_ROOT);
+    let hierarchies = host_hierarchies()?;
+    let dirs = match mode(&hierarchies, root) {
+        Some(Mode::Unified) => vec![root.to_path_buf()],
+        Some(_) => hierarchies.iter().map(|hierarchy| hierarchy.mount_point.clone()).collect(),
+        None => Vec::new(),
+    };
+    Ok(dirs)
+}
+
+fn has_processes(dir: &Path) -> Result<bool, Error> {
+    let procs = dir.join("cgroup.procs");
+    match fs::read_to_string(&procs) {
+        Ok(content) => Ok(!content.trim().is_empty()),
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(err).context(format!("cannot read {:?}", procs))?,
+    }
+}
+
+// Removing a cgroup fails while the processes in it are still exiting.
+fn remove_dir(path: &Path) -> Result<(), Error> {
+    let mut retries = 0;
+    loop {
+        match fs::remove_dir(path) {
+            Ok(_) => return Ok(()),
+            Err(ref err) if err.raw_os_error() == Some(libc::EBUSY) && retries < REMOVE_RETRIES => {
+                retries += 1;
+                thread::sleep(REMOVE_RETRY_DELAY);
+            },
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => Err(err).context(format!("cannot remove cgroup {:?}", path))?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HYBRID: &str = "\
+32 24 0:28 / /sys/fs/cgroup rw,relatime - tmpfs tmpfs rw,mode=755
+33 32 0:29 / /sys/fs/cgroup/cpu,cpuacct rw,relatime - cgroup cgroup rw,cpu,cpuacct
+36 32 0:32 / /sys/fs/cgroup/memory rw,relatime - cgroup cgroup rw,memory
+41 32 0:37 / /sys/fs/cgroup/systemd rw,relatime - cgroup cgroup rw,xattr,name=systemd
+42 32 0:38 / /sys/fs/cgroup/unified rw,relatime - cgroup2 cgroup2 rw,nsdelegate
+50 24 0:40 / /mnt/cgroup rw,relatime - cgroup cgroup rw,pids";
+
+    #[test]
+    fn hierarchies_parses_cgroup_mounts_below_root() {
+        let hierarchies = hierarchies(HYBRID, Path::new("/sys/fs/cgroup"));
+        let mount_points: Vec<&Path> = hierarchies.iter().map(|h| h.mount_point()).collect();
+
+        assert_eq!(mount_points, vec![
+            Path::new("/sys/fs/cgroup/cpu,cpuacct"),
+            Path::new("/sys/fs/cgroup/memory"),
+            Path::new("/sys/fs/cgroup/systemd"),
+            Path::new("/sys/fs/cgroup/unified"),
+        ]);
+        assert!(hierarchies[0].has_controller("cpu") && hierarchies[0].has_controller("cpuacct"));
+        assert_eq!(hierarchies[1].controllers, vec!["memory"]);
+        assert!(hierarchies[2].controllers.is_empty());
+        assert!(hierarchies[3].is_unified());
+    }
+
+    #[test]
+    fn container_path_resolves_cgroups_path_against_root_or_parent() {
+        assert_eq!(container_path(None, "/plankton", "c1").unwrap(), Path::new("plankton/c1"));
+        assert_eq!(container_path(Some("/tenants/a/c1"), "/plankton", "c1").unwrap(), Path::new("tenants/a/c1"));
+        assert_eq!(container_path(Some("tenant-a/c1"), "/plankton", "c1").unwrap(), Path::new("plankton/tenant-a/c1"));
+        assert_eq!(container_path(Some("./c1"), "nodes", "c1").unwrap(), Path::new("nodes/c1"));
+    }
+
+    #[test]
+    fn container_path_returns_error_when_path_escapes_root() {
+        assert!(container_path(Some("/"), "/plankton", "c1").is_err());
+        assert!(container_path(Some("../../etc"), "/plankton", "c1").is_err());
+        assert!(container_path(Some("/a/../.."), "/plankton", "c1").is_err());
+    }
+
+    #[test]
+    fn mode_detects_unified_hybrid_and_legacy_hosts() {
+        let root = Path::new("/sys/fs/cgroup");
+        let unified = format!("{}\n60 24 0:41 / /sys/fs/cgroup rw - cgroup2 cgroup2 rw", HYBRID);
+        let legacy: String = HYBRID.lines().filter(|line| !line.contains("cgroup2")).collect::<Vec<_>>().join("\n");
+
+        assert_eq!(mode(&hierarchies(HYBRID, root), root), Some(Mode::Hybrid));
+        assert_eq!(mode(&hierarchies(&unified, root), root), Some(Mode::Unified));
+        assert_eq!(mode(&hierarchies(&legacy, root), root), Some(Mode::Legacy));
+        assert_eq!(mode(&[], root), None);
+    }
+}
@@ -0,0 +1,96 @@
+use crate::Error;
+use super::stats;
+use std::ffi::CString;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::io::FromRawFd;
+use std::path::Path;
+use std::path::PathBuf;
+use failure::ResultExt;
+
+// Wakes up when the kernel reports a memory event for a cgroup. Both v1
+// memory.oom_control and v2 memory.events keep an oom_kill counter, so a
+// wake up only means the counter may have moved.
+#[derive(Debug)]
+pub struct OomWatch {
+    fd: File,
+    counter: PathBuf,
+}
+
+impl OomWatch {
+    // memory.events is modified on every memory event of the cgroup or its
+    // descendants.
+    pub fn inotify(events: &Path) -> Result<OomWatch, Error> {
+        let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+        if fd < 0 {
+            Err(io::Error::last_os_error()).context("cannot create inotify instance".to_string())?;
+        }
+        let fd = unsafe { File::from_raw_fd(fd) };
+
+        let path = CString::new(events.as_os_str().as_bytes()).context(format!("invalid path {:?}", events))?;
+        if unsafe { libc::inotify_add_watch(fd.as_raw_fd(), path.as_ptr(), libc::IN_MODIFY) } < 0 {
+            Err(io::Error::last_os_error()).context(format!("cannot watch {:?}", events))?;
+        }
+        Ok(OomWatch { fd, counter: events.to_path_buf() })
+    }
+
+    // v1 signals an eventfd registered in cgroup.event_control when the
+    // cgroup hits its limit, and again when the cgroup is removed.
+    pub fn eventfd(memory_dir: &Path) -> Result<OomWatch, Error> {
+        let fd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC) };
+        if fd < 0 {
+            Err(io::Error::last_os_error()).context("cannot create eventfd".to_string())?;
+        }
+        let fd = unsafe { File::from_raw_fd(fd) };
+
+        let counter = memory_dir.join("memory.oom_control");
+        let oom_control = File::open(&counter).context(format!("cannot open {:?}", counter))?;
+        let event_control = memory_dir.join("cgroup.event_control");
+        let registration = format!("{} {}", fd.as_raw_fd(), oom_control.as_raw_fd());
+        fs::write(&event_control, registration).context(format!("cannot register oom event in {:?}", event_control))?;
+        Ok(OomWatch { fd, counter })
+    }
+
+    pub fn try_clone(&self) -> Result<OomWatch, Error> {
+        let fd = self.fd.try_clone().context("cannot duplicate oom watch".to_string())?;
+        Ok(OomWatch { fd, counter: self.counter.clone() })
+    }
+
+    // Kernels before 4.13 don't count v1 OOM kills.
+    pub fn kills(&self) -> Result<u64, Error> {
+        let counters = stats::read_keyed(&self.counter)?;
+        Ok(counters.get("oom_kill").cloned().unwrap_or(0))
+    }
+
+    // Blocks until the next event. Big enough for an inotify event, eventfd
+    // reads only take 8 bytes of it.
+    pub fn wait(&self) -> Result<(), Error> {
+        let mut buffer = [0u8; 4096];
+        (&self.fd).read(&mut buffer).context(format!("cannot wait for memory events of {:?}", self.counter))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn oom_watch_wakes_up_when_memory_events_change() {
+        let dir = tempdir().unwrap();
+        let events = dir.path().join("memory.events");
+        fs::write(&events, "low 0\nhigh 0\nmax 0\noom 0\noom_kill 0\n").unwrap();
+
+        let watch = OomWatch::inotify(&events).unwrap();
+        assert_eq!(watch.kills().unwrap(), 0);
+
+        fs::write(&events, "low 0\nhigh 0\nmax 2\noom 1\noom_kill 1\n").unwrap();
+        watch.try_clone().unwrap().wait().unwrap();
+        assert_eq!(watch.kills().unwrap(), 1);
+    }
+}
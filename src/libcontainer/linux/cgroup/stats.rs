@@ -0,0 +1,297 @@
+use crate::Error;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use failure::ResultExt;
+
+// Some limits read back as the largest page counter instead of "max", a
+// value that depends on the page size. Nothing real comes close to it.
+const UNLIMITED: u64 = 1 << 62;
+
+// A snapshot of what a container cgroup used, the same for v1 and v2.
+// Sizes are in bytes and times in nanoseconds. Values the host doesn't
+// track are left out.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Stats {
+    pub memory: MemoryStats,
+    pub cpu: CpuStats,
+    pub pids: PidsStats,
+    pub io: Vec<IoStats>,
+    pub hugetlb: Vec<HugetlbStats>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pressure: Option<Pressure>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemoryStats {
+    pub usage: u64,
+    pub max_usage: Option<u64>,
+    pub limit: Option<u64>,
+    pub cache: u64,
+    pub swap_usage: Option<u64>,
+    pub swap_limit: Option<u64>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CpuStats {
+    pub usage: u64,
+    pub user: u64,
+    pub system: u64,
+    pub periods: u64,
+    pub throttled_periods: u64,
+    pub throttled_time: u64,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PidsStats {
+    pub current: u64,
+    pub limit: Option<u64>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IoStats {
+    pub major: u64,
+    pub minor: u64,
+    pub read_bytes: u64,
+    pub write_bytes: u64,
+    pub read_ops: u64,
+    pub write_ops: u64,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HugetlbStats {
+    pub page_size: String,
+    pub usage: u64,
+    pub limit: Option<u64>,
+}
+
+// Pressure stall information, only kept by cgroup v2.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Pressure {
+    pub cpu: Option<PressureStats>,
+    pub memory: Option<PressureStats>,
+    pub io: Option<PressureStats>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PressureStats {
+    pub some: PressureValues,
+    pub full: Option<PressureValues>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PressureValues {
+    pub avg10: f64,
+    pub avg60: f64,
+    pub avg300: f64,
+    pub total: u64,
+}
+
+impl Stats {
+    pub fn io_device(&mut self, major: u64, minor: u64) -> &mut IoStats {
+        let position = match self.io.iter().position(|io| io.major == major && io.minor == minor) {
+            Some(position) => position,
+            None => {
+                self.io.push(IoStats { major, minor, ..Default::default() });
+                self.io.len() - 1
+            },
+        };
+        &mut self.io[position]
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let limit = |value: Option<u64>| value.map_or("max".to_string(), |value| value.to_string());
+        let optional = |value: Option<u64>| value.map_or("-".to_string(), |value| value.to_string());
+
+        let mut lines = vec![
+            format!("memory usage {} max_usage {} limit {} cache {}", self.memory.usage, optional(self.memory.max_usage), limit(self.memory.limit), self.memory.cache),
+            format!("swap usage {} limit {}", optional(self.memory.swap_usage), limit(self.memory.swap_limit)),
+            format!("cpu usage {} user {} system {}", self.cpu.usage, self.cpu.user, self.cpu.system),
+            format!("cpu periods {} throttled_periods {} throttled_time {}", self.cpu.periods, self.cpu.throttled_periods, self.cpu.throttled_time),
+            format!("pids current {} limit {}", self.pids.current, limit(self.pids.limit)),
+        ];
+        for io in &self.io {
+            lines.push(format!("io {}:{} read_bytes {} write_bytes {} read_ops {} write_ops {}", io.major, io.minor, io.read_bytes, io.write_bytes, io.read_ops, io.write_ops));
+        }
+        for hugetlb in &self.hugetlb {
+            lines.push(format!("hugetlb {} usage {} limit {}", hugetlb.page_size, hugetlb.usage, limit(hugetlb.limit)));
+        }
+        if let Some(pressure) = &self.pressure {
+            let resources = [("cpu", &pressure.cpu), ("memory", &pressure.memory), ("io", &pressure.io)];
+            for (name, stats) in resources.iter() {
+                if let Some(stats) = stats {
+                    let mut kinds = vec![("some", &stats.some)];
+                    if let Some(full) = &stats.full {
+                        kinds.push(("full", full));
+                    }
+                    for (kind, values) in kinds {
+                        lines.push(format!("pressure {} {} avg10 {:.2} avg60 {:.2} avg300 {:.2} total {}", name, kind, values.avg10, values.avg60, values.avg300, values.total));
+                    }
+                }
+            }
+        }
+
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+// Controller files the kernel doesn't have read as missing values.
+pub fn read(path: &Path) -> Result<Option<String>, Error> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content.trim().to_string())),
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err).context(format!("cannot read {:?}", path))?,
+    }
+}
+
+// Reads a single number, "max" meaning there is no limit.
+pub fn read_u64(path: &Path) -> Result<Option<u64>, Error> {
+    match read(path)? {
+        Some(ref content) if content == "max" => Ok(None),
+        Some(content) => Ok(Some(parse_u64(&content, path)?)),
+        None => Ok(None),
+    }
+}
+
+pub fn read_limit(path: &Path) -> Result<Option<u64>, Error> {
+    Ok(read_u64(path)?.filter(|limit| *limit < UNLIMITED))
+}
+
+pub fn parse_u64(value: &str, path: &Path) -> Result<u64, Error> {
+    let number = value.parse::<u64>().context(format!("invalid value {:?} in {:?}", value, path))?;
+    Ok(number)
+}
+
+// Reads "key value" lines, like memory.stat and cpu.stat.
+pub fn read_keyed(path: &Path) -> Result<BTreeMap<String, u64>, Error> {
+    let mut values = BTreeMap::new();
+    for line in read(path)?.unwrap_or_default().lines() {
+        let mut fields = line.split_whitespace();
+        if let (Some(key), Some(value)) = (fields.next(), fields.next()) {
+            values.insert(key.to_string(), parse_u64(value, path)?);
+        }
+    }
+    Ok(values)
+}
+
+// Pressure files are in the cgroup v2 directory of the container, which
+// hybrid hosts have too.
+pub fn read_pressure(dir: &Path) -> Result<Option<Pressure>, Error> {
+    let pressure = Pressure {
+        cpu: read_pressure_file(&dir.join("cpu.pressure"))?,
+        memory: read_pressure_file(&dir.join("memory.pressure"))?,
+        io: read_pressure_file(&dir.join("io.pressure"))?,
+    };
+    match pressure == Pressure::default() {
+        true => Ok(None),
+        false => Ok(Some(pressure)),
+    }
+}
+
+fn read_pressure_file(path: &Path) -> Result<Option<PressureStats>, Error> {
+    let content = match read(path)? {
+        Some(content) => content,
+        None => return Ok(None),
+    };
+    let mut stats = PressureStats::default();
+    for line in content.lines() {
+        let mut fields = line.split_whitespace();
+        let kind = fields.next().unwrap_or("");
+        let mut values = PressureValues::default();
+        for field in fields {
+            let (key, value) = match field.find('=') {
+                Some(separator) => (&field[..separator], &field[separator + 1..]),
+                None => continue,
+            };
+            let invalid = format!("invalid pressure value {:?} in {:?}", field, path);
+            match key {
+                "avg10" => values.avg10 = value.parse::<f64>().context(invalid)?,
+                "avg60" => values.avg60 = value.parse::<f64>().context(invalid)?,
+                "avg300" => values.avg300 = value.parse::<f64>().context(invalid)?,
+                "total" => values.total = value.parse::<u64>().context(invalid)?,
+                _ => (),
+            }
+        }
+        match kind {
+            "some" => stats.some = values,
+            "full" => stats.full = Some(values),
+            _ => (),
+        }
+    }
+    Ok(Some(stats))
+}
+
+// The page sizes hugetlb files are kept for, from files named like
+// hugetlb.2MB.<suffix>.
+pub fn hugepage_sizes(dir: &Path, suffix: &str) -> Result<Vec<String>, Error> {
+    let mut sizes = Vec::new();
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(sizes),
+        Err(err) => Err(err).context(format!("cannot read cgroup dir {:?}", dir))?,
+    };
+    for entry in entries {
+        let name = entry.context(format!("cannot read cgroup dir {:?}", dir))?.file_name();
+        let name = name.to_string_lossy();
+        let size = name.strip_prefix("hugetlb.").and_then(|name| name.strip_suffix(suffix)).and_then(|name| name.strip_suffix('.'));
+        if let Some(size) = size {
+            if !size.contains('.') {
+                sizes.push(size.to_string());
+            }
+        }
+    }
+    sizes.sort();
+    Ok(sizes)
+}
+
+// Device numbers as cgroup files write them, "8:0".
+pub fn parse_device(device: &str, path: &Path) -> Result<(u64, u64), Error> {
+    match device.find(':') {
+        Some(separator) => Ok((parse_u64(&device[..separator], path)?, parse_u64(&device[separator + 1..], path)?)),
+        None => Err(Error::from("invalid device".to_string())).context(format!("{:?} in {:?}", device, path))?,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn read_pressure_parses_some_and_full_lines() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("cpu.pressure"), "some avg10=0.60 avg60=2.73 avg300=2.37 total=96947191\nfull avg10=0.00 avg60=0.00 avg300=0.00 total=0\n").unwrap();
+        fs::write(dir.path().join("memory.pressure"), "some avg10=1.00 avg60=0.00 avg300=0.00 total=12\n").unwrap();
+
+        let pressure = read_pressure(dir.path()).unwrap().unwrap();
+        let cpu = pressure.cpu.unwrap();
+        assert_eq!((cpu.some.avg10, cpu.some.total), (0.60, 96947191));
+        assert_eq!(cpu.full.unwrap().total, 0);
+        assert_eq!(pressure.memory.unwrap().full, None);
+        assert_eq!(pressure.io, None);
+    }
+
+    #[test]
+    fn read_pressure_returns_none_without_pressure_files() {
+        let dir = tempdir().unwrap();
+        assert_eq!(read_pressure(dir.path()).unwrap(), None);
+    }
+
+    #[test]
+    fn read_u64_treats_max_and_missing_files_as_no_value() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("pids.max"), "max\n").unwrap();
+        fs::write(dir.path().join("pids.current"), "3\n").unwrap();
+
+        assert_eq!(read_u64(&dir.path().join("pids.max")).unwrap(), None);
+        assert_eq!(read_u64(&dir.path().join("pids.current")).unwrap(), Some(3));
+        assert_eq!(read_u64(&dir.path().join("memory.peak")).unwrap(), None);
+    }
+}
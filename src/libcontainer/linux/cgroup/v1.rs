@@ -0,0 +1,502 @@
+use crate::Error;
+use crate::config::linux::Resources;
+use super::CgroupManager;
+use super::devices;
+use super::oom::OomWatch;
+use super::stats;
+use super::stats::HugetlbStats;
+use super::stats::Stats;
+use super::Hierarchy;
+use nix::sys::signal;
+use nix::sys::signal::Signal;
+use nix::unistd::Pid;
+use serde::Deserialize;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use failure::ResultExt;
+
+const CPUSET_FILES: &[&str] = &["cpuset.cpus", "cpuset.mems"];
+
+// A container cgroup on hosts with cgroup v1: one directory at the same
+// path below every mounted hierarchy, so the container is tracked in all
+// of them and limited by the controllers its resources use.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cgroup {
+    hierarchies: Vec<Hierarchy>,
+    path: PathBuf,
+}
+
+impl Cgroup {
+    pub fn new(hierarchies: Vec<Hierarchy>, path: &Path) -> Cgroup {
+        Cgroup {
+            hierarchies,
+            path: path.to_path_buf(),
+        }
+    }
+
+    pub fn path(&self) -> &Path { &self.path }
+
+    fn dir(&self, hierarchy: &Hierarchy) -> PathBuf {
+        hierarchy.mount_point().join(&self.path)
+    }
+
+    fn controller_dir(&self, controller: &str) -> Option<PathBuf> {
+        self.hierarchies.iter().find(|hierarchy| hierarchy.has_controller(controller)).map(|hierarchy| self.dir(hierarchy))
+    }
+
+    fn hierarchy(&self, controller: &str) -> Result<&Hierarchy, Error> {
+        match self.hierarchies.iter().find(|hierarchy| hierarchy.has_controller(controller)) {
+            Some(hierarchy) => Ok(hierarchy),
+            None => Err(Error::from("cgroup controller is not mounted".to_string())).context(controller.to_string())?,
+        }
+    }
+}
+
+impl CgroupManager for Cgroup {
+    fn create(&self, resources: &Resources) -> Result<(), Error> {
+        let settings = settings(resources)?;
+        for (file, _) in &settings {
+            self.hierarchy(controller(file))?;
+        }
+
+        for hierarchy in &self.hierarchies {
+            let dir = self.dir(hierarchy);
+            fs::create_dir_all(&dir).context(format!("cannot create cgroup {:?}", dir))?;
+            if hierarchy.has_controller("cpuset") {
+                inherit_cpuset(hierarchy.mount_point(), &dir)?;
+            }
+        }
+
+        for (file, value) in &settings {
+            let dir = self.dir(self.hierarchy(controller(file))?);
+            fs::write(dir.join(file), value).context(format!("cannot set cgroup {} to {}", file, value))?;
+        }
+        Ok(())
+    }
+
+    // Writing "a" to devices.deny first drops whatever the cgroup inherited,
+    // each rule after that adds or removes an exception.
+    fn set_devices(&self, rules: &[devices::Rule]) -> Result<(), Error> {
+        let dir = self.dir(self.hierarchy("devices")?);
+        for rule in rules {
+            let (file, value) = rule.to_v1();
+            fs::write(dir.join(&file), &value).context(format!("cannot set cgroup {} to {}", file, value))?;
+        }
+        Ok(())
+    }
+
+    fn set_oom_group(&self) -> Result<(), Error> {
+        Err(Error::from("memory.oom.group needs cgroup v2".to_string()))?
+    }
+
+    fn add_process(&self, pid: i32) -> Result<(), Error> {
+        for hierarchy in &self.hierarchies {
+            let dir = self.dir(hierarchy);
+            fs::write(dir.join("cgroup.procs"), pid.to_string()).context(format!("cannot move process {} to cgroup {:?}", pid, dir))?;
+        }
+        Ok(())
+    }
+
+    fn has_processes(&self) -> Result<bool, Error> {
+        for hierarchy in &self.hierarchies {
+            if super::has_processes(&self.dir(hierarchy))? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    // Controllers that aren't mounted are left out of the stats. Pressure is
+    // only tracked by the cgroup v2 directory of hybrid hosts.
+    fn stats(&self) -> Result<Stats, Error> {
+        let mut stats = Stats::default();
+        if let Some(dir) = self.controller_dir("memory") {
+            read_memory_stats(&dir, &mut stats)?;
+        }
+        if let Some(dir) = self.controller_dir("cpuacct") {
+            stats.cpu.usage = stats::read_u64(&dir.join("cpuacct.usage"))?.unwrap_or(0);
+            stats.cpu.user = stats::read_u64(&dir.join("cpuacct.usage_user"))?.unwrap_or(0);
+            stats.cpu.system = stats::read_u64(&dir.join("cpuacct.usage_sys"))?.unwrap_or(0);
+        }
+        if let Some(dir) = self.controller_dir("cpu") {
+            let cpu = stats::read_keyed(&dir.join("cpu.stat"))?;
+            stats.cpu.periods = cpu.get("nr_periods").cloned().unwrap_or(0);
+            stats.cpu.throttled_periods = cpu.get("nr_throttled").cloned().unwrap_or(0);
+            stats.cpu.throttled_time = cpu.get("throttled_time").cloned().unwrap_or(0);
+        }
+        if let Some(dir) = self.controller_dir("pids") {
+            stats.pids.current = stats::read_u64(&dir.join("pids.current"))?.unwrap_or(0);
+            stats.pids.limit = stats::read_u64(&dir.join("pids.max"))?;
+        }
+        if let Some(dir) = self.controller_dir("blkio") {
+            read_blkio_stats(&dir, &mut stats)?;
+        }
+        if let Some(dir) = self.controller_dir("hugetlb") {
+            for page_size in stats::hugepage_sizes(&dir, "usage_in_bytes")? {
+                stats.hugetlb.push(HugetlbStats {
+                    usage: stats::read_u64(&dir.join(format!("hugetlb.{}.usage_in_bytes", page_size)))?.unwrap_or(0),
+                    limit: stats::read_limit(&dir.join(format!("hugetlb.{}.limit_in_bytes", page_size)))?,
+                    page_size,
+                });
+            }
+        }
+        if let Some(hierarchy) = self.hierarchies.iter().find(|hierarchy| hierarchy.is_unified()) {
+            stats.pressure = stats::read_pressure(&self.dir(hierarchy))?;
+        }
+        Ok(stats)
+    }
+
+    fn watch_oom(&self) -> Result<Option<OomWatch>, Error> {
+        match self.controller_dir("memory") {
+            Some(dir) => Ok(Some(OomWatch::eventfd(&dir)?)),
+            None => Ok(None),
+        }
+    }
+
+    // v1 has no cgroup.kill, so processes left in the cgroup are killed one
+    // by one before it is removed.
+    fn remove(&self) -> Result<(), Error> {
+        for hierarchy in &self.hierarchies {
+            let dir = self.dir(hierarchy);
+            if !dir.exists() {
+                continue;
+            }
+            let procs = fs::read_to_string(dir.join("cgroup.procs")).unwrap_or_default();
+            for pid in procs.lines().filter_map(|line| line.trim().parse::<i32>().ok()) {
+                let _ = signal::kill(Pid::from_raw(pid), Signal::SIGKILL);
+            }
+            super::remove_dir(&dir)?;
+        }
+        Ok(())
+    }
+}
+
+// Swap is only known as memory plus swap, when swap accounting is on.
+fn read_memory_stats(dir: &Path, stats: &mut Stats) -> Result<(), Error> {
+    let memory = &mut stats.memory;
+    memory.usage = stats::read_u64(&dir.join("memory.usage_in_bytes"))?.unwrap_or(0);
+    memory.max_usage = stats::read_u64(&dir.join("memory.max_usage_in_bytes"))?;
+    memory.limit = stats::read_limit(&dir.join("memory.limit_in_bytes"))?;
+    let memory_stat = stats::read_keyed(&dir.join("memory.stat"))?;
+    memory.cache = memory_stat.get("total_cache").or_else(|| memory_stat.get("cache")).cloned().unwrap_or(0);
+
+    if let Some(memsw_usage) = stats::read_u64(&dir.join("memory.memsw.usage_in_bytes"))? {
+        memory.swap_usage = Some(memsw_usage.saturating_sub(memory.usage));
+        let memsw_limit = stats::read_limit(&dir.join("memory.memsw.limit_in_bytes"))?;
+        memory.swap_limit = match (memsw_limit, memory.limit) {
+            (Some(memsw_limit), Some(limit)) => Some(memsw_limit.saturating_sub(limit)),
+            _ => None,
+        };
+    }
+    Ok(())
+}
+
+// Lines look like "8:0 Read 4096", with a "Total" line at the end.
+fn read_blkio_stats(dir: &Path, stats: &mut Stats) -> Result<(), Error> {
+    let files = [("blkio.throttle.io_service_bytes", true), ("blkio.throttle.io_serviced", false)];
+    for (file, bytes) in files.iter() {
+        let path = dir.join(file);
+        for line in stats::read(&path)?.unwrap_or_default().lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() != 3 {
+                continue;
+            }
+            let (major, minor) = stats::parse_device(fields[0], &path)?;
+            let value = stats::parse_u64(fields[2], &path)?;
+            let io = stats.io_device(major, minor);
+            match (fields[1], bytes) {
+                ("Read", true) => io.read_bytes = value,
+                ("Write", true) => io.write_bytes = value,
+                ("Read", false) => io.read_ops = value,
+                ("Write", false) => io.write_ops = value,
+                _ => (),
+            }
+        }
+    }
+    Ok(())
+}
+
+// Files of v1 controllers are named after the controller.
+fn controller(file: &str) -> &str {
+    file.split('.').next().unwrap_or(file)
+}
+
+// New cpuset cgroups start without cpus or memory nodes, and processes
+// cannot join them until both are set.
+fn inherit_cpuset(mount_point: &Path, dir: &Path) -> Result<(), Error> {
+    let mut dirs: Vec<&Path> = dir.ancestors().take_while(|ancestor| *ancestor != mount_point && ancestor.starts_with(mount_point)).collect();
+    dirs.reverse();
+    for dir in dirs {
+        let parent = dir.parent().unwrap_or(mount_point);
+        for file in CPUSET_FILES {
+            let current = fs::read_to_string(dir.join(file)).unwrap_or_default();
+            if !current.trim().is_empty() {
+                continue;
+            }
+            let inherited = fs::read_to_string(parent.join(file)).context(format!("cannot read {:?}", parent.join(file)))?;
+            fs::write(dir.join(file), inherited.trim()).context(format!("cannot set {:?}", dir.join(file)))?;
+        }
+    }
+    Ok(())
+}
+
+// Maps the resources to v1 controller files, the way runc does. Kernel
+// memory limits are left out, recent kernels no longer support them, and
+// zero values mean unset. Swap is set after the memory limit, which is fine
+// for a new cgroup since memory+swap starts unlimited.
+pub fn settings(resources: &Resources) -> Result<Vec<(String, String)>, Error> {
+    let mut settings = Vec::new();
+
+    if let Some(memory) = resources.memory() {
+        let limits = [
+            ("memory.limit_in_bytes", memory.limit()),
+            ("memory.memsw.limit_in_bytes", memory.swap()),
+            ("memory.soft_limit_in_bytes", memory.reservation()),
+            ("memory.kmem.tcp.limit_in_bytes", memory.kernel_tcp()),
+        ];
+        for (file, limit) in &limits {
+            if let Some(limit) = limit {
+                if *limit != 0 {
+                    settings.push((file.to_string(), limit.to_string()));
+                }
+            }
+        }
+        if let Some(swappiness) = memory.swappiness() {
+            if swappiness > 100 {
+                Err(Error::from("memory swappiness must be between 0 and 100".to_string())).context(swappiness.to_string())?;
+            }
+            settings.push(("memory.swappiness".to_string(), swappiness.to_string()));
+        }
+        if memory.disable_oom_killer() == Some(true) {
+            settings.push(("memory.oom_control".to_string(), "1".to_string()));
+        }
+    }
+
+    if let Some(cpu) = resources.cpu() {
+        if let Some(shares) = cpu.shares() {
+            if shares != 0 {
+                settings.push(("cpu.shares".to_string(), shares.to_string()));
+            }
+        }
+        if let Some(period) = cpu.period() {
+            settings.push(("cpu.cfs_period_us".to_string(), period.to_string()));
+        }
+        if let Some(quota) = cpu.quota() {
+            settings.push(("cpu.cfs_quota_us".to_string(), quota.to_string()));
+        }
+        if let Some(period) = cpu.realtime_period() {
+            settings.push(("cpu.rt_period_us".to_string(), period.to_string()));
+        }
+        if let Some(runtime) = cpu.realtime_runtime() {
+            settings.push(("cpu.rt_runtime_us".to_string(), runtime.to_string()));
+        }
+        if let Some(idle) = cpu.idle() {
+            settings.push(("cpu.idle".to_string(), idle.to_string()));
+        }
+        if let Some(cpus) = cpu.cpus() {
+            settings.push(("cpuset.cpus".to_string(), cpus.to_string()));
+        }
+        if let Some(mems) = cpu.mems() {
+            settings.push(("cpuset.mems".to_string(), mems.to_string()));
+        }
+    }
+
+    if let Some(pids) = resources.pids() {
+        let limit = match pids.limit() {
+            limit if limit <= 0 => "max".to_string(),
+            limit => limit.to_string(),
+        };
+        settings.push(("pids.max".to_string(), limit));
+    }
+
+    if let Some(block_io) = resources.block_io() {
+        if let Some(weight) = block_io.weight() {
+            if weight != 0 {
+                settings.push(("blkio.weight".to_string(), weight.to_string()));
+            }
+        }
+        if let Some(leaf_weight) = block_io.leaf_weight() {
+            if leaf_weight != 0 {
+                settings.push(("blkio.leaf_weight".to_string(), leaf_weight.to_string()));
+            }
+        }
+        for device in block_io.weight_device() {
+            if let Some(weight) = device.weight() {
+                settings.push(("blkio.weight_device".to_string(), format!("{}:{} {}", device.major(), device.minor(), weight)));
+            }
+            if let Some(leaf_weight) = device.leaf_weight() {
+                settings.push(("blkio.leaf_weight_device".to_string(), format!("{}:{} {}", device.major(), device.minor(), leaf_weight)));
+            }
+        }
+        let throttles = [
+            ("blkio.throttle.read_bps_device", block_io.throttle_read_bps_device()),
+            ("blkio.throttle.write_bps_device", block_io.throttle_write_bps_device()),
+            ("blkio.throttle.read_iops_device", block_io.throttle_read_iops_device()),
+            ("blkio.throttle.write_iops_device", block_io.throttle_write_iops_device()),
+        ];
+        for (file, devices) in &throttles {
+            for device in devices.iter() {
+                settings.push((file.to_string(), format!("{}:{} {}", device.major(), device.minor(), device.rate())));
+            }
+        }
+    }
+
+    for hugepage_limit in resources.hugepage_limits() {
+        settings.push((format!("hugetlb.{}.limit_in_bytes", hugepage_limit.page_size()), hugepage_limit.limit().to_string()));
+    }
+
+    if let Some(network) = resources.network() {
+        if let Some(class_id) = network.class_id() {
+            settings.push(("net_cls.classid".to_string(), class_id.to_string()));
+        }
+        for priority in network.priorities() {
+            settings.push(("net_prio.ifpriomap".to_string(), format!("{} {}", priority.name(), priority.priority())));
+        }
+    }
+
+    if resources.unified().is_some_and(|unified| !unified.is_empty()) {
+        Err(Error::from("linux.resources.unified needs cgroup v2".to_string()))?;
+    }
+
+    Ok(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libcontainer::linux::cgroup::set_devices;
+    use serde_json::json;
+    use tempfile::tempdir;
+    use tempfile::TempDir;
+
+    fn resources(value: serde_json::Value) -> Resources {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn setting<'a>(settings: &'a [(String, String)], file: &str) -> Vec<&'a str> {
+        settings.iter().filter(|(name, _)| name == file).map(|(_, value)| value.as_str()).collect()
+    }
+
+    fn setup_hierarchies(controllers: &[&str]) -> (TempDir, Vec<Hierarchy>) {
+        let root = tempdir().unwrap();
+        let hierarchies = controllers.iter().map(|controller| {
+            let mount_point = root.path().join(controller);
+            fs::create_dir(&mount_point).unwrap();
+            Hierarchy { mount_point, unified: false, controllers: vec![controller.to_string()] }
+        }).collect();
+        (root, hierarchies)
+    }
+
+    #[test]
+    fn settings_maps_memory_and_cpu_to_v1_files() {
+        let settings = settings(&resources(json!({
+            "memory": {"limit": 536870912, "swap": 805306368, "reservation": 0, "kernel": 1024, "swappiness": 10, "disableOOMKiller": true},
+            "cpu": {"shares": 1024, "quota": 50000, "period": 100000, "realtimeRuntime": 950000, "cpus": "0-1"},
+        }))).unwrap();
+        let files: Vec<&str> = settings.iter().map(|(file, _)| file.as_str()).collect();
+
+        assert_eq!(files, vec![
+            "memory.limit_in_bytes", "memory.memsw.limit_in_bytes", "memory.swappiness", "memory.oom_control",
+            "cpu.shares", "cpu.cfs_period_us", "cpu.cfs_quota_us", "cpu.rt_runtime_us", "cpuset.cpus",
+        ]);
+        assert_eq!(setting(&settings, "memory.memsw.limit_in_bytes"), vec!["805306368"]);
+        assert!(super::settings(&resources(json!({"memory": {"swappiness": 101}}))).is_err());
+    }
+
+    #[test]
+    fn settings_maps_block_io_pids_hugepages_and_network_to_v1_files() {
+        let settings = settings(&resources(json!({
+            "pids": {"limit": 0},
+            "blockIO": {
+                "weight": 500,
+                "weightDevice": [{"major": 8, "minor": 0, "weight": 1000, "leafWeight": 300}],
+                "throttleWriteIOPSDevice": [{"major": 8, "minor": 16, "rate": 300}],
+            },
+            "hugepageLimits": [{"pageSize": "2MB", "limit": 4194304}],
+            "network": {"classID": 1048577, "priorities": [{"name": "eth0", "priority": 500}]},
+        }))).unwrap();
+
+        assert_eq!(setting(&settings, "pids.max"), vec!["max"]);
+        assert_eq!(setting(&settings, "blkio.weight"), vec!["500"]);
+        assert_eq!(setting(&settings, "blkio.weight_device"), vec!["8:0 1000"]);
+        assert_eq!(setting(&settings, "blkio.leaf_weight_device"), vec!["8:0 300"]);
+        assert_eq!(setting(&settings, "blkio.throttle.write_iops_device"), vec!["8:16 300"]);
+        assert_eq!(setting(&settings, "hugetlb.2MB.limit_in_bytes"), vec!["4194304"]);
+        assert_eq!(setting(&settings, "net_cls.classid"), vec!["1048577"]);
+        assert_eq!(setting(&settings, "net_prio.ifpriomap"), vec!["eth0 500"]);
+        assert!(super::settings(&resources(json!({"unified": {"memory.high": "1G"}}))).is_err());
+    }
+
+    #[test]
+    fn cgroup_create_makes_a_directory_per_hierarchy() {
+        let (root, hierarchies) = setup_hierarchies(&["memory", "cpuset", "pids", "devices"]);
+        fs::write(root.path().join("cpuset/cpuset.cpus"), "0-3\n").unwrap();
+        fs::write(root.path().join("cpuset/cpuset.mems"), "0\n").unwrap();
+        let cgroup = Cgroup::new(hierarchies, Path::new("plankton/c1"));
+
+        cgroup.create(&resources(json!({"memory": {"limit": 1048576}, "cpu": {"mems": "0"}}))).unwrap();
+        assert!(!cgroup.has_processes().unwrap());
+        cgroup.set_devices(&devices::rules(&Resources::default()).unwrap()).unwrap();
+        cgroup.add_process(42).unwrap();
+        assert!(cgroup.has_processes().unwrap());
+
+        assert_eq!(fs::read_to_string(root.path().join("memory/plankton/c1/memory.limit_in_bytes")).unwrap(), "1048576");
+        assert_eq!(fs::read_to_string(root.path().join("cpuset/plankton/cpuset.cpus")).unwrap(), "0-3");
+        assert_eq!(fs::read_to_string(root.path().join("cpuset/plankton/c1/cpuset.cpus")).unwrap(), "0-3");
+        assert_eq!(fs::read_to_string(root.path().join("pids/plankton/c1/cgroup.procs")).unwrap(), "42");
+        assert_eq!(fs::read_to_string(root.path().join("devices/plankton/c1/devices.deny")).unwrap(), "a");
+    }
+
+    #[test]
+    fn cgroup_stats_reads_each_hierarchy() {
+        let (root, hierarchies) = setup_hierarchies(&["memory", "cpuacct", "blkio"]);
+        let cgroup = Cgroup::new(hierarchies, Path::new("c1"));
+        let files = [
+            ("memory/c1/memory.usage_in_bytes", "8192"),
+            ("memory/c1/memory.limit_in_bytes", "9223372036854771712"),
+            ("memory/c1/memory.memsw.usage_in_bytes", "12288"),
+            ("memory/c1/memory.stat", "cache 4096\nrss 4096\ntotal_cache 4096\n"),
+            ("cpuacct/c1/cpuacct.usage", "3000"),
+            ("cpuacct/c1/cpuacct.usage_user", "2000"),
+            ("blkio/c1/blkio.throttle.io_service_bytes", "8:0 Read 512\n8:0 Write 1024\nTotal 1536\n"),
+            ("blkio/c1/blkio.throttle.io_serviced", "8:0 Read 1\n8:0 Write 2\nTotal 3\n"),
+        ];
+        for (file, content) in files.iter() {
+            let path = root.path().join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+
+        let stats = cgroup.stats().unwrap();
+        assert_eq!((stats.memory.usage, stats.memory.limit, stats.memory.cache), (8192, None, 4096));
+        assert_eq!((stats.memory.swap_usage, stats.memory.swap_limit), (Some(4096), None));
+        assert_eq!((stats.cpu.usage, stats.cpu.user, stats.cpu.system), (3000, 2000, 0));
+        assert_eq!(stats.io.len(), 1);
+        assert_eq!((stats.io[0].read_bytes, stats.io[0].write_bytes, stats.io[0].write_ops), (512, 1024, 2));
+        assert_eq!(stats.pressure, None);
+    }
+
+    #[test]
+    fn set_devices_needs_the_devices_hierarchy_only_for_config_rules() {
+        let (_root, hierarchies) = setup_hierarchies(&["memory", "pids"]);
+        let cgroup = Cgroup::new(hierarchies, Path::new("plankton/c1"));
+        cgroup.create(&Resources::default()).unwrap();
+
+        assert!(set_devices(&cgroup, &Resources::default()).is_ok());
+        let result = set_devices(&cgroup, &resources(json!({"devices": [{"allow": false, "type": "c", "major": 10, "minor": 229}]})));
+        assert!(result.is_err(), "expect {:?} to be err", result);
+    }
+
+    #[test]
+    fn cgroup_create_returns_error_when_controller_is_not_mounted() {
+        let (root, hierarchies) = setup_hierarchies(&["memory"]);
+        let cgroup = Cgroup::new(hierarchies, Path::new("plankton/c1"));
+
+        let result = cgroup.create(&resources(json!({"pids": {"limit": 10}})));
+
+        assert!(result.is_err(), "expect {:?} to be err", result);
+        assert!(!root.path().join("memory/plankton").exists());
+    }
+}
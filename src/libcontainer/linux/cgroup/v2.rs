@@ -0,0 +1,465 @@
+use crate::Error;
+use crate::config::linux::Resources;
+use crate::config::linux::resources::ThrottleDevice;
+use super::CgroupManager;
+use super::devices;
+use super::oom::OomWatch;
+use super::stats;
+use super::stats::HugetlbStats;
+use super::stats::Stats;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use failure::ResultExt;
+
+// A cgroup of the unified hierarchy. `root` is where the hierarchy is
+// mounted and `path` the cgroup directory below it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cgroup {
+    root: PathBuf,
+    path: PathBuf,
+}
+
+impl Cgroup {
+    pub fn new(root: &Path, path: &Path) -> Cgroup {
+        Cgroup {
+            root: root.to_path_buf(),
+            path: path.to_path_buf(),
+        }
+    }
+
+    pub fn path(&self) -> &Path { &self.path }
+
+    fn ancestors(&self) -> Vec<PathBuf> {
+        let mut ancestors: Vec<PathBuf> = self.path.ancestors()
+            .skip(1)
+            .take_while(|ancestor| ancestor.starts_with(&self.root))
+            .map(Path::to_path_buf)
+            .collect();
+        ancestors.reverse();
+        ancestors
+    }
+}
+
+impl CgroupManager for Cgroup {
+    // Controllers only show up in a cgroup once every ancestor enables them
+    // for its children. Like runc, every controller an ancestor has is
+    // enabled, so stats and OOM events work without limits set; only those
+    // the settings need must succeed.
+    fn create(&self, resources: &Resources) -> Result<(), Error> {
+        let settings = settings(resources)?;
+        fs::create_dir_all(&self.path).context(format!("cannot create cgroup {:?}", self.path))?;
+
+        let required: BTreeSet<String> = settings.iter()
+            .filter_map(|(file, _)| file.split('.').next())
+            .filter(|controller| *controller != "cgroup")
+            .map(str::to_string)
+            .collect();
+        for ancestor in self.ancestors() {
+            let mut controllers = available_controllers(&ancestor);
+            controllers.extend(required.iter().cloned());
+
+            let subtree_control = ancestor.join("cgroup.subtree_control");
+            let all: Vec<String> = controllers.iter().map(|controller| format!("+{}", controller)).collect();
+            if fs::write(&subtree_control, all.join(" ")).is_ok() {
+                continue;
+            }
+            for controller in &controllers {
+                let result = fs::write(&subtree_control, format!("+{}", controller));
+                if required.contains(controller) {
+                    result.context(format!("cannot enable cgroup controller {} in {:?}", controller, ancestor))?;
+                }
+            }
+        }
+
+        for (file, value) in &settings {
+            fs::write(self.path.join(file), value).context(format!("cannot set cgroup {} to {}", file, value))?;
+        }
+        Ok(())
+    }
+
+    fn set_devices(&self, rules: &[devices::Rule]) -> Result<(), Error> {
+        devices::attach(&self.path, rules)
+    }
+
+    fn set_oom_group(&self) -> Result<(), Error> {
+        fs::write(self.path.join("memory.oom.group"), "1").context(format!("cannot set memory.oom.group of cgroup {:?}", self.path))?;
+        Ok(())
+    }
+
+    fn add_process(&self, pid: i32) -> Result<(), Error> {
+        fs::write(self.path.join("cgroup.procs"), pid.to_string()).context(format!("cannot move process {} to cgroup {:?}", pid, self.path))?;
+        Ok(())
+    }
+
+    fn has_processes(&self) -> Result<bool, Error> {
+        super::has_processes(&self.path)
+    }
+
+    fn stats(&self) -> Result<Stats, Error> {
+        read_stats(&self.path)
+    }
+
+    // memory.events only exists when the memory controller is enabled.
+    fn watch_oom(&self) -> Result<Option<OomWatch>, Error> {
+        let events = self.path.join("memory.events");
+        match events.exists() {
+            true => Ok(Some(OomWatch::inotify(&events)?)),
+            false => Ok(None),
+        }
+    }
+
+    // Processes left in the cgroup are killed first, the cgroup can only be
+    // removed once they are gone.
+    fn remove(&self) -> Result<(), Error> {
+        if !self.path.exists() {
+            return Ok(());
+        }
+        let kill = self.path.join("cgroup.kill");
+        if kill.exists() {
+            let _ = fs::write(&kill, "1");
+        }
+        super::remove_dir(&self.path)
+    }
+}
+
+fn available_controllers(dir: &Path) -> BTreeSet<String> {
+    fs::read_to_string(dir.join("cgroup.controllers"))
+        .map(|controllers| controllers.split_whitespace().map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+// cpu.stat counts microseconds, stats are kept in nanoseconds.
+pub fn read_stats(dir: &Path) -> Result<Stats, Error> {
+    let mut stats = Stats::default();
+
+    stats.memory.usage = stats::read_u64(&dir.join("memory.current"))?.unwrap_or(0);
+    stats.memory.max_usage = stats::read_u64(&dir.join("memory.peak"))?;
+    stats.memory.limit = stats::read_limit(&dir.join("memory.max"))?;
+    stats.memory.cache = stats::read_keyed(&dir.join("memory.stat"))?.get("file").cloned().unwrap_or(0);
+    stats.memory.swap_usage = stats::read_u64(&dir.join("memory.swap.current"))?;
+    stats.memory.swap_limit = stats::read_limit(&dir.join("memory.swap.max"))?;
+
+    let cpu = stats::read_keyed(&dir.join("cpu.stat"))?;
+    let cpu_value = |key: &str| cpu.get(key).cloned().unwrap_or(0);
+    stats.cpu.usage = cpu_value("usage_usec") * 1000;
+    stats.cpu.user = cpu_value("user_usec") * 1000;
+    stats.cpu.system = cpu_value("system_usec") * 1000;
+    stats.cpu.periods = cpu_value("nr_periods");
+    stats.cpu.throttled_periods = cpu_value("nr_throttled");
+    stats.cpu.throttled_time = cpu_value("throttled_usec") * 1000;
+
+    stats.pids.current = stats::read_u64(&dir.join("pids.current"))?.unwrap_or(0);
+    stats.pids.limit = stats::read_limit(&dir.join("pids.max"))?;
+
+    let io_stat = dir.join("io.stat");
+    for line in stats::read(&io_stat)?.unwrap_or_default().lines() {
+        let mut fields = line.split_whitespace();
+        let (major, minor) = match fields.next() {
+            Some(device) => stats::parse_device(device, &io_stat)?,
+            None => continue,
+        };
+        let io = stats.io_device(major, minor);
+        for field in fields {
+            let (key, value) = match field.find('=') {
+                Some(separator) => (&field[..separator], stats::parse_u64(&field[separator + 1..], &io_stat)?),
+                None => continue,
+            };
+            match key {
+                "rbytes" => io.read_bytes = value,
+                "wbytes" => io.write_bytes = value,
+                "rios" => io.read_ops = value,
+                "wios" => io.write_ops = value,
+                _ => (),
+            }
+        }
+    }
+
+    for page_size in stats::hugepage_sizes(dir, "current")? {
+        stats.hugetlb.push(HugetlbStats {
+            usage: stats::read_u64(&dir.join(format!("hugetlb.{}.current", page_size)))?.unwrap_or(0),
+            limit: stats::read_limit(&dir.join(format!("hugetlb.{}.max", page_size)))?,
+            page_size,
+        });
+    }
+
+    stats.pressure = stats::read_pressure(dir)?;
+    Ok(stats)
+}
+
+// Maps the resources to the files of the unified hierarchy, the way runc
+// does. Knobs that only exist in cgroup v1 (kernel memory, swappiness, the
+// OOM killer switch, leaf weights, network classes) are left out. The unified map is written
+// last so it can override anything else.
+pub fn settings(resources: &Resources) -> Result<Vec<(String, String)>, Error> {
+    let mut settings = Vec::new();
+
+    if let Some(memory) = resources.memory() {
+        if let Some(limit) = memory.limit() {
+            settings.push(("memory.max".to_string(), limit_value(limit)));
+        }
+        if let Some(reservation) = memory.reservation() {
+            settings.push(("memory.low".to_string(), limit_value(reservation)));
+        }
+        if let Some(swap) = memory.swap() {
+            if let Some(swap) = swap_value(swap, memory.limit())? {
+                settings.push(("memory.swap.max".to_string(), swap));
+            }
+        }
+    }
+
+    if let Some(cpu) = resources.cpu() {
+        if cpu.realtime_runtime().unwrap_or(0) != 0 || cpu.realtime_period().unwrap_or(0) != 0 {
+            Err(Error::from("cgroup v2 does not support realtime cpu limits".to_string()))?;
+        }
+        if let Some(shares) = cpu.shares() {
+            if shares != 0 {
+                settings.push(("cpu.weight".to_string(), cpu_weight(shares).to_string()));
+            }
+        }
+        if cpu.quota().is_some() || cpu.period().is_some() {
+            let quota = match cpu.quota() {
+                Some(quota) if quota > 0 => quota.to_string(),
+                _ => "max".to_string(),
+            };
+            let value = match cpu.period() {
+                Some(period) if period != 0 => format!("{} {}", quota, period),
+                _ => quota,
+            };
+            settings.push(("cpu.max".to_string(), value));
+        }
+        if let Some(idle) = cpu.idle() {
+            settings.push(("cpu.idle".to_string(), idle.to_string()));
+        }
+        if let Some(cpus) = cpu.cpus() {
+            settings.push(("cpuset.cpus".to_string(), cpus.to_string()));
+        }
+        if let Some(mems) = cpu.mems() {
+            settings.push(("cpuset.mems".to_string(), mems.to_string()));
+        }
+    }
+
+    if let Some(pids) = resources.pids() {
+        settings.push(("pids.max".to_string(), limit_value(pids.limit())));
+    }
+
+    if let Some(block_io) = resources.block_io() {
+        if let Some(weight) = block_io.weight() {
+            if weight != 0 {
+                settings.push(("io.weight".to_string(), format!("default {}", io_weight(weight)?)));
+            }
+        }
+        for device in block_io.weight_device() {
+            if let Some(weight) = device.weight() {
+                settings.push(("io.weight".to_string(), format!("{}:{} {}", device.major(), device.minor(), io_weight(weight)?)));
+            }
+        }
+        let throttles = [
+            ("rbps", block_io.throttle_read_bps_device()),
+            ("wbps", block_io.throttle_write_bps_device()),
+            ("riops", block_io.throttle_read_iops_device()),
+            ("wiops", block_io.throttle_write_iops_device()),
+        ];
+        for (key, devices) in &throttles {
+            for device in devices.iter() {
+                settings.push(("io.max".to_string(), io_max(key, device)));
+            }
+        }
+    }
+
+    for hugepage_limit in resources.hugepage_limits() {
+        settings.push((format!("hugetlb.{}.max", hugepage_limit.page_size()), hugepage_limit.limit().to_string()));
+    }
+
+    if let Some(unified) = resources.unified() {
+        for (file, value) in unified {
+            if file.contains('/') || !file.contains('.') {
+                Err(Error::from("invalid cgroup file in linux.resources.unified".to_string())).context(file.to_string())?;
+            }
+            settings.push((file.to_string(), value.to_string()));
+        }
+    }
+
+    Ok(settings)
+}
+
+// -1 means no limit, as in cgroup v1.
+fn limit_value(limit: i64) -> String {
+    match limit {
+        limit if limit < 0 => "max".to_string(),
+        limit => limit.to_string(),
+    }
+}
+
+// The spec's swap is memory plus swap, as in cgroup v1, while memory.swap.max
+// only counts the swap.
+fn swap_value(swap: i64, limit: Option<i64>) -> Result<Option<String>, Error> {
+    match (swap, limit) {
+        (0, _) => Ok(None),
+        (-1, _) => Ok(Some("max".to_string())),
+        (_, None) | (_, Some(0)) | (_, Some(-1)) => Err(Error::from("cannot set a swap limit without a memory limit".to_string()))?,
+        (swap, Some(limit)) if swap < limit => Err(Error::from("memory swap limit must not be lower than the memory limit".to_string())).context(swap.to_string())?,
+        (swap, Some(limit)) => Ok(Some((swap - limit).to_string())),
+    }
+}
+
+// Maps cpu shares (2 to 262144) to cpu.weight (1 to 10000).
+fn cpu_weight(shares: u64) -> u64 {
+    let shares = shares.clamp(2, 262_144);
+    1 + ((shares - 2) * 9999) / 262_142
+}
+
+// Maps blkio weights (10 to 1000) to io.weight (1 to 10000).
+fn io_weight(weight: u16) -> Result<u64, Error> {
+    if !(10..=1000).contains(&weight) {
+        Err(Error::from("blockIO weight must be between 10 and 1000".to_string())).context(weight.to_string())?;
+    }
+    Ok(1 + ((u64::from(weight) - 10) * 9999) / 990)
+}
+
+// A rate of 0 removes the limit, as in cgroup v1.
+fn io_max(key: &str, device: &ThrottleDevice) -> String {
+    let rate = match device.rate() {
+        0 => "max".to_string(),
+        rate => rate.to_string(),
+    };
+    format!("{}:{} {}={}", device.major(), device.minor(), key, rate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    fn resources(value: serde_json::Value) -> Resources {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn setting<'a>(settings: &'a [(String, String)], file: &str) -> Vec<&'a str> {
+        settings.iter().filter(|(name, _)| name == file).map(|(_, value)| value.as_str()).collect()
+    }
+
+    #[test]
+    fn settings_maps_memory_to_unified_files() {
+        let settings = settings(&resources(json!({"memory": {"limit": 536870912, "reservation": -1, "swap": 805306368, "swappiness": 0}}))).unwrap();
+        assert_eq!(setting(&settings, "memory.max"), vec!["536870912"]);
+        assert_eq!(setting(&settings, "memory.low"), vec!["max"]);
+        assert_eq!(setting(&settings, "memory.swap.max"), vec!["268435456"]);
+        assert_eq!(settings.len(), 3);
+    }
+
+    #[test]
+    fn settings_returns_error_with_invalid_swap() {
+        assert!(settings(&resources(json!({"memory": {"swap": 1024}}))).is_err());
+        assert!(settings(&resources(json!({"memory": {"limit": 2048, "swap": 1024}}))).is_err());
+        let unlimited = settings(&resources(json!({"memory": {"swap": -1}}))).unwrap();
+        assert_eq!(setting(&unlimited, "memory.swap.max"), vec!["max"]);
+    }
+
+    #[test]
+    fn settings_maps_cpu_to_unified_files() {
+        let settings = settings(&resources(json!({"cpu": {"shares": 1024, "quota": 50000, "period": 100000, "cpus": "0-1", "mems": "0"}}))).unwrap();
+        assert_eq!(setting(&settings, "cpu.weight"), vec!["39"]);
+        assert_eq!(setting(&settings, "cpu.max"), vec!["50000 100000"]);
+        assert_eq!(setting(&settings, "cpuset.cpus"), vec!["0-1"]);
+        assert_eq!(setting(&settings, "cpuset.mems"), vec!["0"]);
+
+        let unlimited = super::settings(&resources(json!({"cpu": {"quota": -1}}))).unwrap();
+        assert_eq!(setting(&unlimited, "cpu.max"), vec!["max"]);
+        assert!(super::settings(&resources(json!({"cpu": {"realtimeRuntime": 950000}}))).is_err());
+    }
+
+    #[test]
+    fn settings_maps_pids_block_io_and_hugepages() {
+        let settings = settings(&resources(json!({
+            "pids": {"limit": 32},
+            "blockIO": {
+                "weight": 500,
+                "weightDevice": [{"major": 8, "minor": 0, "weight": 1000}],
+                "throttleReadBpsDevice": [{"major": 8, "minor": 0, "rate": 600}],
+                "throttleWriteIOPSDevice": [{"major": 8, "minor": 16, "rate": 0}],
+            },
+            "hugepageLimits": [{"pageSize": "2MB", "limit": 4194304}],
+        }))).unwrap();
+        assert_eq!(setting(&settings, "pids.max"), vec!["32"]);
+        assert_eq!(setting(&settings, "io.weight"), vec!["default 4950", "8:0 10000"]);
+        assert_eq!(setting(&settings, "io.max"), vec!["8:0 rbps=600", "8:16 wiops=max"]);
+        assert_eq!(setting(&settings, "hugetlb.2MB.max"), vec!["4194304"]);
+    }
+
+    #[test]
+    fn settings_writes_unified_map_last() {
+        let settings = settings(&resources(json!({"pids": {"limit": 32}, "unified": {"pids.max": "64", "memory.high": "1G"}}))).unwrap();
+        assert_eq!(settings.last(), Some(&("pids.max".to_string(), "64".to_string())));
+        assert!(super::settings(&resources(json!({"unified": {"../memory.max": "1"}}))).is_err());
+        assert!(super::settings(&resources(json!({"unified": {"memory": "1"}}))).is_err());
+    }
+
+    #[test]
+    fn read_stats_parses_unified_files() {
+        let dir = tempdir().unwrap();
+        let files = [
+            ("memory.current", "4096\n"),
+            ("memory.max", "max\n"),
+            ("memory.stat", "anon 1024\nfile 2048\n"),
+            ("cpu.stat", "usage_usec 30\nuser_usec 20\nsystem_usec 10\nnr_periods 4\nnr_throttled 1\nthrottled_usec 5\n"),
+            ("pids.current", "2\n"),
+            ("pids.max", "32\n"),
+            ("io.stat", "8:0 rbytes=100 wbytes=200 rios=3 wios=4 dbytes=0 dios=0\n"),
+            ("hugetlb.2MB.current", "0\n"),
+            ("hugetlb.2MB.max", "4194304\n"),
+            ("hugetlb.2MB.rsvd.current", "0\n"),
+        ];
+        for (file, content) in files.iter() {
+            fs::write(dir.path().join(file), content).unwrap();
+        }
+
+        let stats = read_stats(dir.path()).unwrap();
+        assert_eq!((stats.memory.usage, stats.memory.limit, stats.memory.cache), (4096, None, 2048));
+        assert_eq!((stats.cpu.usage, stats.cpu.user, stats.cpu.throttled_time), (30000, 20000, 5000));
+        assert_eq!((stats.pids.current, stats.pids.limit), (2, Some(32)));
+        assert_eq!((stats.io[0].major, stats.io[0].read_bytes, stats.io[0].write_ops), (8, 100, 4));
+        assert_eq!(stats.hugetlb, vec![HugetlbStats { page_size: "2MB".to_string(), usage: 0, limit: Some(4194304) }]);
+        assert_eq!(stats.pressure, None);
+    }
+
+    #[test]
+    fn cgroup_create_enables_controllers_in_ancestors_and_writes_settings() {
+        let root = tempdir().unwrap();
+        let cgroup = Cgroup::new(root.path(), &root.path().join("plankton/c1"));
+
+        cgroup.create(&resources(json!({"memory": {"limit": 1048576}, "pids": {"limit": 10}}))).unwrap();
+        cgroup.add_process(42).unwrap();
+
+        assert_eq!(fs::read_to_string(root.path().join("cgroup.subtree_control")).unwrap(), "+memory +pids");
+        assert_eq!(fs::read_to_string(root.path().join("plankton/cgroup.subtree_control")).unwrap(), "+memory +pids");
+        assert_eq!(fs::read_to_string(root.path().join("plankton/c1/memory.max")).unwrap(), "1048576");
+        assert_eq!(fs::read_to_string(root.path().join("plankton/c1/cgroup.procs")).unwrap(), "42");
+    }
+
+    #[test]
+    fn cgroup_create_without_limits_enables_available_controllers() {
+        let root = tempdir().unwrap();
+        fs::create_dir(root.path().join("plankton")).unwrap();
+        fs::write(root.path().join("cgroup.controllers"), "cpu io memory pids\n").unwrap();
+        fs::write(root.path().join("plankton/cgroup.controllers"), "cpu io memory pids\n").unwrap();
+        let cgroup = Cgroup::new(root.path(), &root.path().join("plankton/c1"));
+
+        cgroup.create(&resources(json!({}))).unwrap();
+
+        assert_eq!(fs::read_to_string(root.path().join("cgroup.subtree_control")).unwrap(), "+cpu +io +memory +pids");
+        assert_eq!(fs::read_to_string(root.path().join("plankton/cgroup.subtree_control")).unwrap(), "+cpu +io +memory +pids");
+
+        // What the kernel fills in once the controllers are enabled.
+        fs::write(root.path().join("plankton/c1/memory.current"), "8192\n").unwrap();
+        fs::write(root.path().join("plankton/c1/memory.events"), "oom 0\n").unwrap();
+        fs::write(root.path().join("plankton/c1/pids.current"), "3\n").unwrap();
+        let stats = cgroup.stats().unwrap();
+        assert_eq!((stats.memory.usage, stats.pids.current), (8192, 3));
+        assert!(cgroup.watch_oom().unwrap().is_some());
+    }
+}
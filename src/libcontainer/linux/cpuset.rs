@@ -0,0 +1,141 @@
+use crate::Error;
+use nix::sched;
+use nix::sched::CpuSet;
+use nix::unistd::Pid;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use failure::ResultExt;
+
+const ONLINE_CPUS: &str = "/sys/devices/system/cpu/online";
+const ONLINE_NODES: &str = "/sys/devices/system/node/online";
+
+// A set of cpus or memory nodes, written the way the kernel lists them,
+// as in "0-3,8".
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CpuList(BTreeSet<usize>);
+
+impl CpuList {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &usize> {
+        self.0.iter()
+    }
+
+    pub fn max(&self) -> Option<usize> {
+        self.0.iter().next_back().cloned()
+    }
+
+    // Fails with the entries missing from the online list, so the error
+    // says which cpus or nodes the host doesn't have.
+    pub fn check_online(&self, online: &CpuList) -> Result<(), Error> {
+        let offline: BTreeSet<usize> = self.0.difference(&online.0).cloned().collect();
+        if !offline.is_empty() {
+            Err(Error::from(format!("{} not online, online is {}", CpuList(offline), online)))?;
+        }
+        Ok(())
+    }
+
+    pub fn set_affinity(&self, pid: i32) -> Result<(), Error> {
+        let mut cpu_set = CpuSet::new();
+        for cpu in &self.0 {
+            cpu_set.set(*cpu).context(format!("cpu {} is out of range", cpu))?;
+        }
+        sched::sched_setaffinity(Pid::from_raw(pid), &cpu_set).context(format!("cannot set cpu affinity to {}", self))?;
+        Ok(())
+    }
+}
+
+impl FromStr for CpuList {
+    type Err = Error;
+
+    fn from_str(list: &str) -> Result<CpuList, Error> {
+        let mut entries = BTreeSet::new();
+        for range in list.trim().split(',').filter(|range| !range.is_empty()) {
+            let (first, last) = match range.find('-') {
+                Some(separator) => (&range[..separator], &range[separator + 1..]),
+                None => (range, range),
+            };
+            let invalid = || Error::from(format!("invalid cpu list {:?}", list));
+            let first = first.trim().parse::<usize>().map_err(|_| invalid())?;
+            let last = last.trim().parse::<usize>().map_err(|_| invalid())?;
+            if first > last {
+                Err(invalid())?;
+            }
+            entries.extend(first..=last);
+        }
+        Ok(CpuList(entries))
+    }
+}
+
+impl fmt::Display for CpuList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        for entry in &self.0 {
+            match ranges.last_mut() {
+                Some(range) if range.1 + 1 == *entry => range.1 = *entry,
+                _ => ranges.push((*entry, *entry)),
+            }
+        }
+        let ranges: Vec<String> = ranges.iter().map(|(first, last)| match first == last {
+            true => first.to_string(),
+            false => format!("{}-{}", first, last),
+        }).collect();
+        write!(f, "{}", ranges.join(","))
+    }
+}
+
+pub fn online_cpus() -> Result<CpuList, Error> {
+    read_list(Path::new(ONLINE_CPUS))?.ok_or_else(|| Error::from(format!("cannot find online cpus in {}", ONLINE_CPUS)))
+}
+
+// Kernels built without NUMA have no node directory, all memory is node 0.
+pub fn online_nodes() -> Result<CpuList, Error> {
+    Ok(read_list(Path::new(ONLINE_NODES))?.unwrap_or_else(|| CpuList(vec![0].into_iter().collect())))
+}
+
+fn read_list(path: &Path) -> Result<Option<CpuList>, Error> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(Some(CpuList::from_str(&content).context(format!("{:?}", path))?)),
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err).context(format!("cannot read {:?}", path))?,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cpu_list_parses_ranges_and_prints_them_back() {
+        let list = CpuList::from_str("0-3,8,10-11\n").unwrap();
+
+        assert_eq!(list.iter().cloned().collect::<Vec<usize>>(), vec![0, 1, 2, 3, 8, 10, 11]);
+        assert_eq!(list.to_string(), "0-3,8,10-11");
+        assert_eq!(list.max(), Some(11));
+        assert!(CpuList::from_str("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn cpu_list_returns_error_when_invalid() {
+        for list in &["a", "3-1", "1-", "-1", "0,,x"] {
+            assert!(CpuList::from_str(list).is_err(), "expect {:?} to be invalid", list);
+        }
+    }
+
+    #[test]
+    fn check_online_reports_offline_entries() {
+        let online = CpuList::from_str("0-3").unwrap();
+
+        assert!(CpuList::from_str("1,3").unwrap().check_online(&online).is_ok());
+        let err = CpuList::from_str("2-5").unwrap().check_online(&online).unwrap_err();
+        assert_eq!(err.to_string(), "4-5 not online, online is 0-3");
+    }
+}
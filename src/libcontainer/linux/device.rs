@@ -0,0 +1,290 @@
+use crate::Error;
+use crate::filesystem::securejoin::secure_join;
+use serde::Deserialize;
+use serde::Serialize;
+use nix::mount;
+use nix::mount::MsFlags;
+use nix::sys::stat;
+use nix::sys::stat::Mode;
+use nix::sys::stat::SFlag;
+use nix::unistd;
+use nix::unistd::Gid;
+use nix::unistd::Uid;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::fs::symlink;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+use failure::ResultExt;
+
+const DEFAULT_FILE_MODE: u32 = 0o666;
+
+const DEFAULT_DEVICES: &[(&str, u64, u64)] = &[
+    ("/dev/null", 1, 3),
+    ("/dev/zero", 1, 5),
+    ("/dev/full", 1, 7),
+    ("/dev/random", 1, 8),
+    ("/dev/urandom", 1, 9),
+    ("/dev/tty", 5, 0),
+];
+
+const DEFAULT_SYMLINKS: &[(&str, &str)] = &[
+    ("/proc/self/fd", "/dev/fd"),
+    ("/proc/self/fd/0", "/dev/stdin"),
+    ("/proc/self/fd/1", "/dev/stdout"),
+    ("/proc/self/fd/2", "/dev/stderr"),
+    ("pts/ptmx", "/dev/ptmx"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum DeviceType {
+    Char,
+    Block,
+    Fifo,
+}
+
+impl FromStr for DeviceType {
+    type Err = Error;
+
+    fn from_str(original: &str) -> Result<DeviceType, Error> {
+        match original {
+            "c" | "u" => Ok(DeviceType::Char),
+            "b" => Ok(DeviceType::Block),
+            "p" => Ok(DeviceType::Fifo),
+            _ => Err(Error::from("invalid device type".to_string())).context(original.to_string())?,
+        }
+    }
+}
+
+impl DeviceType {
+    fn to_sflag(self) -> SFlag {
+        match self {
+            DeviceType::Char => SFlag::S_IFCHR,
+            DeviceType::Block => SFlag::S_IFBLK,
+            DeviceType::Fifo => SFlag::S_IFIFO,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Device {
+    path: PathBuf,
+    device_type: DeviceType,
+    major: u64,
+    minor: u64,
+    file_mode: u32,
+    uid: Option<u32>,
+    gid: Option<u32>,
+}
+
+impl Device {
+    pub fn new(path: &str, device_type: DeviceType, major: Option<i64>, minor: Option<i64>) -> Result<Device, Error> {
+        let device_path = PathBuf::from(path);
+        if device_path.is_relative() || !device_path.starts_with("/dev") || device_path == Path::new("/dev") {
+            Err(Error::from("device path must be an absolute path inside /dev".to_string())).context(path.to_string())?;
+        }
+
+        let (major, minor) = match (device_type, major, minor) {
+            (DeviceType::Fifo, _, _) => (0, 0),
+            (_, Some(major), Some(minor)) if major >= 0 && minor >= 0 => (major as u64, minor as u64),
+            _ => Err(Error::from("character and block devices need valid major and minor numbers".to_string())).context(path.to_string())?,
+        };
+
+        Ok(Device {
+            path: device_path,
+            device_type,
+            major,
+            minor,
+            file_mode: DEFAULT_FILE_MODE,
+            uid: None,
+            gid: None,
+        })
+    }
+
+    pub fn defaults() -> Vec<Device> {
+        DEFAULT_DEVICES.iter().map(|(path, major, minor)| Device {
+            path: PathBuf::from(path),
+            device_type: DeviceType::Char,
+            major: *major,
+            minor: *minor,
+            file_mode: DEFAULT_FILE_MODE,
+            uid: None,
+            gid: None,
+        }).collect()
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn set_file_mode(&mut self, file_mode: u32) {
+        self.file_mode = file_mode & 0o7777;
+    }
+
+    pub fn set_owner(&mut self, uid: Option<u32>, gid: Option<u32>) {
+        self.uid = uid;
+        self.gid = gid;
+    }
+
+    // Creates the device under the given root. Inside a user namespace
+    // mknod is not permitted, so the host device is bind mounted instead.
+    pub fn create(&self, root: &Path, bind: bool) -> Result<(), Error> {
+        let target = join_parent(root, &self.path)?;
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).context(format!("cannot create {:?}", parent))?;
+        }
+
+        if bind {
+            self.bind(&target)
+        } else {
+            self.mknod(&target)
+        }
+    }
+
+    fn mknod(&self, target: &Path) -> Result<(), Error> {
+        let rdev = stat::makedev(self.major, self.minor);
+        match fs::symlink_metadata(target) {
+            Ok(ref metadata) if self.matches(metadata) => {},
+            Ok(_) => {
+                fs::remove_file(target).context(format!("cannot replace {:?}", target))?;
+                stat::mknod(target, self.device_type.to_sflag(), Mode::empty(), rdev).context(format!("cannot create device {:?}", self.path))?;
+            },
+            Err(_) => {
+                stat::mknod(target, self.device_type.to_sflag(), Mode::empty(), rdev).context(format!("cannot create device {:?}", self.path))?;
+            },
+        }
+
+        // Set the mode explicitly since mknod is subject to the umask.
+        fs::set_permissions(target, fs::Permissions::from_mode(self.file_mode)).context(format!("cannot set mode of {:?}", self.path))?;
+        if self.uid.is_some() || self.gid.is_some() {
+            let uid = self.uid.map(Uid::from_raw);
+            let gid = self.gid.map(Gid::from_raw);
+            unistd::chown(target, uid, gid).context(format!("cannot change owner of {:?}", self.path))?;
+        }
+        Ok(())
+    }
+
+    fn bind(&self, target: &Path) -> Result<(), Error> {
+        if let Err(err) = fs::symlink_metadata(target) {
+            if err.kind() != io::ErrorKind::NotFound {
+                Err(err).context(format!("{:?}", target))?;
+            }
+            OpenOptions::new().write(true).create(true).truncate(false).open(target).context(format!("cannot create {:?}", target))?;
+        }
+        mount::mount(Some(&self.path), target, None::<&str>, MsFlags::MS_BIND, None::<&str>)
+            .context(format!("cannot bind mount device {:?}", self.path))?;
+        Ok(())
+    }
+
+    fn matches(&self, metadata: &fs::Metadata) -> bool {
+        let file_type = metadata.file_type();
+        let same_type = match self.device_type {
+            DeviceType::Char => file_type.is_char_device(),
+            DeviceType::Block => file_type.is_block_device(),
+            DeviceType::Fifo => return file_type.is_fifo(),
+        };
+        same_type && metadata.rdev() == stat::makedev(self.major, self.minor)
+    }
+}
+
+pub fn create_symlinks(root: &Path) -> Result<(), Error> {
+    for (source, link) in DEFAULT_SYMLINKS {
+        let target = join_parent(root, Path::new(link))?;
+        if fs::symlink_metadata(&target).is_ok() {
+            continue;
+        }
+        symlink(source, &target).context(format!("cannot create symlink {:?}", link))?;
+    }
+    Ok(())
+}
+
+// Resolves only the parent directory inside the root, so a symlink at the
+// final component is replaced or kept rather than followed.
+fn join_parent(root: &Path, path: &Path) -> Result<PathBuf, Error> {
+    let parent = path.parent().unwrap_or_else(|| Path::new("/"));
+    let file_name = match path.file_name() {
+        Some(file_name) => file_name,
+        None => Err(Error::from("invalid device path".to_string())).context(format!("{:?}", path))?,
+    };
+    Ok(secure_join(root, parent)?.join(file_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nix::mount::MntFlags;
+    use tempfile::tempdir;
+
+    #[test]
+    fn device_type_from_str() {
+        let table = vec![
+            ("c", DeviceType::Char),
+            ("u", DeviceType::Char),
+            ("b", DeviceType::Block),
+            ("p", DeviceType::Fifo),
+        ];
+        for (original, expect) in table {
+            assert_eq!(DeviceType::from_str(original).unwrap(), expect);
+        }
+        assert!(DeviceType::from_str("x").is_err());
+    }
+
+    #[test]
+    fn device_new_returns_error_outside_dev() {
+        for path in &["/etc/passwd", "dev/null", "/dev"] {
+            let result = Device::new(path, DeviceType::Char, Some(1), Some(3));
+            assert!(result.is_err(), "expect {:?} to be err for {}", result, path);
+        }
+    }
+
+    #[test]
+    fn device_new_requires_major_and_minor_except_for_fifos() {
+        let char_device = Device::new("/dev/fuse", DeviceType::Char, None, Some(229));
+        let fifo = Device::new("/dev/myfifo", DeviceType::Fifo, None, None);
+        assert!(char_device.is_err(), "expect {:?} to be err", char_device);
+        assert!(fifo.is_ok(), "expect {:?} to be ok", fifo);
+    }
+
+    #[test]
+    fn device_create_makes_device_node_with_mode() {
+        let root = tempdir().unwrap();
+        let mut device = Device::new("/dev/null", DeviceType::Char, Some(1), Some(3)).unwrap();
+        device.set_file_mode(0o620);
+
+        device.create(root.path(), false).unwrap();
+        device.create(root.path(), false).unwrap();
+
+        let metadata = fs::metadata(root.path().join("dev/null")).unwrap();
+        assert!(metadata.file_type().is_char_device());
+        assert_eq!(metadata.rdev(), stat::makedev(1, 3));
+        assert_eq!(metadata.permissions().mode() & 0o7777, 0o620);
+    }
+
+    #[test]
+    fn device_create_bind_mounts_host_device() {
+        let root = tempdir().unwrap();
+        let device = Device::new("/dev/zero", DeviceType::Char, Some(1), Some(5)).unwrap();
+
+        device.create(root.path(), true).unwrap();
+        let metadata = fs::metadata(root.path().join("dev/zero")).unwrap();
+        mount::umount2(&root.path().join("dev/zero"), MntFlags::MNT_DETACH).unwrap();
+
+        assert!(metadata.file_type().is_char_device());
+    }
+
+    #[test]
+    fn create_symlinks_adds_standard_dev_links() {
+        let root = tempdir().unwrap();
+        fs::create_dir(root.path().join("dev")).unwrap();
+
+        create_symlinks(root.path()).unwrap();
+
+        assert_eq!(fs::read_link(root.path().join("dev/stdin")).unwrap(), PathBuf::from("/proc/self/fd/0"));
+        assert_eq!(fs::read_link(root.path().join("dev/ptmx")).unwrap(), PathBuf::from("pts/ptmx"));
+    }
+}
@@ -1,48 +1,158 @@
 use crate::Error;
 use crate::bundle;
+use crate::config::linux::Seccomp as SeccompConfig;
 use crate::libcontainer::Namespace;
 use crate::libcontainer::NamespaceType;
+use crate::libcontainer::linux::namespace::IdMap;
+use crate::libcontainer::linux::device::Device;
+use crate::libcontainer::linux::device::DeviceType;
+use crate::libcontainer::linux::mount::propagation::Propagation;
+use crate::libcontainer::linux::capabilities::Capabilities;
+use crate::libcontainer::linux::seccomp::Action;
+use crate::libcontainer::linux::seccomp::Comparison;
+use crate::libcontainer::linux::seccomp::Mode;
+use crate::libcontainer::linux::seccomp::Condition;
+use crate::libcontainer::linux::seccomp::Rule;
+use crate::libcontainer::linux::seccomp::Seccomp;
+use crate::libcontainer::linux::seccomp::notify::Listener;
 use crate::libcontainer::MountPoint;
+use crate::libcontainer::linux::hook::Hook;
+use crate::libcontainer::linux::hook::HookType;
 use crate::libcontainer::linux::user::User;
+use crate::libcontainer::linux::passwd;
 use crate::libcontainer::linux::rlimit::Rlimit;
 use crate::libcontainer::linux::rlimit::ResourceType;
+use crate::libcontainer::linux::cpuset;
+use crate::libcontainer::linux::cpuset::CpuList;
+use crate::libcontainer::linux::mempolicy::MemoryPolicy;
+use crate::libcontainer::linux::mempolicy::PolicyFlag;
+use crate::libcontainer::linux::mempolicy::PolicyMode;
+use crate::libcontainer::linux::scheduler::Policy;
+use crate::libcontainer::linux::scheduler::Scheduler;
+use crate::libcontainer::linux::scheduler::SchedulerFlag;
+use crate::libcontainer::linux::io_priority::IoClass;
+use crate::libcontainer::linux::io_priority::IoPriority;
+use crate::config::process::Scheduler as SchedulerConfig;
 use std::path::PathBuf;
+use std::str::FromStr;
 use super::Environment;
 use failure::ResultExt;
 
 impl Environment {
-    pub fn build(bundle_dir: &str) -> Result<Environment, Error> {
+    pub fn build(bundle_dir: &str, seccomp_mode: Mode) -> Result<Environment, Error> {
         let config = bundle::load_config(bundle_dir)?;
 
-        let argv = config.process().args();
-        let rootfs = PathBuf::from(bundle_dir).join(config.root().path());
+        let process = match config.process() {
+            Some(process) => process,
+            None => Err(Error::from("config does not have a process".to_string()))?,
+        };
+        let root = match config.root() {
+            Some(root) => root,
+            None => Err(Error::from("config does not have a root".to_string()))?,
+        };
 
+        let argv = process.args();
+        if argv.is_empty() {
+            Err(Error::from("process args must not be empty".to_string()))?;
+        }
+
+        let rootfs = PathBuf::from(bundle_dir).join(root.path());
         if !rootfs.exists() {
             Err(Error::from("bundle does not have a rootfs".to_string())).context(format!("{:?}", rootfs))?;
         }
-        let mut environment = Environment::new(&argv[..], rootfs);
+        let mut environment = Environment::new(argv, rootfs);
 
-        let working_dir = config.process().cwd();
+        let working_dir = process.cwd();
         environment.set_working_dir(working_dir)?;
 
-        if let Some(env_vars) = config.process().env() {
-            for env_var in env_vars {
-                environment.add_env_var(env_var)?;
-            }
+        for env_var in process.env() {
+            environment.add_env_var(env_var)?;
         }
 
         for namespace_config in config.namespaces() {
             let str_type = namespace_config.ns_type();
-            let path = match namespace_config.path() {
-                Some(str_path) => Some(str_path.to_string()),
-                None => None,
-            };
+            let path = namespace_config.path().map(String::from);
 
             let ns_type = NamespaceType::from_str(str_type)?;
             let namespace = Namespace::new(ns_type, path);
             environment.set_namespace(namespace)?;
         }
 
+        if let Some(linux) = config.linux() {
+            let has_mappings = !linux.uid_mappings().is_empty() || !linux.gid_mappings().is_empty();
+            if has_mappings || environment.namespaces.creates_type(&NamespaceType::USER) {
+                let mut uid_maps = Vec::new();
+                for mapping in linux.uid_mappings() {
+                    uid_maps.push(IdMap::new(mapping.container_id(), mapping.host_id(), mapping.size()).context("invalid uid mapping".to_string())?);
+                }
+                let mut gid_maps = Vec::new();
+                for mapping in linux.gid_mappings() {
+                    gid_maps.push(IdMap::new(mapping.container_id(), mapping.host_id(), mapping.size()).context("invalid gid mapping".to_string())?);
+                }
+                environment.set_id_maps(uid_maps, gid_maps)?;
+            }
+
+            if let Some(propagation) = linux.rootfs_propagation() {
+                environment.set_rootfs_propagation(Propagation::from_str(propagation)?)?;
+            }
+
+            for device_config in linux.devices() {
+                let device_type = DeviceType::from_str(device_config.device_type())?;
+                let mut device = Device::new(device_config.path(), device_type, device_config.major(), device_config.minor())?;
+                if let Some(file_mode) = device_config.file_mode() {
+                    device.set_file_mode(file_mode);
+                }
+                device.set_owner(device_config.uid(), device_config.gid());
+                environment.add_device(device);
+            }
+
+            for path in linux.masked_paths() {
+                environment.add_masked_path(path)?;
+            }
+
+            for path in linux.readonly_paths() {
+                environment.add_readonly_path(path)?;
+            }
+
+            if let Some(resources) = linux.resources() {
+                if let Some(cpu) = resources.cpu() {
+                    if let Some(cpus) = cpu.cpus() {
+                        online_list(cpus, &cpuset::online_cpus()?).context("invalid linux.resources.cpu.cpus".to_string())?;
+                    }
+                    if let Some(mems) = cpu.mems() {
+                        online_list(mems, &cpuset::online_nodes()?).context("invalid linux.resources.cpu.mems".to_string())?;
+                    }
+                }
+                environment.set_resources(resources.clone());
+            }
+
+            if let Some(policy_config) = linux.memory_policy() {
+                let memory_policy = build_memory_policy(policy_config.mode(), policy_config.nodes(), policy_config.flags()).context("invalid linux.memoryPolicy".to_string())?;
+                environment.set_memory_policy(memory_policy);
+            }
+
+            if let Some(cgroups_path) = linux.cgroups_path() {
+                environment.set_cgroups_path(cgroups_path);
+            }
+
+            if let Some(sysctl) = linux.sysctl() {
+                for (key, value) in sysctl {
+                    environment.add_sysctl(key, value)?;
+                }
+            }
+        }
+
+        let seccomp_config = config.linux().and_then(|linux| linux.seccomp()).cloned();
+        let seccomp_config = match seccomp_mode {
+            Mode::Config => seccomp_config,
+            Mode::Default => Some(seccomp_config.unwrap_or_else(SeccompConfig::default_profile)),
+            Mode::Unconfined | Mode::Record(_) => None,
+        };
+        if let Some(seccomp_config) = seccomp_config {
+            let seccomp = build_seccomp(&seccomp_config).context("invalid seccomp profile".to_string())?;
+            environment.set_seccomp(seccomp).context("invalid seccomp profile".to_string())?;
+        }
+
         if let Some(hostname) = config.hostname() {
             environment.set_hostname(hostname)?;
         }
@@ -51,24 +161,259 @@ impl Environment {
             let source = mount.source();
             let destination = mount.destination();
             let filesystem_type = mount.filesystem_type();
-            let options = mount.options();
+            let options = mount.options().cloned();
 
             let mount_point = MountPoint::create(source, destination, filesystem_type, options);
             environment.add_mount_point(mount_point);
         }
 
-        if let Some(rlimits) = config.process().rlimits() {
-            for rlimit in rlimits {
-                let resource = ResourceType::from_str(rlimit.rl_type())?;
-                let rlim = Rlimit::new(resource, rlimit.soft(), rlimit.hard());
-                environment.add_rlimit(rlim);
+        for rlimit in process.rlimits() {
+            let resource = ResourceType::from_str(rlimit.rl_type())?;
+            let rlim = Rlimit::new(resource, rlimit.soft(), rlimit.hard());
+            environment.add_rlimit(rlim);
+        }
+
+        if let Some(capabilities_config) = process.capabilities() {
+            let mut capabilities = Capabilities::default();
+            capabilities.set_bounding(capabilities_config.bounding())?;
+            capabilities.set_effective(capabilities_config.effective())?;
+            capabilities.set_inheritable(capabilities_config.inheritable())?;
+            capabilities.set_permitted(capabilities_config.permitted())?;
+            capabilities.set_ambient(capabilities_config.ambient())?;
+            environment.set_capabilities(capabilities);
+        }
+
+        environment.set_no_new_privileges(process.no_new_privileges());
+
+        if let Some(oom_score_adj) = process.oom_score_adj() {
+            environment.set_oom_score_adj(oom_score_adj)?;
+        }
+
+        if let Some(affinity) = process.exec_cpu_affinity() {
+            if let Some(initial) = affinity.initial() {
+                let cpus = online_list(initial, &cpuset::online_cpus()?).context("invalid process.execCPUAffinity.initial".to_string())?;
+                environment.set_initial_cpu_affinity(cpus);
+            }
+            if let Some(final_cpus) = affinity.final_cpus() {
+                let cpus = online_list(final_cpus, &cpuset::online_cpus()?).context("invalid process.execCPUAffinity.final".to_string())?;
+                environment.set_final_cpu_affinity(cpus);
             }
         }
 
-        if let Some(user) = config.process().user() {
-            environment.set_user(User::new(user.uid(), user.gid(), user.additional_gids().clone()));
+        if let Some(scheduler_config) = process.scheduler() {
+            let scheduler = build_scheduler(scheduler_config).context("invalid process.scheduler".to_string())?;
+            environment.set_scheduler(scheduler);
+        }
+
+        if let Some(io_priority_config) = process.io_priority() {
+            let class = IoClass::from_str(io_priority_config.class()).context("invalid process.ioPriority".to_string())?;
+            let io_priority = IoPriority::new(class, io_priority_config.priority()).context("invalid process.ioPriority".to_string())?;
+            environment.set_io_priority(io_priority);
+        }
+
+        if let Some(user) = process.user() {
+            let (uid, gid, mut additional_gids) = match user.username() {
+                Some(username) => {
+                    let resolved = passwd::resolve(&environment.rootfs, username).context("cannot resolve process user".to_string())?;
+                    // Ids given along with the username must name the same user.
+                    if user.uid().is_some_and(|uid| uid != resolved.uid) || user.gid().is_some_and(|gid| gid != resolved.gid) {
+                        Err(Error::from(format!("process.user ids do not match username {} ({}:{})", username, resolved.uid, resolved.gid)))?;
+                    }
+                    if !environment.env_vars.iter().any(|(key, _)| key == "HOME") {
+                        environment.add_env_var(&format!("HOME={}", resolved.home))?;
+                    }
+                    (resolved.uid, resolved.gid, resolved.additional_gids)
+                },
+                None => match (user.uid(), user.gid()) {
+                    (Some(uid), Some(gid)) => (uid, gid, Vec::new()),
+                    _ => Err(Error::from("process.user requires uid and gid without username".to_string()))?,
+                },
+            };
+            for gid in user.additional_gids() {
+                if !additional_gids.contains(gid) {
+                    additional_gids.push(*gid);
+                }
+            }
+            let additional_gids = additional_gids.iter().map(|gid| i64::from(*gid)).collect::<Vec<i64>>();
+            let additional_gids = if additional_gids.is_empty() { None } else { Some(additional_gids) };
+            let mut container_user = User::new(i64::from(uid), i64::from(gid), additional_gids);
+            if let Some(umask) = user.umask() {
+                container_user.set_umask(umask);
+            }
+            environment.set_user(container_user);
+        }
+
+        if let Some(hooks) = config.hooks() {
+            let hooks_by_type = vec![
+                (HookType::Prestart, hooks.prestart()),
+                (HookType::CreateRuntime, hooks.create_runtime()),
+                (HookType::CreateContainer, hooks.create_container()),
+                (HookType::StartContainer, hooks.start_container()),
+                (HookType::Poststart, hooks.poststart()),
+                (HookType::Poststop, hooks.poststop()),
+            ];
+            for (hook_type, hook_list) in hooks_by_type {
+                for hook in hook_list {
+                    let hook = Hook::new(hook.path(), hook.args(), hook.env(), hook.timeout()).context(format!("invalid {} hook", hook_type))?;
+                    environment.add_hook(hook_type, hook);
+                }
+            }
+        }
+
+        if let Some(annotations) = config.annotations() {
+            environment.set_annotations(annotations.clone());
         }
 
         Ok(environment)
     }
 }
+
+// An empty list would leave the process without cpus, or the cgroup
+// without any to give, so it is refused along with offline entries.
+fn online_list(list: &str, online: &CpuList) -> Result<CpuList, Error> {
+    let cpus = CpuList::from_str(list)?;
+    if cpus.is_empty() {
+        Err(Error::from("list must not be empty".to_string())).context(format!("{:?}", list))?;
+    }
+    cpus.check_online(online)?;
+    Ok(cpus)
+}
+
+fn build_memory_policy(mode: &str, nodes: Option<&str>, flags: &[String]) -> Result<MemoryPolicy, Error> {
+    let mode = PolicyMode::from_str(mode)?;
+    let nodes = match nodes {
+        Some(nodes) => CpuList::from_str(nodes)?,
+        None => CpuList::default(),
+    };
+    nodes.check_online(&cpuset::online_nodes()?).context("invalid nodes".to_string())?;
+    let mut policy_flags = Vec::new();
+    for flag in flags {
+        policy_flags.push(PolicyFlag::from_str(flag)?);
+    }
+    MemoryPolicy::new(mode, nodes, policy_flags)
+}
+
+fn build_scheduler(scheduler_config: &SchedulerConfig) -> Result<Scheduler, Error> {
+    let policy = Policy::from_str(scheduler_config.policy())?;
+    let mut flags = Vec::new();
+    for flag in scheduler_config.flags() {
+        flags.push(SchedulerFlag::from_str(flag)?);
+    }
+    let mut scheduler = Scheduler::new(policy, scheduler_config.nice(), scheduler_config.priority(), flags)?;
+    scheduler.set_deadline_params(scheduler_config.runtime(), scheduler_config.deadline(), scheduler_config.period())?;
+    Ok(scheduler)
+}
+
+fn build_seccomp(seccomp_config: &SeccompConfig) -> Result<Seccomp, Error> {
+    let default_action = Action::new(seccomp_config.default_action(), seccomp_config.default_errno_ret())?;
+    let mut seccomp = Seccomp::new(default_action);
+    for arch in seccomp_config.architectures() {
+        seccomp.add_architecture(arch)?;
+    }
+    for flag in seccomp_config.flags() {
+        seccomp.add_flag(flag)?;
+    }
+    for syscall in seccomp_config.syscalls() {
+        let action = Action::new(syscall.action(), syscall.errno_ret())?;
+        let mut rule = Rule::new(syscall.names(), action);
+        for arg in syscall.args() {
+            let comparison = Comparison::from_str(arg.op())?;
+            rule.add_condition(Condition::new(arg.index(), comparison, arg.value(), arg.value_two())?);
+        }
+        seccomp.add_rule(rule);
+    }
+    if let Some(path) = seccomp_config.listener_path() {
+        seccomp.set_listener(Listener::new(path, seccomp_config.listener_metadata()));
+    }
+    Ok(seccomp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::fs;
+    use tempfile::tempdir;
+    use tempfile::TempDir;
+
+    fn setup_bundle(seccomp: Option<serde_json::Value>) -> TempDir {
+        let bundle = tempdir().unwrap();
+        let mut config = json!({
+            "ociVersion": "1.0.2",
+            "process": {"cwd": "/", "args": ["sh"]},
+            "root": {"path": "rootfs"},
+        });
+        if let Some(seccomp) = seccomp {
+            config["linux"] = json!({"seccomp": seccomp});
+        }
+        fs::create_dir(bundle.path().join("rootfs")).unwrap();
+        fs::write(bundle.path().join("config.json"), config.to_string()).unwrap();
+        bundle
+    }
+
+    #[test]
+    fn environment_build_applies_default_seccomp_only_when_config_has_none() {
+        let without_profile = setup_bundle(None);
+        let with_profile = setup_bundle(Some(json!({"defaultAction": "SCMP_ACT_ALLOW"})));
+        let build = |bundle: &TempDir, mode: Mode| Environment::build(bundle.path().to_str().unwrap(), mode).unwrap().seccomp;
+
+        assert_eq!(build(&without_profile, Mode::Config), None);
+        assert_eq!(build(&with_profile, Mode::Default), Some(Seccomp::new(Action::Allow)));
+        assert_eq!(build(&with_profile, Mode::Unconfined), None);
+
+        let default_profile = build(&without_profile, Mode::Default).unwrap();
+        let filter = default_profile.compile().unwrap();
+        assert!(filter.len() > 1000, "expect default profile to have an allowlist, got {} instructions", filter.len());
+    }
+
+    fn setup_bundle_with_user(user: serde_json::Value) -> TempDir {
+        let bundle = tempdir().unwrap();
+        let config = json!({
+            "ociVersion": "1.0.2",
+            "process": {"cwd": "/", "args": ["sh"], "user": user},
+            "root": {"path": "rootfs"},
+        });
+        fs::create_dir_all(bundle.path().join("rootfs/etc")).unwrap();
+        fs::write(bundle.path().join("rootfs/etc/passwd"), "root:x:0:0::/root:/bin/sh\nnginx:x:101:102::/var/www:/bin/sh\n").unwrap();
+        fs::write(bundle.path().join("rootfs/etc/group"), "root:x:0:\nnginx:x:102:\n").unwrap();
+        fs::write(bundle.path().join("config.json"), config.to_string()).unwrap();
+        bundle
+    }
+
+    #[test]
+    fn environment_build_requires_user_ids_without_username() {
+        let build = |user: serde_json::Value| Environment::build(setup_bundle_with_user(user).path().to_str().unwrap(), Mode::Config);
+
+        assert!(build(json!({})).is_err());
+        assert!(build(json!({"uid": 1000})).is_err());
+        assert!(build(json!({"gid": 1000})).is_err());
+
+        let environment = build(json!({"uid": 1000, "gid": 1001})).unwrap();
+        assert_eq!((environment.user.uid(), environment.user.gid()), (1000, 1001));
+    }
+
+    #[test]
+    fn environment_build_rejects_user_ids_that_disagree_with_username() {
+        let build = |user: serde_json::Value| Environment::build(setup_bundle_with_user(user).path().to_str().unwrap(), Mode::Config);
+
+        assert!(build(json!({"username": "nginx", "uid": 1000})).is_err());
+        assert!(build(json!({"username": "nginx", "uid": 101, "gid": 0})).is_err());
+
+        for user in [json!({"username": "nginx"}), json!({"username": "nginx", "uid": 101, "gid": 102})].iter() {
+            let environment = build(user.clone()).unwrap();
+            assert_eq!((environment.user.uid(), environment.user.gid()), (101, 102));
+        }
+    }
+
+    #[test]
+    fn environment_build_returns_error_with_invalid_seccomp_profile() {
+        let bundle = setup_bundle(Some(json!({
+            "defaultAction": "SCMP_ACT_ALLOW",
+            "syscalls": [{"names": ["read"], "action": "SCMP_ACT_ERRNO", "args": [{"index": 0, "value": 1, "op": "SCMP_CMP_SIMILAR"}]}],
+        })));
+
+        let result = Environment::build(bundle.path().to_str().unwrap(), Mode::Config);
+
+        assert!(result.is_err(), "expect {:?} to be err", result);
+    }
+}
@@ -1,19 +1,37 @@
 mod process;
 mod config;
 
+pub use self::process::InitProcess;
+
 use crate::Error;
+use crate::config::linux::Resources;
 use crate::libcontainer::linux::rlimit::Rlimit;
 use crate::libcontainer::linux::mount::MountPoint;
 use crate::libcontainer::linux::user::User;
 use crate::libcontainer::linux::namespace::Namespace;
 use crate::libcontainer::linux::namespace::NamespaceType;
 use crate::libcontainer::linux::namespace::NamespaceList;
+use crate::libcontainer::linux::namespace::IdMap;
+use crate::libcontainer::linux::sysctl::Sysctl;
+use crate::libcontainer::linux::device::Device;
+use crate::libcontainer::linux::capabilities::Capabilities;
+use crate::libcontainer::linux::seccomp::Seccomp;
+use crate::libcontainer::linux::mount::propagation::Propagation;
+use crate::libcontainer::linux::hook::Hook;
+use crate::libcontainer::linux::hook::HookType;
+use crate::libcontainer::linux::cpuset::CpuList;
+use crate::libcontainer::linux::mempolicy::MemoryPolicy;
+use crate::libcontainer::linux::scheduler::Scheduler;
+use crate::libcontainer::linux::io_priority::IoPriority;
 use serde::Deserialize;
 use serde::Serialize;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use failure::ResultExt;
 
 const DEFAULT_WORKING_DIR: &str = "/";
+const OOM_SCORE_ADJ_MIN: i32 = -1000;
+const OOM_SCORE_ADJ_MAX: i32 = 1000;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Environment {
@@ -26,6 +44,26 @@ pub struct Environment {
     env_vars: Vec<(String, String)>,
     rlimits: Vec<Rlimit>,
     user: User,
+    hooks: Vec<(HookType, Hook)>,
+    annotations: BTreeMap<String, String>,
+    uid_maps: Vec<IdMap>,
+    gid_maps: Vec<IdMap>,
+    sysctls: Vec<Sysctl>,
+    masked_paths: Vec<PathBuf>,
+    readonly_paths: Vec<PathBuf>,
+    devices: Vec<Device>,
+    rootfs_propagation: Option<Propagation>,
+    capabilities: Option<Capabilities>,
+    no_new_privileges: bool,
+    oom_score_adj: Option<i32>,
+    seccomp: Option<Seccomp>,
+    resources: Resources,
+    cgroups_path: Option<String>,
+    memory_policy: Option<MemoryPolicy>,
+    initial_cpu_affinity: Option<CpuList>,
+    final_cpu_affinity: Option<CpuList>,
+    scheduler: Option<Scheduler>,
+    io_priority: Option<IoPriority>,
 }
 
 impl Environment {
@@ -40,21 +78,63 @@ impl Environment {
             env_vars: Vec::new(),
             rlimits: Vec::new(),
             user: User::root(),
+            hooks: Vec::new(),
+            annotations: BTreeMap::new(),
+            uid_maps: Vec::new(),
+            gid_maps: Vec::new(),
+            sysctls: Vec::new(),
+            masked_paths: Vec::new(),
+            readonly_paths: Vec::new(),
+            devices: Device::defaults(),
+            rootfs_propagation: None,
+            capabilities: None,
+            no_new_privileges: false,
+            oom_score_adj: None,
+            seccomp: None,
+            resources: Resources::default(),
+            cgroups_path: None,
+            memory_policy: None,
+            initial_cpu_affinity: None,
+            final_cpu_affinity: None,
+            scheduler: None,
+            io_priority: None,
         }
     }
 
-    pub fn spawn_process(&self) -> Result<i32, Error> {
-        for namespace in self.namespaces.as_vec() {
-            namespace.enter()?;
-        }
+    pub fn annotations(&self) -> &BTreeMap<String, String> {
+        &self.annotations
+    }
+
+    pub fn resources(&self) -> &Resources {
+        &self.resources
+    }
+
+    pub fn cgroups_path(&self) -> Option<&str> {
+        self.cgroups_path.as_deref()
+    }
+
+    pub fn spawn_process(&self) -> Result<InitProcess, Error> {
+        process::clone(self)
+    }
 
-        process::clone(&self)
+    pub fn kill_process(pid: i32) -> Result<(), Error> {
+        process::kill(pid)
     }
 
     pub fn wait_process(pid: i32) -> Result<(), Error> {
         process::wait(pid)
     }
 
+    pub fn unmount_points(&self) -> Result<(), Error> {
+        if self.namespaces.contains_type(&NamespaceType::MOUNT) {
+            return Ok(());
+        }
+        for mount_point in self.mount_list.iter().rev() {
+            mount_point.unmount(&self.rootfs)?;
+        }
+        Ok(())
+    }
+
     pub fn set_working_dir(&mut self, working_dir: &str) -> Result<(), Error> {
         let cwd = PathBuf::from(working_dir);
 
@@ -75,14 +155,61 @@ impl Environment {
         Ok(())
     }
 
+    pub fn set_rootfs_propagation(&mut self, propagation: Propagation) -> Result<(), Error> {
+        if !self.namespaces.creates_type(&NamespaceType::MOUNT) {
+            Err("container needs a new mount namespace in order to set rootfs propagation".to_string())?
+        }
+
+        self.rootfs_propagation = Some(propagation);
+        Ok(())
+    }
+
     pub fn set_namespace(&mut self, namespace: Namespace) -> Result<(), Error> {
         self.namespaces.insert(namespace)
     }
 
+    pub fn set_id_maps(&mut self, uid_maps: Vec<IdMap>, gid_maps: Vec<IdMap>) -> Result<(), Error> {
+        if !self.namespaces.creates_type(&NamespaceType::USER) {
+            Err("container needs a new user namespace in order to set uid and gid mappings".to_string())?
+        }
+        if uid_maps.is_empty() || gid_maps.is_empty() {
+            Err("a new user namespace requires both uid and gid mappings".to_string())?
+        }
+
+        self.uid_maps = uid_maps;
+        self.gid_maps = gid_maps;
+        Ok(())
+    }
+
     pub fn add_mount_point(&mut self, mount_point: MountPoint) {
         self.mount_list.push(mount_point);
     }
 
+    pub fn add_device(&mut self, device: Device) {
+        self.devices.retain(|existing| existing.path() != device.path());
+        self.devices.push(device);
+    }
+
+    pub fn add_masked_path(&mut self, path: &str) -> Result<(), Error> {
+        let masked_path = PathBuf::from(path);
+        if masked_path.is_relative() {
+            Err(Error::from("masked path must be absolute".to_string())).context(path.to_string())?
+        }
+
+        self.masked_paths.push(masked_path);
+        Ok(())
+    }
+
+    pub fn add_readonly_path(&mut self, path: &str) -> Result<(), Error> {
+        let readonly_path = PathBuf::from(path);
+        if readonly_path.is_relative() {
+            Err(Error::from("readonly path must be absolute".to_string())).context(path.to_string())?
+        }
+
+        self.readonly_paths.push(readonly_path);
+        Ok(())
+    }
+
     pub fn add_env_var(&mut self, env_var: &str) -> Result<(), Error> {
         let mut splitted_env: Vec<&str> = env_var.split("=").collect();
         let error_message = "environment variable must have 'KEY=VALUE' format";
@@ -103,6 +230,12 @@ impl Environment {
         Ok(())
     }
 
+    pub fn add_sysctl(&mut self, key: &str, value: &str) -> Result<(), Error> {
+        let sysctl = Sysctl::new(key, value, &self.namespaces)?;
+        self.sysctls.push(sysctl);
+        Ok(())
+    }
+
     pub fn add_rlimit(&mut self, rlimit: Rlimit) {
         self.rlimits.push(rlimit);
     }
@@ -110,13 +243,83 @@ impl Environment {
     pub fn set_user(&mut self, user: User) {
         self.user = user;
     }
+
+    pub fn set_capabilities(&mut self, capabilities: Capabilities) {
+        self.capabilities = Some(capabilities);
+    }
+
+    pub fn set_no_new_privileges(&mut self, no_new_privileges: bool) {
+        self.no_new_privileges = no_new_privileges;
+    }
+
+    pub fn set_oom_score_adj(&mut self, oom_score_adj: i32) -> Result<(), Error> {
+        if !(OOM_SCORE_ADJ_MIN..=OOM_SCORE_ADJ_MAX).contains(&oom_score_adj) {
+            Err(Error::from(format!("oom score adj must be between {} and {}", OOM_SCORE_ADJ_MIN, OOM_SCORE_ADJ_MAX))).context(oom_score_adj.to_string())?
+        }
+
+        self.oom_score_adj = Some(oom_score_adj);
+        Ok(())
+    }
+
+    // Compiling here reports an invalid profile before the container is
+    // created, rather than from the init process.
+    pub fn set_seccomp(&mut self, seccomp: Seccomp) -> Result<(), Error> {
+        seccomp.compile()?;
+        self.seccomp = Some(seccomp);
+        Ok(())
+    }
+
+    pub fn set_resources(&mut self, resources: Resources) {
+        self.resources = resources;
+    }
+
+    pub fn set_cgroups_path(&mut self, cgroups_path: &str) {
+        self.cgroups_path = Some(cgroups_path.to_string());
+    }
+
+    pub fn set_memory_policy(&mut self, memory_policy: MemoryPolicy) {
+        self.memory_policy = Some(memory_policy);
+    }
+
+    pub fn set_initial_cpu_affinity(&mut self, cpus: CpuList) {
+        self.initial_cpu_affinity = Some(cpus);
+    }
+
+    pub fn set_final_cpu_affinity(&mut self, cpus: CpuList) {
+        self.final_cpu_affinity = Some(cpus);
+    }
+
+    pub fn set_scheduler(&mut self, scheduler: Scheduler) {
+        self.scheduler = Some(scheduler);
+    }
+
+    pub fn set_io_priority(&mut self, io_priority: IoPriority) {
+        self.io_priority = Some(io_priority);
+    }
+
+    pub fn add_hook(&mut self, hook_type: HookType, hook: Hook) {
+        self.hooks.push((hook_type, hook));
+    }
+
+    pub fn run_hooks(&self, hook_type: HookType, state: &str) -> Result<(), Error> {
+        for (_, hook) in self.hooks.iter().filter(|(t, _)| *t == hook_type) {
+            hook.run(state).context(format!("{} hook failed", hook_type))?;
+        }
+        Ok(())
+    }
+
+    pub fn set_annotations(&mut self, annotations: BTreeMap<String, String>) {
+        self.annotations = annotations;
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use std::path::Path;
     use std::path::PathBuf;
+    use crate::libcontainer::linux::device::DeviceType;
     use crate::libcontainer::linux::namespace::Namespace;
     use crate::libcontainer::linux::namespace::NamespaceType;
     use crate::libcontainer::linux::rlimit::Rlimit;
@@ -205,6 +408,19 @@ mod tests {
 
     }
 
+    #[test]
+    fn environment_set_rootfs_propagation_requires_new_mount_namespace() {
+        let mut environment = setup_environment();
+
+        let without_namespace = environment.set_rootfs_propagation(Propagation::Slave);
+        environment.set_namespace(Namespace::new(NamespaceType::MOUNT, None)).unwrap();
+        let with_namespace = environment.set_rootfs_propagation(Propagation::Slave);
+
+        assert!(without_namespace.is_err(), "expect {:?} to be err", without_namespace);
+        assert!(with_namespace.is_ok(), "expect {:?} to be ok", with_namespace);
+        assert_eq!(environment.rootfs_propagation, Some(Propagation::Slave));
+    }
+
     #[test]
     fn environment_set_namespace() {
         let mut environment = setup_environment();
@@ -229,6 +445,28 @@ mod tests {
         assert!(result2.is_err(), "expect {:?} to be err", result2);
     }
 
+    #[test]
+    fn environment_set_id_maps_returns_error_without_user_namespace() {
+        let mut environment = setup_environment();
+
+        let result = environment.set_id_maps(vec![IdMap::new(0, 1000, 1).unwrap()], vec![IdMap::new(0, 1000, 1).unwrap()]);
+
+        assert!(result.is_err(), "expect {:?} to be err", result);
+        assert!(environment.uid_maps.is_empty());
+    }
+
+    #[test]
+    fn environment_set_id_maps_requires_uid_and_gid_mappings() {
+        let mut environment = setup_environment();
+        environment.set_namespace(Namespace::new(NamespaceType::USER, None)).unwrap();
+
+        let missing_gid_maps = environment.set_id_maps(vec![IdMap::new(0, 1000, 1).unwrap()], vec![]);
+        let result = environment.set_id_maps(vec![IdMap::new(0, 1000, 1).unwrap()], vec![IdMap::new(0, 1000, 1).unwrap()]);
+
+        assert!(missing_gid_maps.is_err(), "expect {:?} to be err", missing_gid_maps);
+        assert!(result.is_ok(), "expect {:?} to be ok", result);
+    }
+
     #[test]
     fn environment_add_mount_point() {
         let mut environment = setup_environment();
@@ -239,6 +477,40 @@ mod tests {
         assert_eq!(environment.mount_list.len(), 1);
     }
 
+    #[test]
+    fn environment_devices_default_to_oci_default_devices() {
+        let environment = setup_environment();
+        let paths: Vec<&Path> = environment.devices.iter().map(|device| device.path()).collect();
+
+        for path in &["/dev/null", "/dev/zero", "/dev/full", "/dev/random", "/dev/urandom", "/dev/tty"] {
+            assert!(paths.contains(&Path::new(path)), "expect {:?} to contain {}", paths, path);
+        }
+    }
+
+    #[test]
+    fn environment_add_device_replaces_device_with_same_path() {
+        let mut environment = setup_environment();
+        let count = environment.devices.len();
+
+        environment.add_device(Device::new("/dev/null", DeviceType::Char, Some(1), Some(3)).unwrap());
+        environment.add_device(Device::new("/dev/fuse", DeviceType::Char, Some(10), Some(229)).unwrap());
+
+        assert_eq!(environment.devices.len(), count + 1);
+    }
+
+    #[test]
+    fn environment_add_masked_and_readonly_paths_require_absolute_paths() {
+        let mut environment = setup_environment();
+
+        assert!(environment.add_masked_path("proc/kcore").is_err());
+        assert!(environment.add_readonly_path("proc/sys").is_err());
+        environment.add_masked_path("/proc/kcore").unwrap();
+        environment.add_readonly_path("/proc/sys").unwrap();
+
+        assert_eq!(environment.masked_paths, vec![PathBuf::from("/proc/kcore")]);
+        assert_eq!(environment.readonly_paths, vec![PathBuf::from("/proc/sys")]);
+    }
+
     #[test]
     fn environment_add_env_var() {
         let mut environment = setup_environment();
@@ -271,6 +543,19 @@ mod tests {
         assert!(result.is_err(), "expect {:?} to be err", result);
     }
 
+    #[test]
+    fn environment_add_sysctl_validates_against_environment_namespaces() {
+        let mut environment = setup_environment();
+
+        let without_namespace = environment.add_sysctl("net.core.somaxconn", "1024");
+        environment.set_namespace(Namespace::new(NamespaceType::NETWORK, None)).unwrap();
+        let with_namespace = environment.add_sysctl("net.core.somaxconn", "1024");
+
+        assert!(without_namespace.is_err(), "expect {:?} to be err", without_namespace);
+        assert!(with_namespace.is_ok(), "expect {:?} to be ok", with_namespace);
+        assert_eq!(environment.sysctls.len(), 1);
+    }
+
     #[test]
     fn environment_add_rlimit() {
         let mut environment = setup_environment();
@@ -281,6 +566,39 @@ mod tests {
         assert_eq!(environment.rlimits.len(), 1);
     }
 
+    #[test]
+    fn environment_capabilities_default_to_none() {
+        let mut environment = setup_environment();
+        assert!(environment.capabilities.is_none());
+
+        environment.set_capabilities(Capabilities::default());
+
+        assert_eq!(environment.capabilities, Some(Capabilities::default()));
+    }
+
+    #[test]
+    fn environment_set_oom_score_adj_validates_range() {
+        let mut environment = setup_environment();
+
+        for invalid in &[-1001, 1001] {
+            let result = environment.set_oom_score_adj(*invalid);
+            assert!(result.is_err(), "expect {:?} to be err", result);
+        }
+        environment.set_oom_score_adj(500).unwrap();
+
+        assert_eq!(environment.oom_score_adj, Some(500));
+    }
+
+    #[test]
+    fn environment_no_new_privileges_defaults_to_false() {
+        let mut environment = setup_environment();
+        assert!(!environment.no_new_privileges);
+
+        environment.set_no_new_privileges(true);
+
+        assert!(environment.no_new_privileges);
+    }
+
     #[test]
     fn environment_set_user() {
         let mut environment = setup_environment();
@@ -290,4 +608,20 @@ mod tests {
 
         assert_eq!(environment.user, User::root());
     }
+
+    #[test]
+    fn environment_run_hooks_only_runs_hooks_of_the_given_type() {
+        let mut environment = setup_environment();
+        let failing = Hook::new("/bin/false", &["false".to_string()], &[], None).unwrap();
+        let succeeding = Hook::new("/bin/true", &["true".to_string()], &[], None).unwrap();
+
+        environment.add_hook(HookType::Poststop, failing);
+        environment.add_hook(HookType::Prestart, succeeding);
+
+        let prestart_result = environment.run_hooks(HookType::Prestart, "{}");
+        let poststop_result = environment.run_hooks(HookType::Poststop, "{}");
+
+        assert!(prestart_result.is_ok(), "expect {:?} to be ok", prestart_result);
+        assert!(poststop_result.is_err(), "expect {:?} to be err", poststop_result);
+    }
 }
@@ -3,28 +3,177 @@ use crate::filesystem::pathbuf;
 use crate::libcontainer::linux::rlimit::Rlimit;
 use crate::libcontainer::linux::user::User;
 use crate::libcontainer::linux::mount::MountPoint;
+use crate::libcontainer::linux::mount::propagation;
+use crate::libcontainer::linux::mount::propagation::Propagation;
+use crate::libcontainer::linux::mount::protect;
+use crate::libcontainer::linux::device;
+use crate::libcontainer::linux::device::Device;
+use crate::filesystem::securejoin::secure_join;
+use std::path::Path;
+use crate::libcontainer::linux::namespace::NamespaceList;
+use crate::libcontainer::linux::namespace::NamespaceType;
+use crate::libcontainer::linux::namespace::id_map;
+use crate::libcontainer::linux::hook::HookType;
+use crate::libcontainer::linux::sysctl::Sysctl;
+use crate::libcontainer::linux::cpuset::CpuList;
+use crate::libcontainer::linux::mempolicy::MemoryPolicy;
+use crate::libcontainer::linux::scheduler::Scheduler;
+use crate::libcontainer::linux::io_priority::IoPriority;
+use crate::libcontainer::linux::capabilities::Capabilities;
+use crate::libcontainer::linux::seccomp::Seccomp;
+use crate::libcontainer::linux::seccomp::notify;
+use crate::libcontainer::linux::seccomp::notify::Listener;
 use failure::ResultExt;
+use nix::errno::Errno;
+use nix::fcntl::OFlag;
 use nix::sched;
+use nix::sched::CloneFlags;
+use nix::sys::stat;
+use nix::sys::stat::Mode;
 use nix::unistd;
+use nix::sys::signal;
+use nix::sys::signal::Signal;
 use nix::sys::wait;
 use nix::unistd::Pid;
 use nix::unistd::Uid;
 use nix::unistd::Gid;
-use nix::sched::CloneFlags;
 use nix::sys::wait::WaitPidFlag;
 use std::env;
 use std::process;
 use std::ffi::OsStr;
 use std::ffi::CString;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::io::FromRawFd;
+use std::os::unix::io::RawFd;
+use std::os::unix::net::UnixStream;
 use std::path::PathBuf;
 use super::Environment;
 
-pub fn clone(environment: &Environment) -> Result<i32, Error> {
+// The init process blocks before touching the container environment until
+// the runtime sends the container state through the start pipe. Any error
+// from then on is reported back through the error pipe, which is closed on
+// exec, so an empty read on the runtime side means the exec succeeded.
+// With seccomp notify rules the init process also sends the notify fd right
+// before exec, which the runtime hands over to the listener.
+#[derive(Debug)]
+pub struct InitProcess {
+    pid: i32,
+    start_pipe: Option<File>,
+    error_pipe: File,
+    seccomp_socket: Option<UnixStream>,
+    seccomp_listener: Option<Listener>,
+}
+
+impl InitProcess {
+    pub fn pid(&self) -> i32 {
+        self.pid
+    }
+
+    pub fn start(&mut self, state: &str) -> Result<(), Error> {
+        if let Some(mut start_pipe) = self.start_pipe.take() {
+            start_pipe.write_all(state.as_bytes()).context(format!("cannot send state to init process {}", self.pid))?;
+        }
+
+        if let (Some(socket), Some(listener)) = (self.seccomp_socket.take(), &self.seccomp_listener) {
+            // Nothing is received when the init process fails before
+            // installing the filter, its error is in the error pipe.
+            if let (_, Some(notify_fd)) = notify::receive_fd(socket.as_raw_fd()).context("cannot receive seccomp fd".to_string())? {
+                let result = listener.send(notify_fd, self.pid, state);
+                let _ = unistd::close(notify_fd);
+                result?;
+            }
+        }
+
+        let mut message = String::new();
+        self.error_pipe.read_to_string(&mut message).context(format!("cannot read from init process {}", self.pid))?;
+        if !message.is_empty() {
+            Err(Error::from(message)).context(format!("init process {} failed", self.pid))?;
+        }
+        Ok(())
+    }
+}
+
+struct ChildPipes {
+    start_reader: RawFd,
+    start_writer: RawFd,
+    error_reader: RawFd,
+    error_writer: RawFd,
+    seccomp_sockets: Option<(RawFd, RawFd)>,
+}
+
+pub fn clone(environment: &Environment) -> Result<InitProcess, Error> {
+    let (start_reader, start_writer) = unistd::pipe2(OFlag::O_CLOEXEC).context("cannot create start pipe".to_string())?;
+    let start_pipe = unsafe { File::from_raw_fd(start_writer) };
+    let start_reader = unsafe { File::from_raw_fd(start_reader) };
+    let (error_reader, error_writer) = unistd::pipe2(OFlag::O_CLOEXEC).context("cannot create error pipe".to_string())?;
+    let error_pipe = unsafe { File::from_raw_fd(error_reader) };
+    let error_writer = unsafe { File::from_raw_fd(error_writer) };
+    let seccomp_listener = environment.seccomp.as_ref().and_then(|seccomp| seccomp.listener()).cloned();
+    let seccomp_sockets = match seccomp_listener {
+        Some(_) => Some(UnixStream::pair().context("cannot create seccomp socket".to_string())?),
+        None => None,
+    };
+
+    let pipes = ChildPipes {
+        start_reader: start_reader.as_raw_fd(),
+        start_writer: start_pipe.as_raw_fd(),
+        error_reader: error_pipe.as_raw_fd(),
+        error_writer: error_writer.as_raw_fd(),
+        seccomp_sockets: seccomp_sockets.as_ref().map(|(runtime, init)| (runtime.as_raw_fd(), init.as_raw_fd())),
+    };
+
     let stack = &mut[0; 1024*1024];
-    let exec_fn = Box::new(|| child_callback(&environment));
+    let exec_fn = Box::new(|| child_callback(environment, &pipes));
 
-    let pid = sched::clone(exec_fn, stack, CloneFlags::empty(), None)?;
-    Ok(pid.as_raw())
+    let flags = environment.namespaces.clone_flags();
+    let pid = match environment.namespaces.to_enter_before_clone() {
+        Some(namespace) => {
+            let runtime_namespace = File::open("/proc/self/ns/pid").context("cannot open runtime pid namespace".to_string())?;
+            namespace.enter()?;
+            let result = sched::clone(exec_fn, stack, flags, None);
+            let restored = sched::setns(runtime_namespace.as_raw_fd(), CloneFlags::CLONE_NEWPID);
+            let pid = result?.as_raw();
+            if let Err(err) = restored {
+                let _ = kill(pid);
+                Err(err).context("cannot return to runtime pid namespace".to_string())?;
+            }
+            pid
+        },
+        None => sched::clone(exec_fn, stack, flags, None)?.as_raw(),
+    };
+
+    // The init process is still blocked on the start pipe, so the mappings
+    // are in place before it does anything inside its user namespace.
+    if environment.namespaces.creates_type(&NamespaceType::USER) {
+        let result = id_map::write_uid_maps(pid, &environment.uid_maps)
+            .and_then(|_| id_map::write_gid_maps(pid, &environment.gid_maps));
+        if let Err(err) = result {
+            let _ = kill(pid);
+            return Err(err);
+        }
+    }
+
+    // Set from here so it holds before the runtime moves the init process
+    // into the container cgroup.
+    if let Some(cpus) = &environment.initial_cpu_affinity {
+        if let Err(err) = cpus.set_affinity(pid) {
+            let _ = kill(pid);
+            return Err(err);
+        }
+    }
+
+    Ok(InitProcess {
+        pid,
+        start_pipe: Some(start_pipe),
+        error_pipe,
+        seccomp_socket: seccomp_sockets.map(|(runtime, _)| runtime),
+        seccomp_listener,
+    })
 }
 
 pub fn wait(pid: i32) -> Result<(), Error> {
@@ -32,26 +181,146 @@ pub fn wait(pid: i32) -> Result<(), Error> {
     Ok(())
 }
 
-pub fn child_callback(environment: &Environment) -> isize {
-    if let Err(err) = try_create_environment(environment) {
-        eprintln!("Error: {}", err);
+pub fn kill(pid: i32) -> Result<(), Error> {
+    match signal::kill(Pid::from_raw(pid), Signal::SIGKILL) {
+        Err(err) if err.as_errno() == Some(Errno::ESRCH) => return Ok(()),
+        result => result.context(format!("cannot kill process {}", pid))?,
+    }
+    wait(pid)
+}
+
+fn child_callback(environment: &Environment, pipes: &ChildPipes) -> isize {
+    let _ = unistd::close(pipes.start_writer);
+    let _ = unistd::close(pipes.error_reader);
+    let mut error_pipe = unsafe { File::from_raw_fd(pipes.error_writer) };
+    let seccomp_socket = pipes.seccomp_sockets.map(|(runtime, init)| {
+        let _ = unistd::close(runtime);
+        init
+    });
+
+    if let Err(err) = wait_start(pipes.start_reader).and_then(|state| try_create_environment(environment, &state, seccomp_socket)) {
+        let _ = write!(error_pipe, "{}", err);
         process::exit(exitcode::OSERR);
     }
-    return 0;
+    0
+}
+
+fn wait_start(start_reader: RawFd) -> Result<String, Error> {
+    let mut start_pipe = unsafe { File::from_raw_fd(start_reader) };
+    let mut state = String::new();
+    start_pipe.read_to_string(&mut state).context("cannot read container state".to_string())?;
+    if state.is_empty() {
+        Err(Error::from("container start aborted by the runtime".to_string()))?;
+    }
+    Ok(state)
 }
 
-fn try_create_environment(environment: &Environment) -> Result<(), Error> {
+fn try_create_environment(environment: &Environment, state: &str, seccomp_socket: Option<RawFd>) -> Result<(), Error> {
+    apply_namespaces(&environment.namespaces)?;
+    apply_sysctls(&environment.sysctls)?;
+    apply_oom_score_adj(environment.oom_score_adj)?;
+    prepare_rootfs(&environment.rootfs, &environment.namespaces)?;
+    apply_mount_points(&environment.rootfs, &environment.mount_list)?;
+    apply_devices(&environment.rootfs, &environment.devices, environment.namespaces.contains_type(&NamespaceType::USER))?;
+    apply_masked_paths(&environment.rootfs, &environment.masked_paths)?;
+    apply_readonly_paths(&environment.rootfs, &environment.readonly_paths)?;
+    apply_rootfs_propagation(&environment.rootfs, environment.rootfs_propagation)?;
+    // Hooks run here already see the container mounts, but still resolve
+    // their paths against the runtime root.
+    environment.run_hooks(HookType::CreateContainer, state)?;
     apply_chroot(&environment.rootfs)?;
     apply_env_vars(&environment.env_vars);
-    apply_mount_points(&environment.mount_list)?;
     apply_working_dir(&environment.working_dir)?;
     apply_hostname(&environment.hostname)?;
     apply_rlimits(&environment.rlimits)?;
+    environment.run_hooks(HookType::StartContainer, state)?;
+    apply_scheduler(&environment.scheduler)?;
+    apply_io_priority(&environment.io_priority)?;
+    // Without noNewPrivileges the filter can only be installed while
+    // CAP_SYS_ADMIN is held, so it goes in before the user and capabilities
+    // are applied, as runc does. The profile must then allow the syscalls
+    // made until exec.
+    if !environment.no_new_privileges {
+        apply_seccomp(&environment.seccomp, seccomp_socket)?;
+    }
+    drop_bounding_capabilities(&environment.capabilities)?;
     apply_user(&environment.user)?;
+    apply_capabilities(&environment.capabilities)?;
+    apply_no_new_privileges(environment.no_new_privileges)?;
+    apply_memory_policy(&environment.memory_policy)?;
+    apply_cpu_affinity(&environment.final_cpu_affinity)?;
+    if environment.no_new_privileges {
+        apply_seccomp(&environment.seccomp, seccomp_socket)?;
+    }
     try_exec(&environment.argv)?;
     Ok(())
 }
 
+fn apply_namespaces(namespaces: &NamespaceList) -> Result<(), Error> {
+    for namespace in namespaces.to_enter() {
+        namespace.enter()?;
+    }
+
+    // Entering a user namespace keeps the runtime credentials, which are
+    // not mapped inside it. Switching to root makes files created from now
+    // on owned by the mapped ids.
+    if namespaces.contains_type(&NamespaceType::USER) {
+        unistd::setgid(Gid::from_raw(0)).context("cannot become root group in user namespace".to_string())?;
+        unistd::setuid(Uid::from_raw(0)).context("cannot become root in user namespace".to_string())?;
+    }
+    Ok(())
+}
+
+// Namespaced sysctls resolve against the namespaces of the writing process,
+// so the runtime /proc is fine to use before changing root.
+fn apply_sysctls(sysctls: &[Sysctl]) -> Result<(), Error> {
+    for sysctl in sysctls {
+        sysctl.set()?;
+    }
+    Ok(())
+}
+
+// Lowering the score needs CAP_SYS_RESOURCE, and /proc/self is only known
+// to be there before changing root.
+fn apply_oom_score_adj(oom_score_adj: Option<i32>) -> Result<(), Error> {
+    if let Some(oom_score_adj) = oom_score_adj {
+        fs::write("/proc/self/oom_score_adj", oom_score_adj.to_string()).context(format!("cannot set oom score adj to {}", oom_score_adj))?;
+    }
+    Ok(())
+}
+
+// Real time policies and raising priorities need CAP_SYS_NICE, which the
+// process may not keep once its capabilities are applied.
+fn apply_scheduler(scheduler: &Option<Scheduler>) -> Result<(), Error> {
+    if let Some(scheduler) = scheduler {
+        scheduler.set()?;
+    }
+    Ok(())
+}
+
+fn apply_io_priority(io_priority: &Option<IoPriority>) -> Result<(), Error> {
+    if let Some(io_priority) = io_priority {
+        io_priority.set()?;
+    }
+    Ok(())
+}
+
+fn apply_memory_policy(memory_policy: &Option<MemoryPolicy>) -> Result<(), Error> {
+    if let Some(memory_policy) = memory_policy {
+        memory_policy.set()?;
+    }
+    Ok(())
+}
+
+// The init process is in the container cgroup by now, the kernel keeps the
+// affinity within the cpus the cgroup allows.
+fn apply_cpu_affinity(cpus: &Option<CpuList>) -> Result<(), Error> {
+    if let Some(cpus) = cpus {
+        cpus.set_affinity(0)?;
+    }
+    Ok(())
+}
+
 fn apply_chroot(rootfs: &PathBuf) -> Result<(), Error> {
     unistd::chroot(rootfs).context(pathbuf::to_string(rootfs.to_path_buf()))?;
     Ok(())
@@ -65,9 +334,47 @@ fn apply_env_vars(env_vars: &Vec<(String, String)>) {
     }
 }
 
-fn apply_mount_points(mount_list: &Vec<MountPoint>) -> Result<(), Error> {
+// Only a mount namespace of our own can be changed without touching the host.
+fn prepare_rootfs(rootfs: &Path, namespaces: &NamespaceList) -> Result<(), Error> {
+    if namespaces.creates_type(&NamespaceType::MOUNT) {
+        propagation::make_root_private()?;
+        propagation::bind_rootfs(rootfs)?;
+    }
+    Ok(())
+}
+
+fn apply_rootfs_propagation(rootfs: &Path, rootfs_propagation: Option<Propagation>) -> Result<(), Error> {
+    if let Some(rootfs_propagation) = rootfs_propagation {
+        propagation::set(rootfs, rootfs_propagation)?;
+    }
+    Ok(())
+}
+
+fn apply_mount_points(rootfs: &Path, mount_list: &Vec<MountPoint>) -> Result<(), Error> {
     for mount_point in mount_list {
-        mount_point.mount()?;
+        mount_point.mount(rootfs)?;
+    }
+    Ok(())
+}
+
+fn apply_devices(rootfs: &Path, devices: &[Device], bind: bool) -> Result<(), Error> {
+    for device in devices {
+        device.create(rootfs, bind)?;
+    }
+    device::create_symlinks(rootfs)?;
+    Ok(())
+}
+
+fn apply_masked_paths(rootfs: &Path, masked_paths: &[PathBuf]) -> Result<(), Error> {
+    for path in masked_paths {
+        protect::mask(&secure_join(rootfs, path)?)?;
+    }
+    Ok(())
+}
+
+fn apply_readonly_paths(rootfs: &Path, readonly_paths: &[PathBuf]) -> Result<(), Error> {
+    for path in readonly_paths {
+        protect::make_readonly(&secure_join(rootfs, path)?)?;
     }
     Ok(())
 }
@@ -91,13 +398,61 @@ fn apply_rlimits(rlimits: &Vec<Rlimit>) -> Result<(), Error> {
     Ok(())
 }
 
+fn drop_bounding_capabilities(capabilities: &Option<Capabilities>) -> Result<(), Error> {
+    if let Some(capabilities) = capabilities {
+        capabilities.drop_bounding()?;
+        Capabilities::keep_on_setuid(true)?;
+    }
+    Ok(())
+}
+
+fn apply_capabilities(capabilities: &Option<Capabilities>) -> Result<(), Error> {
+    if let Some(capabilities) = capabilities {
+        Capabilities::keep_on_setuid(false)?;
+        capabilities.apply()?;
+    }
+    Ok(())
+}
+
+// Groups go first, since changing them needs privileges that setuid drops.
 fn apply_user(user: &User) -> Result<(), Error> {
-    let uid = Uid::from_raw(user.uid() as u32);
-    unistd::setuid(uid).context(format!("cannot set user id {}", uid))?;
+    let groups: Vec<Gid> = user.additional_gids().iter().map(|gid| Gid::from_raw(*gid as u32)).collect();
+    match unistd::setgroups(&groups) {
+        // setgroups is denied in user namespaces mapped without privileges,
+        // which is harmless as long as no supplementary groups were asked.
+        Err(err) if err.as_errno() == Some(Errno::EPERM) && groups.is_empty() => {},
+        result => result.context(format!("cannot set additional group ids {:?}", user.additional_gids()))?,
+    }
 
     let gid = Gid::from_raw(user.gid() as u32);
     unistd::setgid(gid).context(format!("cannot set group id {}", gid))?;
 
+    let uid = Uid::from_raw(user.uid() as u32);
+    unistd::setuid(uid).context(format!("cannot set user id {}", uid))?;
+
+    if let Some(umask) = user.umask() {
+        stat::umask(Mode::from_bits_truncate(umask));
+    }
+
+    Ok(())
+}
+
+fn apply_no_new_privileges(no_new_privileges: bool) -> Result<(), Error> {
+    if no_new_privileges && unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) } != 0 {
+        Err(io::Error::last_os_error()).context("cannot set no new privileges".to_string())?;
+    }
+    Ok(())
+}
+
+// Anything the runtime does after this point must be allowed by the
+// profile, so only passing the notify fd and the exec are left.
+fn apply_seccomp(seccomp: &Option<Seccomp>, seccomp_socket: Option<RawFd>) -> Result<(), Error> {
+    if let Some(seccomp) = seccomp {
+        if let (Some(notify_fd), Some(socket)) = (seccomp.install()?, seccomp_socket) {
+            notify::send_fd(socket, notify_fd, b"seccomp").context("cannot send seccomp fd to the runtime".to_string())?;
+            let _ = unistd::close(notify_fd);
+        }
+    }
     Ok(())
 }
 
@@ -110,3 +465,85 @@ fn try_exec(argv: &Vec<String>) -> Result<(), Error> {
     unistd::execvp(&path, &args).context(format!("{:?}", &argv))?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nix::sys::wait::WaitStatus;
+    use nix::unistd::ForkResult;
+    use crate::libcontainer::linux::seccomp::Action;
+    use crate::libcontainer::linux::seccomp::Rule;
+
+    fn run_in_child<F: FnOnce() -> bool>(check: F) -> WaitStatus {
+        match unistd::fork().unwrap() {
+            ForkResult::Child => {
+                let code = if check() { 0 } else { 1 };
+                unsafe { libc::_exit(code) }
+            },
+            ForkResult::Parent { child } => wait::waitpid(child, None).unwrap(),
+        }
+    }
+
+    #[test]
+    fn apply_user_sets_ids_groups_and_umask() {
+        let status = run_in_child(|| {
+            let mut user = User::new(1000, 2000, Some(vec![3000, 3001]));
+            user.set_umask(0o027);
+            if apply_user(&user).is_err() {
+                return false;
+            }
+
+            let mut groups = [0 as libc::gid_t; 8];
+            let count = unsafe { libc::getgroups(groups.len() as libc::c_int, groups.as_mut_ptr()) };
+            let umask = stat::umask(Mode::empty());
+            unistd::getuid().as_raw() == 1000
+                && unistd::geteuid().as_raw() == 1000
+                && unistd::getgid().as_raw() == 2000
+                && unistd::getegid().as_raw() == 2000
+                && count == 2 && groups[..2] == [3000, 3001]
+                && umask == Mode::from_bits_truncate(0o027)
+        });
+
+        assert_eq!(status, WaitStatus::Exited(status.pid().unwrap(), 0));
+    }
+
+    #[test]
+    fn apply_user_clears_inherited_supplementary_groups() {
+        let status = run_in_child(|| {
+            unistd::setgroups(&[Gid::from_raw(4000)]).unwrap();
+            if apply_user(&User::new(1000, 1000, None)).is_err() {
+                return false;
+            }
+            unsafe { libc::getgroups(0, std::ptr::null_mut()) == 0 }
+        });
+
+        assert_eq!(status, WaitStatus::Exited(status.pid().unwrap(), 0));
+    }
+
+    #[test]
+    fn apply_seccomp_before_user_without_no_new_privileges() {
+        let status = run_in_child(|| {
+            let mut seccomp = Seccomp::new(Action::Allow);
+            seccomp.add_rule(Rule::new(&["getcwd".to_string()], Action::Errno(libc::ENOTTY as u32)));
+            if apply_seccomp(&Some(seccomp), None).is_err() || apply_user(&User::new(1000, 1000, None)).is_err() {
+                return false;
+            }
+            unistd::getuid().as_raw() == 1000
+                && unistd::getcwd().unwrap_err().as_errno() == Some(Errno::ENOTTY)
+        });
+
+        assert_eq!(status, WaitStatus::Exited(status.pid().unwrap(), 0));
+    }
+
+    #[test]
+    fn apply_seccomp_after_user_requires_no_new_privileges() {
+        let status = run_in_child(|| {
+            if apply_user(&User::new(1000, 1000, None)).is_err() {
+                return false;
+            }
+            apply_seccomp(&Some(Seccomp::new(Action::Allow)), None).is_err()
+        });
+
+        assert_eq!(status, WaitStatus::Exited(status.pid().unwrap(), 0));
+    }
+}
@@ -0,0 +1,272 @@
+use crate::Error;
+use failure::ResultExt;
+use nix::sys::signal;
+use nix::sys::signal::Signal;
+use nix::unistd::Pid;
+use serde::Deserialize;
+use serde::Serialize;
+use std::fmt;
+use std::io::Read;
+use std::io::Write;
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Stdio;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+const POLL_INTERVAL_MS: u64 = 10;
+const STDERR_GRACE_MS: u64 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum HookType {
+    Prestart,
+    CreateRuntime,
+    CreateContainer,
+    StartContainer,
+    Poststart,
+    Poststop,
+}
+
+impl fmt::Display for HookType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let hook_type = match *self {
+            HookType::Prestart => "prestart",
+            HookType::CreateRuntime => "createRuntime",
+            HookType::CreateContainer => "createContainer",
+            HookType::StartContainer => "startContainer",
+            HookType::Poststart => "poststart",
+            HookType::Poststop => "poststop",
+        };
+        write!(f, "{}", hook_type)
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Hook {
+    path: PathBuf,
+    args: Vec<String>,
+    env: Vec<(String, String)>,
+    timeout: Option<u64>,
+}
+
+impl Hook {
+    pub fn new(path: &str, args: &[String], env: &[String], timeout: Option<i64>) -> Result<Hook, Error> {
+        let path = PathBuf::from(path);
+        if path.is_relative() {
+            Err(Error::from("hook path must be absolute".to_string())).context(format!("{:?}", path))?;
+        }
+
+        let timeout = match timeout {
+            Some(seconds) if seconds <= 0 => Err(Error::from("hook timeout must be greater than zero".to_string())).context(format!("{:?}", path))?,
+            Some(seconds) => Some(seconds as u64),
+            None => None,
+        };
+
+        let mut hook_env = Vec::new();
+        for env_var in env {
+            let mut splitted_env = env_var.splitn(2, '=');
+            match (splitted_env.next(), splitted_env.next()) {
+                (Some(k), Some(v)) if !k.is_empty() => hook_env.push((k.to_string(), v.to_string())),
+                _ => Err(Error::from("hook environment variable must have 'KEY=VALUE' format".to_string())).context(env_var.to_string())?,
+            }
+        }
+
+        Ok(Hook {
+            path,
+            args: args.to_vec(),
+            env: hook_env,
+            timeout,
+        })
+    }
+
+    pub fn run(&self, state: &str) -> Result<(), Error> {
+        let mut command = Command::new(&self.path);
+        if let Some((arg0, args)) = self.args.split_first() {
+            command.arg0(arg0).args(args);
+        }
+        command.env_clear().envs(self.env.iter().cloned());
+        command.stdin(Stdio::piped()).stdout(Stdio::inherit()).stderr(Stdio::piped());
+        // Its own process group lets a timeout kill whatever the hook started.
+        command.process_group(0);
+
+        let mut child = command.spawn().context(format!("cannot execute hook {:?}", self.path))?;
+        // Both pipes are served from threads, a hook that ignores its stdin
+        // or writes a lot to stderr must not block the runtime past the
+        // hook timeout.
+        if let Some(mut stdin) = child.stdin.take() {
+            let state = state.to_string();
+            // A hook is free to ignore its stdin, so a closed pipe is not an error.
+            thread::spawn(move || stdin.write_all(state.as_bytes()));
+        }
+        let stderr_chunks = child.stderr.take().map(|mut pipe| {
+            let (sender, receiver) = mpsc::channel();
+            thread::spawn(move || {
+                let mut buffer = [0u8; 4096];
+                while let Ok(count) = pipe.read(&mut buffer) {
+                    if count == 0 || sender.send(buffer[..count].to_vec()).is_err() {
+                        break;
+                    }
+                }
+            });
+            receiver
+        });
+
+        let deadline = self.timeout.map(|seconds| Instant::now() + Duration::from_secs(seconds));
+        let status = loop {
+            if let Some(status) = child.try_wait().context(format!("cannot wait for hook {:?}", self.path))? {
+                break status;
+            }
+            if let Some(deadline) = deadline {
+                if Instant::now() >= deadline {
+                    // A negative pid signals the whole process group.
+                    let _ = signal::kill(Pid::from_raw(-(child.id() as i32)), Signal::SIGKILL);
+                    let _ = child.wait();
+                    Err(Error::from(format!("hook timed out after {} seconds", self.timeout.unwrap_or(0)))).context(format!("{:?}", self.path))?;
+                }
+            }
+            thread::sleep(Duration::from_millis(POLL_INTERVAL_MS));
+        };
+
+        if !status.success() {
+            let stderr = stderr_chunks.map(|chunks| collect_stderr(&chunks)).unwrap_or_default();
+            Err(Error::from(format!("hook exited with {}: {}", status, stderr.trim()))).context(format!("{:?}", self.path))?;
+        }
+
+        Ok(())
+    }
+}
+
+// Whatever the hook wrote is in the pipe once it exits, but processes it
+// left behind may keep the pipe open, so the wait for its end is bounded.
+fn collect_stderr(chunks: &Receiver<Vec<u8>>) -> String {
+    let deadline = Instant::now() + Duration::from_millis(STDERR_GRACE_MS);
+    let mut stderr = Vec::new();
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+        match chunks.recv_timeout(remaining) {
+            Ok(chunk) => stderr.extend(chunk),
+            Err(_) => break,
+        }
+    }
+    String::from_utf8_lossy(&stderr).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn sh(script: &str, env: &[String], timeout: Option<i64>) -> Hook {
+        let args = vec!["sh".to_string(), "-c".to_string(), script.to_string()];
+        Hook::new("/bin/sh", &args, env, timeout).unwrap()
+    }
+
+    #[test]
+    fn hook_new_returns_error_with_relative_path() {
+        let result = Hook::new("bin/true", &[], &[], None);
+        assert!(result.is_err(), "expect {:?} to be err", result);
+    }
+
+    #[test]
+    fn hook_new_returns_error_with_non_positive_timeout() {
+        for timeout in &[0, -1] {
+            let result = Hook::new("/bin/true", &[], &[], Some(*timeout));
+            assert!(result.is_err(), "expect {:?} to be err", result);
+        }
+    }
+
+    #[test]
+    fn hook_new_returns_error_with_invalid_env() {
+        let result = Hook::new("/bin/true", &[], &["=value".to_string()], None);
+        assert!(result.is_err(), "expect {:?} to be err", result);
+    }
+
+    #[test]
+    fn hook_run_returns_ok_when_hook_succeeds() {
+        let result = sh("exit 0", &[], None).run("{}");
+        assert!(result.is_ok(), "expect {:?} to be ok", result);
+    }
+
+    #[test]
+    fn hook_run_returns_error_when_hook_fails() {
+        let result = sh("echo failed >&2; exit 3", &[], None).run("{}");
+        assert!(result.is_err(), "expect {:?} to be err", result);
+        assert!(format!("{}", result.unwrap_err()).contains("failed"));
+    }
+
+    #[test]
+    fn hook_run_passes_state_on_stdin_and_env() {
+        let dir = tempdir().unwrap();
+        let output = dir.path().join("output");
+        let script = format!("cat > {:?}; echo \"$KEY\" >> {:?}", output, output);
+        let hook = sh(&script, &["KEY=value".to_string()], None);
+
+        hook.run(r#"{"id":"test"}"#).unwrap();
+
+        assert_eq!(fs::read_to_string(output).unwrap(), "{\"id\":\"test\"}value\n");
+    }
+
+    #[test]
+    fn hook_run_does_not_block_on_large_stderr() {
+        let result = sh("i=0; while [ $i -lt 2000 ]; do echo 0123456789012345678901234567890123456789012345678901234567890123 >&2; i=$((i+1)); done; exit 1", &[], None).run("{}");
+        assert!(result.is_err(), "expect {:?} to be err", result);
+        assert!(format!("{}", result.unwrap_err()).len() > 100_000);
+    }
+
+    #[test]
+    fn hook_run_times_out_when_hook_ignores_large_state() {
+        let started = Instant::now();
+        let state = "x".repeat(1 << 20);
+        let result = sh("sleep 10", &[], Some(1)).run(&state);
+        assert!(result.is_err(), "expect {:?} to be err", result);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn hook_run_kills_hook_after_timeout() {
+        let started = Instant::now();
+        let result = sh("sleep 10", &[], Some(1)).run("{}");
+        assert!(result.is_err(), "expect {:?} to be err", result);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn hook_run_does_not_wait_for_processes_left_behind_by_a_failed_hook() {
+        let dir = tempdir().unwrap();
+        let pid_file = dir.path().join("pid");
+        let script = format!("echo failed >&2; sleep 1000 >/dev/null & echo $! > {:?}; exit 1", pid_file);
+        let started = Instant::now();
+        let result = sh(&script, &[], None).run("{}");
+        let elapsed = started.elapsed();
+
+        let pid = fs::read_to_string(&pid_file).unwrap().trim().parse().unwrap();
+        let _ = signal::kill(Pid::from_raw(pid), Signal::SIGKILL);
+        assert!(result.is_err(), "expect {:?} to be err", result);
+        assert!(format!("{}", result.unwrap_err()).contains("failed"));
+        assert!(elapsed < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn hook_run_kills_background_processes_after_timeout() {
+        let dir = tempdir().unwrap();
+        let pid_file = dir.path().join("pid");
+        let script = format!("sleep 1000 >/dev/null & echo $! > {:?}; sleep 1000", pid_file);
+        let result = sh(&script, &[], Some(1)).run("{}");
+        assert!(result.is_err(), "expect {:?} to be err", result);
+
+        let pid = fs::read_to_string(&pid_file).unwrap().trim().to_string();
+        let stat = format!("/proc/{}/stat", pid);
+        let gone = (0..100).any(|_| {
+            let running = fs::read_to_string(&stat).is_ok_and(|stat| !stat.contains(") Z "));
+            if running {
+                thread::sleep(Duration::from_millis(POLL_INTERVAL_MS));
+            }
+            !running
+        });
+        assert!(gone, "expect background process {} to be killed", pid);
+    }
+}
@@ -0,0 +1,84 @@
+use crate::Error;
+use serde::Deserialize;
+use serde::Serialize;
+use std::io;
+use std::str::FromStr;
+use failure::ResultExt;
+
+const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+const IOPRIO_LEVEL_MAX: i32 = 7;
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum IoClass {
+    IOPRIO_CLASS_RT,
+    IOPRIO_CLASS_BE,
+    IOPRIO_CLASS_IDLE,
+}
+
+impl FromStr for IoClass {
+    type Err = Error;
+
+    fn from_str(class: &str) -> Result<IoClass, Error> {
+        match class {
+            "IOPRIO_CLASS_RT" => Ok(IoClass::IOPRIO_CLASS_RT),
+            "IOPRIO_CLASS_BE" => Ok(IoClass::IOPRIO_CLASS_BE),
+            "IOPRIO_CLASS_IDLE" => Ok(IoClass::IOPRIO_CLASS_IDLE),
+            _ => Err(Error::from("invalid class".to_string())).context(class.to_string())?,
+        }
+    }
+}
+
+impl IoClass {
+    fn to_libc(self) -> libc::c_int {
+        match self {
+            IoClass::IOPRIO_CLASS_RT => 1,
+            IoClass::IOPRIO_CLASS_BE => 2,
+            IoClass::IOPRIO_CLASS_IDLE => 3,
+        }
+    }
+}
+
+// The IO scheduling class of the container process, set with
+// ioprio_set(2). Lower priorities are served first within a class.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IoPriority {
+    class: IoClass,
+    priority: i32,
+}
+
+impl IoPriority {
+    pub fn new(class: IoClass, priority: i32) -> Result<IoPriority, Error> {
+        if !(0..=IOPRIO_LEVEL_MAX).contains(&priority) {
+            Err(Error::from(format!("priority {} must be between 0 and {}", priority, IOPRIO_LEVEL_MAX)))?;
+        }
+        Ok(IoPriority { class, priority })
+    }
+
+    pub fn set(&self) -> Result<(), Error> {
+        if unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, self.to_ioprio()) } != 0 {
+            Err(io::Error::last_os_error()).context(format!("cannot set io priority {:?} {}", self.class, self.priority))?;
+        }
+        Ok(())
+    }
+
+    fn to_ioprio(&self) -> libc::c_int {
+        self.class.to_libc() << IOPRIO_CLASS_SHIFT | self.priority
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn io_priority_checks_priority_and_encodes_class() {
+        assert!(IoPriority::new(IoClass::IOPRIO_CLASS_BE, 8).is_err());
+        assert!(IoPriority::new(IoClass::IOPRIO_CLASS_BE, -1).is_err());
+        assert!(IoClass::from_str("IOPRIO_CLASS_NONE").is_err());
+
+        assert_eq!(IoPriority::new(IoClass::IOPRIO_CLASS_IDLE, 0).unwrap().to_ioprio(), 3 << 13);
+        assert_eq!(IoPriority::new(IoClass::IOPRIO_CLASS_BE, 4).unwrap().to_ioprio(), 2 << 13 | 4);
+    }
+}
@@ -0,0 +1,172 @@
+use crate::Error;
+use crate::libcontainer::linux::cpuset::CpuList;
+use serde::Deserialize;
+use serde::Serialize;
+use std::io;
+use std::str::FromStr;
+use failure::ResultExt;
+
+const MPOL_F_NUMA_BALANCING: libc::c_int = 1 << 13;
+const MPOL_F_RELATIVE_NODES: libc::c_int = 1 << 14;
+const MPOL_F_STATIC_NODES: libc::c_int = 1 << 15;
+
+const WORD_BITS: usize = 64;
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PolicyMode {
+    MPOL_DEFAULT,
+    MPOL_PREFERRED,
+    MPOL_BIND,
+    MPOL_INTERLEAVE,
+    MPOL_LOCAL,
+    MPOL_PREFERRED_MANY,
+    MPOL_WEIGHTED_INTERLEAVE,
+}
+
+impl FromStr for PolicyMode {
+    type Err = Error;
+
+    fn from_str(mode: &str) -> Result<PolicyMode, Error> {
+        match mode {
+            "MPOL_DEFAULT" => Ok(PolicyMode::MPOL_DEFAULT),
+            "MPOL_PREFERRED" => Ok(PolicyMode::MPOL_PREFERRED),
+            "MPOL_BIND" => Ok(PolicyMode::MPOL_BIND),
+            "MPOL_INTERLEAVE" => Ok(PolicyMode::MPOL_INTERLEAVE),
+            "MPOL_LOCAL" => Ok(PolicyMode::MPOL_LOCAL),
+            "MPOL_PREFERRED_MANY" => Ok(PolicyMode::MPOL_PREFERRED_MANY),
+            "MPOL_WEIGHTED_INTERLEAVE" => Ok(PolicyMode::MPOL_WEIGHTED_INTERLEAVE),
+            _ => Err(Error::from("invalid memory policy mode".to_string())).context(mode.to_string())?,
+        }
+    }
+}
+
+impl PolicyMode {
+    fn to_libc(self) -> libc::c_int {
+        match self {
+            PolicyMode::MPOL_DEFAULT => 0,
+            PolicyMode::MPOL_PREFERRED => 1,
+            PolicyMode::MPOL_BIND => 2,
+            PolicyMode::MPOL_INTERLEAVE => 3,
+            PolicyMode::MPOL_LOCAL => 4,
+            PolicyMode::MPOL_PREFERRED_MANY => 5,
+            PolicyMode::MPOL_WEIGHTED_INTERLEAVE => 6,
+        }
+    }
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PolicyFlag {
+    MPOL_F_NUMA_BALANCING,
+    MPOL_F_RELATIVE_NODES,
+    MPOL_F_STATIC_NODES,
+}
+
+impl FromStr for PolicyFlag {
+    type Err = Error;
+
+    fn from_str(flag: &str) -> Result<PolicyFlag, Error> {
+        match flag {
+            "MPOL_F_NUMA_BALANCING" => Ok(PolicyFlag::MPOL_F_NUMA_BALANCING),
+            "MPOL_F_RELATIVE_NODES" => Ok(PolicyFlag::MPOL_F_RELATIVE_NODES),
+            "MPOL_F_STATIC_NODES" => Ok(PolicyFlag::MPOL_F_STATIC_NODES),
+            _ => Err(Error::from("invalid memory policy flag".to_string())).context(flag.to_string())?,
+        }
+    }
+}
+
+impl PolicyFlag {
+    fn to_libc(self) -> libc::c_int {
+        match self {
+            PolicyFlag::MPOL_F_NUMA_BALANCING => MPOL_F_NUMA_BALANCING,
+            PolicyFlag::MPOL_F_RELATIVE_NODES => MPOL_F_RELATIVE_NODES,
+            PolicyFlag::MPOL_F_STATIC_NODES => MPOL_F_STATIC_NODES,
+        }
+    }
+}
+
+// The NUMA memory policy of the container process, inherited by everything
+// it starts.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemoryPolicy {
+    mode: PolicyMode,
+    nodes: CpuList,
+    flags: Vec<PolicyFlag>,
+}
+
+impl MemoryPolicy {
+    // Mirrors the checks of set_mempolicy(2), so a bad policy is reported
+    // when the container is created rather than by the init process.
+    pub fn new(mode: PolicyMode, nodes: CpuList, flags: Vec<PolicyFlag>) -> Result<MemoryPolicy, Error> {
+        match mode {
+            PolicyMode::MPOL_DEFAULT | PolicyMode::MPOL_LOCAL if !nodes.is_empty() => {
+                Err(Error::from(format!("memory policy {:?} does not take nodes", mode)))?;
+            },
+            PolicyMode::MPOL_BIND | PolicyMode::MPOL_INTERLEAVE | PolicyMode::MPOL_PREFERRED_MANY | PolicyMode::MPOL_WEIGHTED_INTERLEAVE if nodes.is_empty() => {
+                Err(Error::from(format!("memory policy {:?} requires nodes", mode)))?;
+            },
+            _ => (),
+        }
+        if mode == PolicyMode::MPOL_DEFAULT && !flags.is_empty() {
+            Err(Error::from("memory policy MPOL_DEFAULT does not take flags".to_string()))?;
+        }
+        if flags.contains(&PolicyFlag::MPOL_F_STATIC_NODES) && flags.contains(&PolicyFlag::MPOL_F_RELATIVE_NODES) {
+            Err(Error::from("memory policy flags MPOL_F_STATIC_NODES and MPOL_F_RELATIVE_NODES are exclusive".to_string()))?;
+        }
+        if flags.contains(&PolicyFlag::MPOL_F_NUMA_BALANCING) && mode != PolicyMode::MPOL_BIND {
+            Err(Error::from("memory policy flag MPOL_F_NUMA_BALANCING requires MPOL_BIND".to_string()))?;
+        }
+
+        Ok(MemoryPolicy { mode, nodes, flags })
+    }
+
+    pub fn set(&self) -> Result<(), Error> {
+        let mode = self.flags.iter().fold(self.mode.to_libc(), |mode, flag| mode | flag.to_libc());
+        let mask = self.node_mask();
+        // The kernel drops the last bit of maxnode.
+        let max_node = mask.len() * WORD_BITS + 1;
+        let mask_ptr = if self.nodes.is_empty() { std::ptr::null() } else { mask.as_ptr() };
+        if unsafe { libc::syscall(libc::SYS_set_mempolicy, mode, mask_ptr, max_node) } != 0 {
+            Err(io::Error::last_os_error()).context(format!("cannot set memory policy {:?} on nodes {:?}", self.mode, self.nodes.to_string()))?;
+        }
+        Ok(())
+    }
+
+    fn node_mask(&self) -> Vec<u64> {
+        let words = self.nodes.max().map_or(1, |max| max / WORD_BITS + 1);
+        let mut mask = vec![0u64; words];
+        for node in self.nodes.iter() {
+            mask[node / WORD_BITS] |= 1 << (node % WORD_BITS);
+        }
+        mask
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nodes(list: &str) -> CpuList {
+        CpuList::from_str(list).unwrap()
+    }
+
+    #[test]
+    fn memory_policy_checks_nodes_and_flags_against_mode() {
+        assert!(MemoryPolicy::new(PolicyMode::MPOL_BIND, nodes("0"), vec![PolicyFlag::MPOL_F_NUMA_BALANCING]).is_ok());
+        assert!(MemoryPolicy::new(PolicyMode::MPOL_LOCAL, nodes(""), vec![]).is_ok());
+        assert!(MemoryPolicy::new(PolicyMode::MPOL_PREFERRED, nodes(""), vec![]).is_ok());
+
+        assert!(MemoryPolicy::new(PolicyMode::MPOL_DEFAULT, nodes("0"), vec![]).is_err());
+        assert!(MemoryPolicy::new(PolicyMode::MPOL_INTERLEAVE, nodes(""), vec![]).is_err());
+        assert!(MemoryPolicy::new(PolicyMode::MPOL_INTERLEAVE, nodes("0-1"), vec![PolicyFlag::MPOL_F_NUMA_BALANCING]).is_err());
+        assert!(MemoryPolicy::new(PolicyMode::MPOL_BIND, nodes("0"), vec![PolicyFlag::MPOL_F_STATIC_NODES, PolicyFlag::MPOL_F_RELATIVE_NODES]).is_err());
+    }
+
+    #[test]
+    fn memory_policy_node_mask_spans_words() {
+        let policy = MemoryPolicy::new(PolicyMode::MPOL_INTERLEAVE, nodes("0,2,64"), vec![]).unwrap();
+
+        assert_eq!(policy.node_mask(), vec![0b101, 1]);
+    }
+}
@@ -1,5 +1,16 @@
+pub mod capabilities;
+pub mod cgroup;
+pub mod cpuset;
+pub mod device;
 pub mod environment;
+pub mod hook;
+pub mod io_priority;
+pub mod mempolicy;
 pub mod mount;
 pub mod namespace;
+pub mod passwd;
 pub mod rlimit;
+pub mod scheduler;
+pub mod seccomp;
+pub mod sysctl;
 pub mod user;
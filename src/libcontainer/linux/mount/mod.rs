@@ -1,10 +1,16 @@
 mod mount_flags;
+pub mod propagation;
+pub mod protect;
 
+use std::path::Path;
 use std::path::PathBuf;
 use crate::Error;
+use crate::filesystem::securejoin::secure_join;
 use serde::Deserialize;
 use serde::Serialize;
+use nix::errno::Errno;
 use nix::mount;
+use nix::mount::MntFlags;
 use nix::mount::MsFlags;
 use failure::ResultExt;
 
@@ -19,16 +25,16 @@ pub struct MountPoint {
 impl MountPoint {
     pub fn create(source: Option<&str>, destination: &str, filesystem_type: Option<&str>, options: Option<Vec<String>>) -> MountPoint {
         MountPoint {
-            source: source.map(|s| PathBuf::from(s)),
+            source: source.map(PathBuf::from),
             destination: PathBuf::from(destination),
-            filesystem_type: filesystem_type.map(|s| PathBuf::from(s)),
+            filesystem_type: filesystem_type.map(PathBuf::from),
             options: options,
         }
     }
 
-    pub fn mount(&self) -> Result<(), Error> {
+    pub fn mount(&self, root: &Path) -> Result<(), Error> {
         let source = self.source.clone();
-        let destination = self.destination.clone();
+        let destination = secure_join(root, &self.destination)?;
         let filesystem_type = self.filesystem_type.clone();
         let flags = mount_flags::extract_flags(self.options.clone())?;
         let data = mount_flags::extract_data(self.options.clone())?;
@@ -48,6 +54,14 @@ impl MountPoint {
         )?;
         Ok(())
     }
+
+    pub fn unmount(&self, root: &Path) -> Result<(), Error> {
+        let target = secure_join(root, &self.destination)?;
+        match mount::umount2(&target, MntFlags::MNT_DETACH) {
+            Err(err) if err.as_errno() == Some(Errno::EINVAL) || err.as_errno() == Some(Errno::ENOENT) => Ok(()),
+            result => Ok(result.context(format!("cannot unmount {:?}", target))?),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -64,22 +78,47 @@ mod tests {
 
     #[test]
     fn mount_point_mount_returns_ok() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::create_dir(root.path().join("tmp")).unwrap();
         let mount_point = MountPoint::create(Some("/tmp"), "/tmp", Some("tmpfs"), None);
-        let mount_result = mount_point.mount();
+        let mount_result = mount_point.mount(root.path());
         assert!(mount_result.is_ok(), "expect {:?} to be ok", &mount_result);
+        mount_point.unmount(root.path()).unwrap();
     }
 
     #[test]
-    fn mount_point_mount_returns_error_if_src_does_not_exist() {
+    fn mount_point_mount_returns_error_if_destination_does_not_exist() {
+        let root = tempfile::tempdir().unwrap();
         let mount_point = MountPoint::create(Some("/tmp"), "/invalid", Some("tmpfs"), None);
-        let mount_result = mount_point.mount();
+        let mount_result = mount_point.mount(root.path());
+        assert!(mount_result.is_err(), "expect {:?} to be err", &mount_result);
+    }
+
+    #[test]
+    fn mount_point_mount_does_not_follow_symlinks_out_of_root() {
+        let root = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        std::os::unix::fs::symlink(outside.path(), root.path().join("escape")).unwrap();
+        let mount_point = MountPoint::create(Some("tmpfs"), "/escape", Some("tmpfs"), None);
+        let mount_result = mount_point.mount(root.path());
         assert!(mount_result.is_err(), "expect {:?} to be err", &mount_result);
     }
 
+    #[test]
+    fn mount_point_unmount_ignores_targets_that_are_not_mounted() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::create_dir(root.path().join("proc")).unwrap();
+        let mount_point = MountPoint::create(Some("proc"), "/proc", Some("proc"), None);
+        let result = mount_point.unmount(root.path());
+        assert!(result.is_ok(), "expect {:?} to be ok", &result);
+    }
+
     #[test]
     fn mount_point_mount_returns_error_if_fs_type_is_invalid() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::create_dir(root.path().join("tmp")).unwrap();
         let mount_point = MountPoint::create(Some("/tmp"), "/tmp", Some("invalid"), None);
-        let mount_result = mount_point.mount();
+        let mount_result = mount_point.mount(root.path());
         assert!(mount_result.is_err(), "expect {:?} to be err", &mount_result);
     }
 }
@@ -0,0 +1,108 @@
+use crate::Error;
+use serde::Deserialize;
+use serde::Serialize;
+use nix::mount;
+use nix::mount::MsFlags;
+use std::path::Path;
+use std::str::FromStr;
+use failure::ResultExt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Propagation {
+    Shared,
+    Slave,
+    Private,
+    Unbindable,
+}
+
+impl FromStr for Propagation {
+    type Err = Error;
+
+    fn from_str(original: &str) -> Result<Propagation, Error> {
+        match original {
+            "shared" => Ok(Propagation::Shared),
+            "slave" => Ok(Propagation::Slave),
+            "private" => Ok(Propagation::Private),
+            "unbindable" => Ok(Propagation::Unbindable),
+            _ => Err(Error::from("invalid rootfs propagation".to_string())).context(original.to_string())?,
+        }
+    }
+}
+
+impl Propagation {
+    fn to_flags(self) -> MsFlags {
+        match self {
+            Propagation::Shared => MsFlags::MS_SHARED,
+            Propagation::Slave => MsFlags::MS_SLAVE,
+            Propagation::Private => MsFlags::MS_PRIVATE,
+            Propagation::Unbindable => MsFlags::MS_UNBINDABLE,
+        }
+    }
+}
+
+// Detaches the whole mount tree of a new mount namespace from its peers, so
+// nothing mounted while setting up the container reaches the host.
+pub fn make_root_private() -> Result<(), Error> {
+    mount::mount(None::<&str>, "/", None::<&str>, MsFlags::MS_PRIVATE | MsFlags::MS_REC, None::<&str>)
+        .context("cannot make root mount private".to_string())?;
+    Ok(())
+}
+
+// Turns the rootfs into a mount point of its own, so its propagation can be
+// changed independently of the filesystem it lives in.
+pub fn bind_rootfs(rootfs: &Path) -> Result<(), Error> {
+    mount::mount(Some(rootfs), rootfs, None::<&str>, MsFlags::MS_BIND | MsFlags::MS_REC, None::<&str>)
+        .context(format!("cannot bind mount rootfs {:?}", rootfs))?;
+    Ok(())
+}
+
+pub fn set(rootfs: &Path, propagation: Propagation) -> Result<(), Error> {
+    mount::mount(None::<&str>, rootfs, None::<&str>, propagation.to_flags() | MsFlags::MS_REC, None::<&str>)
+        .context(format!("cannot set rootfs propagation to {:?}", propagation))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nix::mount::MntFlags;
+    use std::fs;
+
+    fn optional_fields(mount_point: &Path) -> Option<String> {
+        let mountinfo = fs::read_to_string("/proc/self/mountinfo").unwrap();
+        mountinfo.lines()
+            .map(|line| line.split(' ').collect::<Vec<&str>>())
+            .filter(|fields| Path::new(fields[4]) == mount_point)
+            .map(|fields| fields[6..].iter().take_while(|field| **field != "-").cloned().collect::<Vec<&str>>().join(" "))
+            .next_back()
+    }
+
+    #[test]
+    fn propagation_from_str() {
+        let table = vec![
+            ("shared", Propagation::Shared),
+            ("slave", Propagation::Slave),
+            ("private", Propagation::Private),
+            ("unbindable", Propagation::Unbindable),
+        ];
+        for (original, expect) in table {
+            assert_eq!(Propagation::from_str(original).unwrap(), expect);
+        }
+        assert!(Propagation::from_str("rshared").is_err());
+    }
+
+    #[test]
+    fn bind_rootfs_and_set_propagation() {
+        let rootfs = tempfile::Builder::new().tempdir_in("/run").unwrap();
+
+        bind_rootfs(rootfs.path()).unwrap();
+        set(rootfs.path(), Propagation::Shared).unwrap();
+        let shared = optional_fields(rootfs.path());
+        set(rootfs.path(), Propagation::Unbindable).unwrap();
+        let unbindable = optional_fields(rootfs.path());
+        mount::umount2(rootfs.path(), MntFlags::MNT_DETACH).unwrap();
+
+        assert!(shared.unwrap().starts_with("shared:"));
+        assert_eq!(unbindable.unwrap(), "unbindable");
+    }
+}
@@ -0,0 +1,184 @@
+use crate::Error;
+use nix::mount;
+use nix::mount::MsFlags;
+use nix::sys::statvfs;
+use nix::sys::statvfs::FsFlags;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use failure::ResultExt;
+
+const DEV_NULL: &str = "/dev/null";
+const MOUNTINFO: &str = "/proc/self/mountinfo";
+
+// Hides a path from the container: files are covered by /dev/null and
+// directories by an empty read-only tmpfs. Missing paths are ignored.
+pub fn mask(path: &Path) -> Result<(), Error> {
+    let metadata = match fs::metadata(path) {
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        result => result.context(format!("cannot mask {:?}", path))?,
+    };
+
+    if metadata.is_dir() {
+        mount::mount(Some("tmpfs"), path, Some("tmpfs"), MsFlags::MS_RDONLY, None::<&str>)
+            .context(format!("cannot mask directory {:?}", path))?;
+    } else {
+        mount::mount(Some(DEV_NULL), path, None::<&str>, MsFlags::MS_BIND, None::<&str>)
+            .context(format!("cannot mask file {:?}", path))?;
+    }
+    Ok(())
+}
+
+// Bind mounts a path onto itself and remounts it, along with every mount
+// below it, read-only. Missing paths are ignored.
+pub fn make_readonly(path: &Path) -> Result<(), Error> {
+    if let Err(err) = fs::metadata(path) {
+        if err.kind() == io::ErrorKind::NotFound {
+            return Ok(());
+        }
+        Err(err).context(format!("cannot make {:?} read-only", path))?;
+    }
+
+    mount::mount(Some(path), path, None::<&str>, MsFlags::MS_BIND | MsFlags::MS_REC, None::<&str>)
+        .context(format!("cannot bind mount {:?}", path))?;
+
+    let mut targets = vec![path.to_path_buf()];
+    targets.extend(submounts(path));
+    for target in targets {
+        remount_readonly(&target)?;
+    }
+    Ok(())
+}
+
+fn remount_readonly(path: &Path) -> Result<(), Error> {
+    // Flags like nosuid or nodev may be locked when running in a user
+    // namespace, so they have to be kept on the remount.
+    let current = statvfs::statvfs(path).context(format!("cannot stat filesystem at {:?}", path))?.flags();
+    let mut flags = MsFlags::MS_BIND | MsFlags::MS_REMOUNT | MsFlags::MS_RDONLY;
+    let preserved = [
+        (FsFlags::ST_NOSUID, MsFlags::MS_NOSUID),
+        (FsFlags::ST_NODEV, MsFlags::MS_NODEV),
+        (FsFlags::ST_NOEXEC, MsFlags::MS_NOEXEC),
+        (FsFlags::ST_NOATIME, MsFlags::MS_NOATIME),
+        (FsFlags::ST_NODIRATIME, MsFlags::MS_NODIRATIME),
+        (FsFlags::ST_RELATIME, MsFlags::MS_RELATIME),
+    ];
+    for (fs_flag, ms_flag) in preserved.iter() {
+        if current.contains(*fs_flag) {
+            flags.insert(*ms_flag);
+        }
+    }
+
+    mount::mount(Some(path), path, None::<&str>, flags, None::<&str>)
+        .context(format!("cannot remount {:?} read-only", path))?;
+    Ok(())
+}
+
+// Mount points strictly below the given path. Without a /proc mounted in
+// the container there is nothing to read, so only the top mount is handled.
+fn submounts(path: &Path) -> Vec<PathBuf> {
+    let mountinfo = fs::read_to_string(MOUNTINFO).unwrap_or_default();
+    let mut mounts: Vec<PathBuf> = mountinfo.lines()
+        .filter_map(|line| line.split(' ').nth(4))
+        .map(|mount_point| PathBuf::from(unescape(mount_point)))
+        .filter(|mount_point| mount_point != path && mount_point.starts_with(path))
+        .collect();
+    mounts.sort();
+    mounts.dedup();
+    mounts
+}
+
+// mountinfo escapes space, tab, newline and backslash as octal sequences.
+fn unescape(field: &str) -> String {
+    let mut result = String::new();
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            let octal: String = chars.clone().take(3).collect();
+            if let Ok(byte) = u8::from_str_radix(&octal, 8) {
+                result.push(byte as char);
+                chars.nth(2);
+                continue;
+            }
+        }
+        result.push(c);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nix::mount::MntFlags;
+    use tempfile::TempDir;
+
+    // Another test mounts a tmpfs over /tmp, so these use a different base.
+    fn setup_dir() -> TempDir {
+        tempfile::Builder::new().tempdir_in("/run").unwrap()
+    }
+
+    fn unmount_all(path: &Path) {
+        while mount::umount2(path, MntFlags::MNT_DETACH).is_ok() {}
+    }
+
+    #[test]
+    fn mask_ignores_missing_paths() {
+        let dir = setup_dir();
+        let result = mask(&dir.path().join("missing"));
+        assert!(result.is_ok(), "expect {:?} to be ok", result);
+    }
+
+    #[test]
+    fn mask_hides_file_contents() {
+        let dir = setup_dir();
+        let file = dir.path().join("secret");
+        fs::write(&file, "secret").unwrap();
+
+        mask(&file).unwrap();
+        let contents = fs::read_to_string(&file).unwrap();
+        unmount_all(&file);
+
+        assert_eq!(contents, "");
+    }
+
+    #[test]
+    fn mask_hides_directory_with_readonly_tmpfs() {
+        let dir = setup_dir();
+        let masked = dir.path().join("masked");
+        fs::create_dir(&masked).unwrap();
+        fs::write(masked.join("secret"), "secret").unwrap();
+
+        mask(&masked).unwrap();
+        let entries = fs::read_dir(&masked).unwrap().count();
+        let write_result = fs::write(masked.join("new"), "new");
+        unmount_all(&masked);
+
+        assert_eq!(entries, 0);
+        assert!(write_result.is_err(), "expect {:?} to be err", write_result);
+    }
+
+    #[test]
+    fn make_readonly_remounts_path_and_submounts() {
+        let dir = setup_dir();
+        let readonly = dir.path().join("readonly");
+        let submount = readonly.join("sub");
+        fs::create_dir_all(&submount).unwrap();
+        mount::mount(Some("tmpfs"), &submount, Some("tmpfs"), MsFlags::empty(), None::<&str>).unwrap();
+
+        make_readonly(&readonly).unwrap();
+        let top_write = fs::write(readonly.join("file"), "data");
+        let sub_write = fs::write(submount.join("file"), "data");
+        unmount_all(&submount);
+        unmount_all(&readonly);
+
+        assert!(top_write.is_err(), "expect {:?} to be err", top_write);
+        assert!(sub_write.is_err(), "expect {:?} to be err", sub_write);
+    }
+
+    #[test]
+    fn unescape_decodes_octal_sequences() {
+        assert_eq!(unescape("/with\\040space"), "/with space");
+        assert_eq!(unescape("/plain"), "/plain");
+    }
+}
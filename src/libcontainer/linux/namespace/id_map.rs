@@ -0,0 +1,89 @@
+use crate::Error;
+use serde::Deserialize;
+use serde::Serialize;
+use nix::unistd::Uid;
+use std::fs::OpenOptions;
+use std::io::Write;
+use failure::ResultExt;
+
+// Linux accepts at most 340 lines in uid_map and gid_map (5 before 4.15).
+const MAX_ID_MAPS: usize = 340;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct IdMap {
+    container_id: u32,
+    host_id: u32,
+    size: u32,
+}
+
+impl IdMap {
+    pub fn new(container_id: u32, host_id: u32, size: u32) -> Result<IdMap, Error> {
+        let description = format!("{} {} {}", container_id, host_id, size);
+        if size == 0 {
+            Err(Error::from("id mapping size must be greater than zero".to_string())).context(description.clone())?;
+        }
+        if container_id.checked_add(size - 1).is_none() || host_id.checked_add(size - 1).is_none() {
+            Err(Error::from("id mapping range overflows".to_string())).context(description)?;
+        }
+        Ok(IdMap { container_id, host_id, size })
+    }
+}
+
+pub fn write_uid_maps(pid: i32, uid_maps: &[IdMap]) -> Result<(), Error> {
+    write_proc_file(pid, "uid_map", &format_id_maps(uid_maps)?)
+}
+
+pub fn write_gid_maps(pid: i32, gid_maps: &[IdMap]) -> Result<(), Error> {
+    // Without CAP_SETGID in the parent namespace the kernel only accepts a
+    // gid_map once setgroups(2) has been disabled for the child.
+    if !Uid::effective().is_root() {
+        write_proc_file(pid, "setgroups", "deny")?;
+    }
+    write_proc_file(pid, "gid_map", &format_id_maps(gid_maps)?)
+}
+
+fn format_id_maps(id_maps: &[IdMap]) -> Result<String, Error> {
+    if id_maps.len() > MAX_ID_MAPS {
+        Err(Error::from(format!("at most {} id mappings are allowed", MAX_ID_MAPS)))?;
+    }
+    let lines: Vec<String> = id_maps.iter().map(|map| format!("{} {} {}\n", map.container_id, map.host_id, map.size)).collect();
+    Ok(lines.concat())
+}
+
+fn write_proc_file(pid: i32, name: &str, contents: &str) -> Result<(), Error> {
+    // The kernel requires the whole map to be written with a single write(2).
+    let path = format!("/proc/{}/{}", pid, name);
+    let mut file = OpenOptions::new().write(true).open(&path).context(format!("cannot open {}", path))?;
+    file.write_all(contents.as_bytes()).context(format!("cannot write {}", path))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn id_map_new_returns_error_with_zero_size() {
+        let result = IdMap::new(0, 1000, 0);
+        assert!(result.is_err(), "expect {:?} to be err", result);
+    }
+
+    #[test]
+    fn id_map_new_returns_error_when_range_overflows() {
+        let result = IdMap::new(0, u32::MAX - 10, 100);
+        assert!(result.is_err(), "expect {:?} to be err", result);
+    }
+
+    #[test]
+    fn format_id_maps_writes_one_line_per_mapping() {
+        let id_maps = vec![IdMap::new(0, 1000, 1).unwrap(), IdMap::new(1, 100000, 65536).unwrap()];
+        assert_eq!(format_id_maps(&id_maps).unwrap(), "0 1000 1\n1 100000 65536\n");
+    }
+
+    #[test]
+    fn format_id_maps_returns_error_with_too_many_mappings() {
+        let id_maps: Vec<IdMap> = (0..=MAX_ID_MAPS as u32).map(|id| IdMap::new(id, id, 1).unwrap()).collect();
+        let result = format_id_maps(&id_maps);
+        assert!(result.is_err(), "expect {:?} to be err", result);
+    }
+}
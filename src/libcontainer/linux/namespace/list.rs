@@ -5,6 +5,7 @@ use super::Namespace;
 use super::NamespaceType;
 use serde::Deserialize;
 use serde::Serialize;
+use nix::sched::CloneFlags;
 use failure::ResultExt;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -35,6 +36,34 @@ impl NamespaceList {
     pub fn contains_type(&self, ns_type: &NamespaceType) -> bool {
         self.list.iter().any(|ns| ns.r#type == *ns_type)
     }
+
+    pub fn creates_type(&self, ns_type: &NamespaceType) -> bool {
+        self.list.iter().any(|ns| ns.r#type == *ns_type && ns.is_new())
+    }
+
+    pub fn is_private(&self, ns_type: &NamespaceType) -> bool {
+        self.list.iter().any(|ns| ns.r#type == *ns_type && !ns.is_host())
+    }
+
+    pub fn clone_flags(&self) -> CloneFlags {
+        let mut flags = CloneFlags::empty();
+        for namespace in self.list.iter().filter(|ns| ns.is_new()) {
+            flags.insert(namespace.clone_flags());
+        }
+        flags
+    }
+
+    // Joining a pid namespace only moves the children of the caller, so it
+    // is left to the runtime, which joins it around the clone of the init.
+    pub fn to_enter(&self) -> Vec<&Namespace> {
+        let mut namespaces: Vec<&Namespace> = self.list.iter().filter(|ns| !ns.is_new() && ns.r#type != NamespaceType::PID).collect();
+        namespaces.sort_by_key(|ns| ns.r#type != NamespaceType::USER);
+        namespaces
+    }
+
+    pub fn to_enter_before_clone(&self) -> Option<&Namespace> {
+        self.list.iter().find(|ns| !ns.is_new() && ns.r#type == NamespaceType::PID)
+    }
 }
 
 #[cfg(test)]
@@ -80,13 +109,65 @@ mod tests {
     #[test]
     fn namespace_list_contains_returns_false_if_ns_type_is_not_present() {
         let namespaces = NamespaceList::empty();
-        assert_eq!(namespaces.contains_type(&NamespaceType::UTS), false);
+        assert!(!namespaces.contains_type(&NamespaceType::UTS));
+    }
+
+    #[test]
+    fn namespace_list_creates_type_ignores_namespaces_with_path() {
+        let mut namespaces = NamespaceList::empty();
+        namespaces.insert(Namespace::new(NamespaceType::PID, None)).unwrap();
+        namespaces.insert(Namespace::new(NamespaceType::USER, Some("/proc/1/ns/user".to_string()))).unwrap();
+        assert!(namespaces.creates_type(&NamespaceType::PID));
+        assert!(!namespaces.creates_type(&NamespaceType::USER));
+    }
+
+    #[test]
+    fn namespace_list_is_private_rejects_runtime_namespaces() {
+        let mut namespaces = NamespaceList::empty();
+        namespaces.insert(Namespace::new(NamespaceType::IPC, None)).unwrap();
+        namespaces.insert(Namespace::new(NamespaceType::NETWORK, Some("/proc/self/ns/net".to_string()))).unwrap();
+        assert!(namespaces.is_private(&NamespaceType::IPC));
+        assert!(!namespaces.is_private(&NamespaceType::NETWORK));
+        assert!(!namespaces.is_private(&NamespaceType::UTS));
+    }
+
+    #[test]
+    fn namespace_list_clone_flags_include_only_new_namespaces() {
+        let mut namespaces = NamespaceList::empty();
+        namespaces.insert(Namespace::new(NamespaceType::PID, None)).unwrap();
+        namespaces.insert(Namespace::new(NamespaceType::MOUNT, None)).unwrap();
+        namespaces.insert(Namespace::new(NamespaceType::NETWORK, Some("/var/run/netns/test".to_string()))).unwrap();
+        assert_eq!(namespaces.clone_flags(), CloneFlags::CLONE_NEWPID | CloneFlags::CLONE_NEWNS);
+    }
+
+    #[test]
+    fn namespace_list_to_enter_returns_user_namespace_first() {
+        let mut namespaces = NamespaceList::empty();
+        namespaces.insert(Namespace::new(NamespaceType::NETWORK, Some("/proc/1/ns/net".to_string()))).unwrap();
+        namespaces.insert(Namespace::new(NamespaceType::PID, None)).unwrap();
+        namespaces.insert(Namespace::new(NamespaceType::USER, Some("/proc/1/ns/user".to_string()))).unwrap();
+        let to_enter = namespaces.to_enter();
+        assert_eq!(to_enter.len(), 2);
+        assert_eq!(to_enter[0].r#type, NamespaceType::USER);
+        assert_eq!(to_enter[1].r#type, NamespaceType::NETWORK);
+    }
+
+    #[test]
+    fn namespace_list_leaves_pid_namespace_with_path_to_enter_before_clone() {
+        let mut namespaces = NamespaceList::empty();
+        namespaces.insert(Namespace::new(NamespaceType::NETWORK, Some("/proc/1/ns/net".to_string()))).unwrap();
+        namespaces.insert(Namespace::new(NamespaceType::PID, Some("/proc/1/ns/pid".to_string()))).unwrap();
+        let to_enter = namespaces.to_enter();
+        assert_eq!(to_enter.len(), 1);
+        assert_eq!(to_enter[0].r#type, NamespaceType::NETWORK);
+        assert_eq!(namespaces.to_enter_before_clone().map(|ns| &ns.r#type), Some(&NamespaceType::PID));
+        assert!(NamespaceList::empty().to_enter_before_clone().is_none());
     }
 
     #[test]
     fn namespace_list_contains_returns_true_if_ns_type_is_present() {
         let mut namespaces = NamespaceList::empty();
         namespaces.insert(Namespace::new(NamespaceType::UTS, None)).unwrap();
-        assert_eq!(namespaces.contains_type(&NamespaceType::UTS), true);
+        assert!(namespaces.contains_type(&NamespaceType::UTS));
     }
 }
@@ -1,16 +1,24 @@
 pub mod error;
 pub mod r#type;
 pub mod list;
+pub mod id_map;
 
 use crate::Error;
 use serde::Deserialize;
 use serde::Serialize;
+use nix::fcntl;
+use nix::fcntl::OFlag;
 use nix::sched;
 use nix::sched::CloneFlags;
+use nix::sys::stat;
+use nix::sys::stat::Mode;
+use nix::unistd;
+use failure::ResultExt;
 
 pub use self::error::ErrorKind;
 pub use self::r#type::NamespaceType;
 pub use self::list::NamespaceList;
+pub use self::id_map::IdMap;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Namespace {
@@ -26,14 +34,35 @@ impl Namespace {
         }
     }
 
+    pub fn is_new(&self) -> bool {
+        self.path.is_none()
+    }
+
+    // Tells whether the namespace path points to the namespace the runtime
+    // itself is running in, i.e. joining it gives the container no isolation.
+    pub fn is_host(&self) -> bool {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return false,
+        };
+        let host_path = format!("/proc/self/ns/{}", self.r#type.proc_name());
+        match (stat::stat(path.as_str()), stat::stat(host_path.as_str())) {
+            (Ok(ns), Ok(host_ns)) => ns.st_dev == host_ns.st_dev && ns.st_ino == host_ns.st_ino,
+            _ => false,
+        }
+    }
+
     pub fn enter(&self) -> Result<(), Error> {
-        if let None = self.path {
-            sched::unshare(self.unshare_flags())?;
+        if let Some(path) = &self.path {
+            let fd = fcntl::open(path.as_str(), OFlag::O_RDONLY | OFlag::O_CLOEXEC, Mode::empty()).context(path.to_string())?;
+            let result = sched::setns(fd, self.clone_flags());
+            unistd::close(fd)?;
+            result.context(format!("cannot join {} namespace {}", self.r#type.to_string(), path))?;
         }
         Ok(())
     }
 
-    fn unshare_flags(&self) -> CloneFlags {
+    pub fn clone_flags(&self) -> CloneFlags {
         let mut flags = CloneFlags::empty();
         match self.r#type {
             NamespaceType::PID => flags.insert(CloneFlags::CLONE_NEWPID),
@@ -64,4 +93,31 @@ mod tests {
         let namespace = Namespace::new(NamespaceType::UTS, Some(String::from("/proc/1234/ns/uts")));
         assert!(namespace.path.is_some());
     }
+
+    #[test]
+    fn namespace_is_new_only_without_path() {
+        assert!(Namespace::new(NamespaceType::UTS, None).is_new());
+        assert!(!Namespace::new(NamespaceType::UTS, Some(String::from("/proc/1234/ns/uts"))).is_new());
+    }
+
+    #[test]
+    fn namespace_is_host_when_path_points_to_runtime_namespace() {
+        assert!(Namespace::new(NamespaceType::NETWORK, Some(String::from("/proc/self/ns/net"))).is_host());
+        assert!(!Namespace::new(NamespaceType::NETWORK, None).is_host());
+    }
+
+    #[test]
+    fn namespace_enter_without_path_does_not_change_namespaces() {
+        let before = std::fs::read_link("/proc/self/ns/uts").unwrap();
+        let result = Namespace::new(NamespaceType::UTS, None).enter();
+        assert!(result.is_ok(), "expect {:?} to be ok", result);
+        assert_eq!(std::fs::read_link("/proc/self/ns/uts").unwrap(), before);
+    }
+
+    #[test]
+    fn namespace_enter_returns_error_if_path_does_not_exist() {
+        let namespace = Namespace::new(NamespaceType::UTS, Some(String::from("/invalid/ns/uts")));
+        let result = namespace.enter();
+        assert!(result.is_err(), "expect {:?} to be err", result);
+    }
 }
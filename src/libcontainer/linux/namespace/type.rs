@@ -41,6 +41,18 @@ impl NamespaceType {
         };
         str_type.to_string()
     }
+
+    pub fn proc_name(&self) -> &'static str {
+        match *self {
+            NamespaceType::PID => "pid",
+            NamespaceType::UTS => "uts",
+            NamespaceType::IPC => "ipc",
+            NamespaceType::USER => "user",
+            NamespaceType::MOUNT => "mnt",
+            NamespaceType::CGROUP => "cgroup",
+            NamespaceType::NETWORK => "net",
+        }
+    }
 }
 
 #[cfg(test)]
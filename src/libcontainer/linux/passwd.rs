@@ -0,0 +1,192 @@
+use crate::Error;
+use crate::filesystem::securejoin::secure_join;
+use std::fs;
+use std::io;
+use std::path::Path;
+use failure::ResultExt;
+
+const PASSWD_FILE: &str = "/etc/passwd";
+const GROUP_FILE: &str = "/etc/group";
+const DEFAULT_HOME: &str = "/";
+
+#[derive(Debug, PartialEq)]
+pub struct PasswdEntry {
+    name: String,
+    uid: u32,
+    gid: u32,
+    home: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct GroupEntry {
+    name: String,
+    gid: u32,
+    members: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ResolvedUser {
+    pub uid: u32,
+    pub gid: u32,
+    pub additional_gids: Vec<u32>,
+    pub home: String,
+}
+
+// Resolves "user", "uid", "user:group" or "uid:gid" the way the container
+// would, reading the passwd and group files from its rootfs.
+pub fn resolve(rootfs: &Path, username: &str) -> Result<ResolvedUser, Error> {
+    let mut parts = username.splitn(2, ':');
+    let user_part = parts.next().unwrap_or("");
+    let group_part = parts.next();
+    if user_part.is_empty() || group_part == Some("") {
+        Err(Error::from("username must have 'user' or 'user:group' format".to_string())).context(username.to_string())?;
+    }
+
+    let passwd = parse_passwd(&read_rootfs_file(rootfs, PASSWD_FILE)?);
+    let entry = match user_part.parse::<u32>() {
+        Ok(uid) => passwd.into_iter().find(|entry| entry.uid == uid).map(Ok).unwrap_or_else(|| Ok(PasswdEntry {
+            name: String::new(),
+            uid,
+            gid: 0,
+            home: DEFAULT_HOME.to_string(),
+        })),
+        Err(_) => match passwd.into_iter().find(|entry| entry.name == user_part) {
+            Some(entry) => Ok(entry),
+            None => Err(Error::from("user not found in container /etc/passwd".to_string())).context(user_part.to_string()),
+        },
+    }?;
+
+    let groups = parse_group(&read_rootfs_file(rootfs, GROUP_FILE)?);
+    let gid = match group_part {
+        None => entry.gid,
+        Some(group_part) => match group_part.parse::<u32>() {
+            Ok(gid) => gid,
+            Err(_) => match groups.iter().find(|group| group.name == group_part) {
+                Some(group) => group.gid,
+                None => Err(Error::from("group not found in container /etc/group".to_string())).context(group_part.to_string())?,
+            },
+        },
+    };
+
+    let mut additional_gids: Vec<u32> = Vec::new();
+    if !entry.name.is_empty() {
+        for group in groups.iter().filter(|group| group.members.contains(&entry.name)) {
+            if group.gid != gid && !additional_gids.contains(&group.gid) {
+                additional_gids.push(group.gid);
+            }
+        }
+    }
+
+    Ok(ResolvedUser {
+        uid: entry.uid,
+        gid,
+        additional_gids,
+        home: if entry.home.is_empty() { DEFAULT_HOME.to_string() } else { entry.home },
+    })
+}
+
+// A rootfs without the file behaves like an empty one; symlinks are
+// resolved inside the rootfs so they cannot point at host files.
+fn read_rootfs_file(rootfs: &Path, path: &str) -> Result<String, Error> {
+    let full_path = secure_join(rootfs, Path::new(path))?;
+    match fs::read_to_string(&full_path) {
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+        result => Ok(result.context(format!("cannot read container {}", path))?),
+    }
+}
+
+fn parse_passwd(contents: &str) -> Vec<PasswdEntry> {
+    contents.lines().filter_map(|line| {
+        let fields: Vec<&str> = line.split(':').collect();
+        if line.starts_with('#') || fields.len() < 7 {
+            return None;
+        }
+        Some(PasswdEntry {
+            name: fields[0].to_string(),
+            uid: fields[2].parse().ok()?,
+            gid: fields[3].parse().ok()?,
+            home: fields[5].to_string(),
+        })
+    }).collect()
+}
+
+fn parse_group(contents: &str) -> Vec<GroupEntry> {
+    contents.lines().filter_map(|line| {
+        let fields: Vec<&str> = line.split(':').collect();
+        if line.starts_with('#') || fields.len() < 4 {
+            return None;
+        }
+        Some(GroupEntry {
+            name: fields[0].to_string(),
+            gid: fields[2].parse().ok()?,
+            members: fields[3].split(',').filter(|member| !member.is_empty()).map(String::from).collect(),
+        })
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+    use tempfile::tempdir;
+    use tempfile::TempDir;
+
+    fn setup_rootfs() -> TempDir {
+        let rootfs = tempdir().unwrap();
+        fs::create_dir(rootfs.path().join("etc")).unwrap();
+        fs::write(rootfs.path().join("etc/passwd"), "\
+root:x:0:0:root:/root:/bin/sh
+nginx:x:101:101:nginx:/var/cache/nginx:/sbin/nologin
+# comment
+broken:x:abc:1::/:/bin/sh
+").unwrap();
+        fs::write(rootfs.path().join("etc/group"), "\
+root:x:0:
+nginx:x:101:
+www-data:x:33:nginx
+adm:x:4:root,nginx
+").unwrap();
+        rootfs
+    }
+
+    #[test]
+    fn resolve_user_name_uses_passwd_entry_and_group_membership() {
+        let rootfs = setup_rootfs();
+        let user = resolve(rootfs.path(), "nginx").unwrap();
+        assert_eq!(user, ResolvedUser { uid: 101, gid: 101, additional_gids: vec![33, 4], home: "/var/cache/nginx".to_string() });
+    }
+
+    #[test]
+    fn resolve_user_and_group_names() {
+        let rootfs = setup_rootfs();
+        let user = resolve(rootfs.path(), "nginx:www-data").unwrap();
+        assert_eq!((user.uid, user.gid, user.additional_gids), (101, 33, vec![4]));
+    }
+
+    #[test]
+    fn resolve_numeric_ids_not_in_passwd() {
+        let rootfs = setup_rootfs();
+        let user = resolve(rootfs.path(), "1000:www-data").unwrap();
+        assert_eq!(user, ResolvedUser { uid: 1000, gid: 33, additional_gids: vec![], home: "/".to_string() });
+    }
+
+    #[test]
+    fn resolve_returns_error_for_unknown_names() {
+        let rootfs = setup_rootfs();
+        for username in &["apache", "nginx:apache", "nginx:", ":nginx"] {
+            let result = resolve(rootfs.path(), username);
+            assert!(result.is_err(), "expect {:?} to be err for {}", result, username);
+        }
+    }
+
+    #[test]
+    fn resolve_does_not_follow_symlinks_out_of_rootfs() {
+        let rootfs = tempdir().unwrap();
+        let host = setup_rootfs();
+        symlink(host.path().join("etc"), rootfs.path().join("etc")).unwrap();
+
+        let result = resolve(rootfs.path(), "nginx");
+
+        assert!(result.is_err(), "expect {:?} to be err", result);
+    }
+}
@@ -0,0 +1,249 @@
+use crate::Error;
+use serde::Deserialize;
+use serde::Serialize;
+use std::io;
+use std::mem;
+use std::str::FromStr;
+use failure::ResultExt;
+
+const SCHED_FLAG_RESET_ON_FORK: u64 = 0x01;
+const SCHED_FLAG_RECLAIM: u64 = 0x02;
+const SCHED_FLAG_DL_OVERRUN: u64 = 0x04;
+const SCHED_FLAG_KEEP_POLICY: u64 = 0x08;
+const SCHED_FLAG_KEEP_PARAMS: u64 = 0x10;
+const SCHED_FLAG_UTIL_CLAMP_MIN: u64 = 0x20;
+const SCHED_FLAG_UTIL_CLAMP_MAX: u64 = 0x40;
+
+const NICE_MIN: i32 = -20;
+const NICE_MAX: i32 = 19;
+const RT_PRIORITY_MIN: i32 = 1;
+const RT_PRIORITY_MAX: i32 = 99;
+// The kernel refuses deadline runtimes under 2^10 nanoseconds.
+const DL_RUNTIME_MIN: u64 = 1 << 10;
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Policy {
+    SCHED_OTHER,
+    SCHED_FIFO,
+    SCHED_RR,
+    SCHED_BATCH,
+    SCHED_IDLE,
+    SCHED_DEADLINE,
+}
+
+impl FromStr for Policy {
+    type Err = Error;
+
+    fn from_str(policy: &str) -> Result<Policy, Error> {
+        match policy {
+            "SCHED_OTHER" => Ok(Policy::SCHED_OTHER),
+            "SCHED_FIFO" => Ok(Policy::SCHED_FIFO),
+            "SCHED_RR" => Ok(Policy::SCHED_RR),
+            "SCHED_BATCH" => Ok(Policy::SCHED_BATCH),
+            "SCHED_IDLE" => Ok(Policy::SCHED_IDLE),
+            "SCHED_DEADLINE" => Ok(Policy::SCHED_DEADLINE),
+            "SCHED_ISO" => Err(Error::from("policy SCHED_ISO is not supported by linux".to_string()))?,
+            _ => Err(Error::from("invalid policy".to_string())).context(policy.to_string())?,
+        }
+    }
+}
+
+impl Policy {
+    fn to_libc(self) -> u32 {
+        match self {
+            Policy::SCHED_OTHER => 0,
+            Policy::SCHED_FIFO => 1,
+            Policy::SCHED_RR => 2,
+            Policy::SCHED_BATCH => 3,
+            Policy::SCHED_IDLE => 5,
+            Policy::SCHED_DEADLINE => 6,
+        }
+    }
+
+    fn is_realtime(self) -> bool {
+        self == Policy::SCHED_FIFO || self == Policy::SCHED_RR
+    }
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SchedulerFlag {
+    SCHED_FLAG_RESET_ON_FORK,
+    SCHED_FLAG_RECLAIM,
+    SCHED_FLAG_DL_OVERRUN,
+    SCHED_FLAG_KEEP_POLICY,
+    SCHED_FLAG_KEEP_PARAMS,
+    SCHED_FLAG_UTIL_CLAMP_MIN,
+    SCHED_FLAG_UTIL_CLAMP_MAX,
+}
+
+impl FromStr for SchedulerFlag {
+    type Err = Error;
+
+    fn from_str(flag: &str) -> Result<SchedulerFlag, Error> {
+        match flag {
+            "SCHED_FLAG_RESET_ON_FORK" => Ok(SchedulerFlag::SCHED_FLAG_RESET_ON_FORK),
+            "SCHED_FLAG_RECLAIM" => Ok(SchedulerFlag::SCHED_FLAG_RECLAIM),
+            "SCHED_FLAG_DL_OVERRUN" => Ok(SchedulerFlag::SCHED_FLAG_DL_OVERRUN),
+            "SCHED_FLAG_KEEP_POLICY" => Ok(SchedulerFlag::SCHED_FLAG_KEEP_POLICY),
+            "SCHED_FLAG_KEEP_PARAMS" => Ok(SchedulerFlag::SCHED_FLAG_KEEP_PARAMS),
+            "SCHED_FLAG_UTIL_CLAMP_MIN" => Ok(SchedulerFlag::SCHED_FLAG_UTIL_CLAMP_MIN),
+            "SCHED_FLAG_UTIL_CLAMP_MAX" => Ok(SchedulerFlag::SCHED_FLAG_UTIL_CLAMP_MAX),
+            _ => Err(Error::from("invalid flag".to_string())).context(flag.to_string())?,
+        }
+    }
+}
+
+impl SchedulerFlag {
+    fn to_libc(self) -> u64 {
+        match self {
+            SchedulerFlag::SCHED_FLAG_RESET_ON_FORK => SCHED_FLAG_RESET_ON_FORK,
+            SchedulerFlag::SCHED_FLAG_RECLAIM => SCHED_FLAG_RECLAIM,
+            SchedulerFlag::SCHED_FLAG_DL_OVERRUN => SCHED_FLAG_DL_OVERRUN,
+            SchedulerFlag::SCHED_FLAG_KEEP_POLICY => SCHED_FLAG_KEEP_POLICY,
+            SchedulerFlag::SCHED_FLAG_KEEP_PARAMS => SCHED_FLAG_KEEP_PARAMS,
+            SchedulerFlag::SCHED_FLAG_UTIL_CLAMP_MIN => SCHED_FLAG_UTIL_CLAMP_MIN,
+            SchedulerFlag::SCHED_FLAG_UTIL_CLAMP_MAX => SCHED_FLAG_UTIL_CLAMP_MAX,
+        }
+    }
+}
+
+// struct sched_attr from include/uapi/linux/sched/types.h, with the
+// utilization clamps added in 5.3.
+#[repr(C)]
+#[derive(Debug, Default)]
+struct SchedAttr {
+    size: u32,
+    sched_policy: u32,
+    sched_flags: u64,
+    sched_nice: i32,
+    sched_priority: u32,
+    sched_runtime: u64,
+    sched_deadline: u64,
+    sched_period: u64,
+    sched_util_min: u32,
+    sched_util_max: u32,
+}
+
+// Scheduling of the container process, set with sched_setattr(2).
+// Deadline times are in nanoseconds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Scheduler {
+    policy: Policy,
+    nice: i32,
+    priority: i32,
+    flags: Vec<SchedulerFlag>,
+    runtime: u64,
+    deadline: u64,
+    period: u64,
+}
+
+impl Scheduler {
+    pub fn new(policy: Policy, nice: i32, priority: i32, flags: Vec<SchedulerFlag>) -> Result<Scheduler, Error> {
+        if !(NICE_MIN..=NICE_MAX).contains(&nice) {
+            Err(Error::from(format!("nice {} must be between {} and {}", nice, NICE_MIN, NICE_MAX)))?;
+        }
+        if policy.is_realtime() && !(RT_PRIORITY_MIN..=RT_PRIORITY_MAX).contains(&priority) {
+            Err(Error::from(format!("priority {} must be between {} and {} for {:?}", priority, RT_PRIORITY_MIN, RT_PRIORITY_MAX, policy)))?;
+        }
+        if !policy.is_realtime() && priority != 0 {
+            Err(Error::from(format!("priority {} must be 0 for {:?}, only SCHED_FIFO and SCHED_RR take one", priority, policy)))?;
+        }
+        let deadline_flags = [SchedulerFlag::SCHED_FLAG_RECLAIM, SchedulerFlag::SCHED_FLAG_DL_OVERRUN];
+        if let Some(flag) = flags.iter().find(|flag| deadline_flags.contains(flag)) {
+            if policy != Policy::SCHED_DEADLINE {
+                Err(Error::from(format!("flag {:?} requires SCHED_DEADLINE", flag)))?;
+            }
+        }
+
+        Ok(Scheduler { policy, nice, priority, flags, runtime: 0, deadline: 0, period: 0 })
+    }
+
+    // A period of 0 means the period is the deadline, as for the kernel.
+    pub fn set_deadline_params(&mut self, runtime: u64, deadline: u64, period: u64) -> Result<(), Error> {
+        if self.policy != Policy::SCHED_DEADLINE {
+            if runtime != 0 || deadline != 0 || period != 0 {
+                Err(Error::from(format!("runtime, deadline and period are only for SCHED_DEADLINE, not {:?}", self.policy)))?;
+            }
+            return Ok(());
+        }
+        if runtime < DL_RUNTIME_MIN {
+            Err(Error::from(format!("runtime {} must be at least {}", runtime, DL_RUNTIME_MIN)))?;
+        }
+        if deadline < runtime {
+            Err(Error::from(format!("deadline {} must not be shorter than runtime {}", deadline, runtime)))?;
+        }
+        if period != 0 && period < deadline {
+            Err(Error::from(format!("period {} must not be shorter than deadline {}", period, deadline)))?;
+        }
+
+        self.runtime = runtime;
+        self.deadline = deadline;
+        self.period = period;
+        Ok(())
+    }
+
+    pub fn set(&self) -> Result<(), Error> {
+        let attr = self.to_attr();
+        if unsafe { libc::syscall(libc::SYS_sched_setattr, 0, &attr as *const SchedAttr, 0) } != 0 {
+            Err(io::Error::last_os_error()).context(format!("cannot set scheduler policy {:?}", self.policy))?;
+        }
+        Ok(())
+    }
+
+    fn to_attr(&self) -> SchedAttr {
+        SchedAttr {
+            size: mem::size_of::<SchedAttr>() as u32,
+            sched_policy: self.policy.to_libc(),
+            sched_flags: self.flags.iter().fold(0, |flags, flag| flags | flag.to_libc()),
+            sched_nice: self.nice,
+            sched_priority: self.priority as u32,
+            sched_runtime: self.runtime,
+            sched_deadline: self.deadline,
+            sched_period: self.period,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scheduler_checks_nice_and_priority_against_policy() {
+        assert!(Scheduler::new(Policy::SCHED_IDLE, 0, 0, vec![]).is_ok());
+        assert!(Scheduler::new(Policy::SCHED_BATCH, 19, 0, vec![SchedulerFlag::SCHED_FLAG_RESET_ON_FORK]).is_ok());
+        assert!(Scheduler::new(Policy::SCHED_FIFO, 0, 99, vec![]).is_ok());
+
+        assert!(Scheduler::new(Policy::SCHED_OTHER, 20, 0, vec![]).is_err());
+        assert!(Scheduler::new(Policy::SCHED_RR, 0, 0, vec![]).is_err());
+        assert!(Scheduler::new(Policy::SCHED_IDLE, 0, 10, vec![]).is_err());
+        assert!(Scheduler::new(Policy::SCHED_OTHER, 0, 0, vec![SchedulerFlag::SCHED_FLAG_RECLAIM]).is_err());
+        assert!(Policy::from_str("SCHED_ISO").is_err());
+    }
+
+    #[test]
+    fn scheduler_checks_deadline_params() {
+        let mut deadline = Scheduler::new(Policy::SCHED_DEADLINE, 0, 0, vec![]).unwrap();
+        assert!(deadline.set_deadline_params(10_000_000, 30_000_000, 0).is_ok());
+        assert!(deadline.set_deadline_params(100, 30_000_000, 0).is_err());
+        assert!(deadline.set_deadline_params(10_000_000, 5_000_000, 0).is_err());
+        assert!(deadline.set_deadline_params(10_000_000, 30_000_000, 20_000_000).is_err());
+
+        let mut other = Scheduler::new(Policy::SCHED_OTHER, 0, 0, vec![]).unwrap();
+        assert!(other.set_deadline_params(0, 0, 0).is_ok());
+        assert!(other.set_deadline_params(10_000_000, 30_000_000, 0).is_err());
+    }
+
+    #[test]
+    fn scheduler_builds_sched_attr() {
+        let mut scheduler = Scheduler::new(Policy::SCHED_DEADLINE, 0, 0, vec![SchedulerFlag::SCHED_FLAG_RESET_ON_FORK, SchedulerFlag::SCHED_FLAG_DL_OVERRUN]).unwrap();
+        scheduler.set_deadline_params(10_000_000, 30_000_000, 100_000_000).unwrap();
+
+        let attr = scheduler.to_attr();
+        assert_eq!((attr.size, attr.sched_policy, attr.sched_flags), (56, 6, 0x05));
+        assert_eq!((attr.sched_runtime, attr.sched_deadline, attr.sched_period), (10_000_000, 30_000_000, 100_000_000));
+    }
+}
@@ -0,0 +1,360 @@
+use crate::Error;
+use super::mediate::Syscall;
+use super::notify;
+use super::notify::Notification;
+use super::notify::Response;
+use serde::Deserialize;
+use serde_json::Value;
+use nix::unistd;
+use std::fs;
+use std::io::Read;
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::io::RawFd;
+use std::os::unix::net::UnixListener;
+use std::os::unix::net::UnixStream;
+use std::sync::Arc;
+use std::thread;
+use failure::ResultExt;
+
+// Decides what happens to a syscall the filter sent to the agent. When the
+// policy lets it go ahead with Response::Continue, the agent carries out
+// mknod and mount itself, any other syscall runs with the privileges of
+// the process.
+pub trait Policy: Send + Sync {
+    fn decide(&self, syscall: &Syscall) -> Response;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Decision {
+    Allow,
+    Deny,
+}
+
+// A device node mknod may make, any major or minor when left out.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+struct DeviceMatch {
+    #[serde(rename = "type")]
+    device_type: String,
+    major: Option<u32>,
+    minor: Option<u32>,
+}
+
+impl DeviceMatch {
+    fn matches(&self, (device_type, major, minor): (&str, u32, u32)) -> bool {
+        self.device_type == device_type
+            && self.major.is_none_or(|m| m == major)
+            && self.minor.is_none_or(|m| m == minor)
+    }
+}
+
+// With devices or fsTypes a rule only applies to mknod of those devices or
+// mount of those filesystems.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PolicyRule {
+    names: Vec<String>,
+    action: Decision,
+    errno: Option<i32>,
+
+    #[serde(default)]
+    devices: Option<Vec<DeviceMatch>>,
+
+    #[serde(default)]
+    fs_types: Option<Vec<String>>,
+}
+
+impl PolicyRule {
+    fn matches(&self, syscall: &Syscall) -> bool {
+        self.names.iter().any(|name| name == syscall.name())
+            && self.devices.as_ref().is_none_or(|devices| syscall.device().is_some_and(|device| devices.iter().any(|d| d.matches(device))))
+            && self.fs_types.as_ref().is_none_or(|fs_types| syscall.fs_type().is_some_and(|fs_type| fs_types.iter().any(|t| t == fs_type)))
+    }
+}
+
+// A policy file of the form:
+//   {"defaultAction": "deny", "rules": [
+//     {"names": ["mknod", "mknodat"], "action": "allow", "devices": [{"type": "c", "major": 1, "minor": 3}]},
+//     {"names": ["mount"], "action": "allow", "fsTypes": ["tmpfs"]}]}
+// Denied syscalls fail with EPERM unless another errno is given.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RulesPolicy {
+    default_action: Decision,
+
+    #[serde(default)]
+    default_errno: Option<i32>,
+
+    #[serde(default)]
+    rules: Vec<PolicyRule>,
+}
+
+impl RulesPolicy {
+    // Syscalls are only notified because the profile does not trust them,
+    // so without a policy none goes through.
+    pub fn deny_all() -> RulesPolicy {
+        RulesPolicy {
+            default_action: Decision::Deny,
+            default_errno: None,
+            rules: Vec::new(),
+        }
+    }
+
+    pub fn load<R: Read>(reader: R) -> Result<RulesPolicy, Error> {
+        let policy: RulesPolicy = serde_json::from_reader(reader).context("error reading seccomp agent policy".to_string())?;
+        Ok(policy)
+    }
+
+    fn decide_syscall(&self, syscall: &Syscall) -> Response {
+        let (decision, errno) = match self.rules.iter().find(|rule| rule.matches(syscall)) {
+            Some(rule) => (rule.action, rule.errno),
+            None => (self.default_action, self.default_errno),
+        };
+        match decision {
+            Decision::Allow => Response::Continue,
+            Decision::Deny => Response::Errno(errno.unwrap_or(libc::EPERM)),
+        }
+    }
+}
+
+impl Policy for RulesPolicy {
+    fn decide(&self, syscall: &Syscall) -> Response {
+        self.decide_syscall(syscall)
+    }
+}
+
+// Listens on the socket set as listenerPath and answers the notifications
+// of every container that connects, one thread per container.
+pub struct Agent<P: Policy + 'static> {
+    socket_path: String,
+    policy: Arc<P>,
+}
+
+impl<P: Policy + 'static> Agent<P> {
+    pub fn new(socket_path: &str, policy: P) -> Agent<P> {
+        Agent {
+            socket_path: socket_path.to_string(),
+            policy: Arc::new(policy),
+        }
+    }
+
+    pub fn run(&self) -> Result<(), Error> {
+        // A socket left behind by a previous agent would make bind fail.
+        if let Ok(metadata) = fs::symlink_metadata(&self.socket_path) {
+            if metadata.file_type().is_socket() {
+                fs::remove_file(&self.socket_path).context(format!("cannot remove {}", self.socket_path))?;
+            }
+        }
+        let listener = UnixListener::bind(&self.socket_path).context(format!("cannot listen on {}", self.socket_path))?;
+
+        for stream in listener.incoming() {
+            let stream = stream.context(format!("cannot accept connection on {}", self.socket_path))?;
+            match accept(&stream) {
+                Ok((container_id, notify_fd)) => {
+                    let policy = Arc::clone(&self.policy);
+                    thread::spawn(move || {
+                        if let Err(err) = serve(&container_id, notify_fd, policy.as_ref()) {
+                            eprintln!("{}: {}", container_id, err);
+                        }
+                        let _ = unistd::close(notify_fd);
+                    });
+                }
+                Err(err) => eprintln!("{}", err),
+            }
+        }
+        Ok(())
+    }
+}
+
+// Reads the container process state and the notify fd sent by the runtime.
+fn accept(stream: &UnixStream) -> Result<(String, RawFd), Error> {
+    let (message, notify_fd) = notify::receive_fd(stream.as_raw_fd()).context("cannot receive seccomp fd".to_string())?;
+    let notify_fd = match notify_fd {
+        Some(fd) => fd,
+        None => Err(Error::from("no seccomp fd received".to_string())).context(String::from_utf8_lossy(&message).to_string())?,
+    };
+    let container_id = serde_json::from_slice::<Value>(&message).ok()
+        .and_then(|process_state| process_state["state"]["id"].as_str().map(String::from))
+        .unwrap_or_default();
+    Ok((container_id, notify_fd))
+}
+
+fn serve<P: Policy>(container_id: &str, notify_fd: RawFd, policy: &P) -> Result<(), Error> {
+    while let Some(notification) = Notification::receive(notify_fd)? {
+        let response = match Syscall::read(&notification, notify_fd) {
+            Ok(syscall) => match policy.decide(&syscall) {
+                Response::Continue => syscall.perform(&notification, notify_fd).unwrap_or_else(|err| {
+                    eprintln!("{}: {}", container_id, err);
+                    Response::Errno(libc::EPERM)
+                }),
+                response => response,
+            },
+            Err(err) => {
+                eprintln!("{}: {}", container_id, err);
+                Response::Errno(libc::EPERM)
+            },
+        };
+        println!("{} {} {} {:?}", container_id, notification.pid(), notification.syscall_name().unwrap_or("unknown"), response);
+        notification.respond(notify_fd, response)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::Action;
+    use super::super::Rule;
+    use super::super::Seccomp;
+    use super::super::mediate::Call;
+    use super::super::notify::Listener;
+    use nix::sys::wait;
+    use nix::sys::wait::WaitStatus;
+    use nix::unistd::ForkResult;
+    use std::ffi::CString;
+    use std::io;
+    use std::os::unix::fs::MetadataExt;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    fn syscall(name: &str) -> Syscall {
+        Syscall::new(name, Call::Other)
+    }
+
+    fn mknod(mode: libc::mode_t, major: u32, minor: u32) -> Syscall {
+        Syscall::new("mknodat", Call::Mknod { dirfd: None, path: CString::new("dev").unwrap(), mode, dev: (major << 8) | minor })
+    }
+
+    fn mount(fs_type: &str, flags: libc::c_ulong) -> Syscall {
+        let call = Call::Mount {
+            source: Some(CString::new("none").unwrap()),
+            target: CString::new("/mnt").unwrap(),
+            fs_type: Some(CString::new(fs_type).unwrap()),
+            flags,
+            data: None,
+        };
+        Syscall::new("mount", call)
+    }
+
+    #[test]
+    fn rules_policy_uses_the_first_matching_rule() {
+        let data = r#"{"defaultAction":"allow","rules":[{"names":["mkdir","mkdirat"],"action":"deny","errno":13},{"names":["mkdirat"],"action":"allow"}]}"#;
+        let policy = RulesPolicy::load(data.as_bytes()).unwrap();
+        assert_eq!(policy.decide_syscall(&syscall("mkdirat")), Response::Errno(13));
+        assert_eq!(policy.decide_syscall(&syscall("read")), Response::Continue);
+    }
+
+    #[test]
+    fn rules_policy_denies_with_eperm_by_default() {
+        let policy = RulesPolicy::load(r#"{"defaultAction":"deny","rules":[{"names":["getpid"],"action":"allow"}]}"#.as_bytes()).unwrap();
+        assert_eq!(policy.decide_syscall(&syscall("mount")), Response::Errno(libc::EPERM));
+        assert_eq!(policy.decide_syscall(&syscall("getpid")), Response::Continue);
+        assert_eq!(RulesPolicy::deny_all().decide_syscall(&syscall("mount")), Response::Errno(libc::EPERM));
+    }
+
+    #[test]
+    fn rules_policy_matches_devices_and_fs_types() {
+        let data = r#"{"defaultAction":"deny","rules":[
+            {"names":["mknod","mknodat"],"action":"allow","devices":[{"type":"c","major":1,"minor":3},{"type":"c","major":136},{"type":"p"}]},
+            {"names":["mount"],"action":"allow","fsTypes":["tmpfs"]}]}"#;
+        let policy = RulesPolicy::load(data.as_bytes()).unwrap();
+
+        assert_eq!(policy.decide_syscall(&mknod(libc::S_IFCHR, 1, 3)), Response::Continue);
+        assert_eq!(policy.decide_syscall(&mknod(libc::S_IFCHR, 136, 7)), Response::Continue);
+        assert_eq!(policy.decide_syscall(&mknod(libc::S_IFIFO, 0, 0)), Response::Continue);
+        assert_eq!(policy.decide_syscall(&mknod(libc::S_IFCHR, 1, 5)), Response::Errno(libc::EPERM));
+        assert_eq!(policy.decide_syscall(&mknod(libc::S_IFBLK, 1, 3)), Response::Errno(libc::EPERM));
+        assert_eq!(policy.decide_syscall(&mknod(libc::S_IFREG, 0, 0)), Response::Errno(libc::EPERM));
+
+        assert_eq!(policy.decide_syscall(&mount("tmpfs", libc::MS_NOSUID)), Response::Continue);
+        assert_eq!(policy.decide_syscall(&mount("tmpfs", libc::MS_BIND)), Response::Errno(libc::EPERM));
+        assert_eq!(policy.decide_syscall(&mount("proc", 0)), Response::Errno(libc::EPERM));
+    }
+
+    #[test]
+    fn rules_policy_rejects_unknown_decisions() {
+        assert!(RulesPolicy::load(r#"{"defaultAction":"maybe"}"#.as_bytes()).is_err());
+    }
+
+    // Runs check in a child that sends the syscalls named to the agent,
+    // with the ids of an unprivileged user so only the agent can carry
+    // them out.
+    fn serve_unprivileged<F: FnOnce() -> bool>(policy: &str, names: &[&str], check: F) -> WaitStatus {
+        let (runtime, init) = UnixStream::pair().unwrap();
+        let policy = RulesPolicy::load(policy.as_bytes()).unwrap();
+
+        let child = match unistd::fork().unwrap() {
+            ForkResult::Child => {
+                let mut seccomp = Seccomp::new(Action::Allow);
+                seccomp.add_rule(Rule::new(&names.iter().map(|name| name.to_string()).collect::<Vec<String>>(), Action::Notify));
+                seccomp.set_listener(Listener::new("/run/agent.sock", None));
+                let dropped = unsafe { libc::setgid(1000) == 0 && libc::setuid(1000) == 0 && libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) == 0 };
+                let sent = match seccomp.install() {
+                    Ok(Some(notify_fd)) => notify::send_fd(init.as_raw_fd(), notify_fd, b"{}").is_ok() && unistd::close(notify_fd).is_ok(),
+                    _ => false,
+                };
+                let checked = dropped && sent && check();
+                unsafe { libc::_exit(if checked { 0 } else { 1 }) }
+            },
+            ForkResult::Parent { child } => child,
+        };
+
+        let (_, notify_fd) = accept(&runtime).unwrap();
+        serve("test", notify_fd, &policy).unwrap();
+        let _ = unistd::close(notify_fd);
+        wait::waitpid(child, None).unwrap()
+    }
+
+    fn c_path(path: &Path) -> CString {
+        CString::new(path.to_str().unwrap()).unwrap()
+    }
+
+    fn last_errno() -> Option<i32> {
+        io::Error::last_os_error().raw_os_error()
+    }
+
+    #[test]
+    fn serve_makes_allowed_devices_for_an_unprivileged_process() {
+        let dir = tempdir().unwrap();
+        fs::set_permissions(dir.path(), fs::Permissions::from_mode(0o777)).unwrap();
+        let (null, zero) = (dir.path().join("null"), dir.path().join("zero"));
+        let policy = r#"{"defaultAction":"deny","rules":[{"names":["mknod","mknodat"],"action":"allow","devices":[{"type":"c","major":1,"minor":3}]}]}"#;
+
+        let status = serve_unprivileged(policy, &["mknod", "mknodat"], || {
+            let mknod = |path: &Path, minor: u32| unsafe { libc::mknod(c_path(path).as_ptr(), libc::S_IFCHR | 0o600, libc::dev_t::from((1 << 8) | minor)) };
+            let made = mknod(&null, 3) == 0
+                && fs::symlink_metadata(&null).is_ok_and(|metadata| metadata.file_type().is_char_device() && metadata.rdev() == 0x103 && metadata.uid() == 1000);
+            let denied = mknod(&zero, 5) == -1 && last_errno() == Some(libc::EPERM);
+            made && denied
+        });
+
+        assert_eq!(status, WaitStatus::Exited(status.pid().unwrap(), 0));
+        assert!(!zero.exists());
+    }
+
+    #[test]
+    fn serve_mounts_allowed_filesystems_for_an_unprivileged_process() {
+        let dir = tempdir().unwrap();
+        let target = dir.path().join("mnt");
+        fs::create_dir(&target).unwrap();
+        let policy = r#"{"defaultAction":"deny","rules":[{"names":["mount"],"action":"allow","fsTypes":["tmpfs"]}]}"#;
+
+        let status = serve_unprivileged(policy, &["mount"], || {
+            let mount = |fs_type: &str| {
+                let fs_type = CString::new(fs_type).unwrap();
+                unsafe { libc::mount(fs_type.as_ptr(), c_path(&target).as_ptr(), fs_type.as_ptr(), libc::MS_NOSUID, std::ptr::null()) }
+            };
+            let denied = mount("ramfs") == -1 && last_errno() == Some(libc::EPERM);
+            let mounted = mount("tmpfs") == 0;
+            denied && mounted
+        });
+
+        let mounted = fs::read_to_string("/proc/self/mountinfo").unwrap().lines()
+            .any(|line| line.contains(target.to_str().unwrap()) && line.contains(" - tmpfs "));
+        let _ = nix::mount::umount(&target);
+        assert_eq!(status, WaitStatus::Exited(status.pid().unwrap(), 0));
+        assert!(mounted, "expect tmpfs to be mounted on {:?}", target);
+    }
+}
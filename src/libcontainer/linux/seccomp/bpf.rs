@@ -0,0 +1,455 @@
+use crate::Error;
+use super::Action;
+use super::Arch;
+use super::Comparison;
+use super::Condition;
+use super::Rule;
+use super::SECCOMP_RET_KILL_PROCESS;
+use super::X32_SYSCALL_BIT;
+
+const BPF_LD: u16 = 0x00;
+const BPF_ALU: u16 = 0x04;
+const BPF_JMP: u16 = 0x05;
+const BPF_RET: u16 = 0x06;
+const BPF_W: u16 = 0x00;
+const BPF_ABS: u16 = 0x20;
+const BPF_K: u16 = 0x00;
+const BPF_AND: u16 = 0x50;
+const BPF_JA: u16 = 0x00;
+const BPF_JEQ: u16 = 0x10;
+const BPF_JGT: u16 = 0x20;
+const BPF_JGE: u16 = 0x30;
+
+const BPF_MAXINSNS: usize = 4096;
+
+// Offsets into struct seccomp_data.
+const NR_OFFSET: u32 = 0;
+const ARCH_OFFSET: u32 = 4;
+const ARGS_OFFSET: u32 = 16;
+
+#[cfg(target_endian = "little")]
+const LOW_WORD: u32 = 0;
+#[cfg(target_endian = "big")]
+const LOW_WORD: u32 = 4;
+
+// Conditional jumps only reach 255 instructions ahead, so long syscall
+// lists are split in chunks sharing one return each.
+const MAX_CHUNK: usize = 200;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SockFilter {
+    pub code: u16,
+    pub jt: u8,
+    pub jf: u8,
+    pub k: u32,
+}
+
+#[repr(C)]
+pub struct SockFprog {
+    pub len: libc::c_ushort,
+    pub filter: *const SockFilter,
+}
+
+fn stmt(code: u16, k: u32) -> SockFilter {
+    SockFilter { code, jt: 0, jf: 0, k }
+}
+
+fn jump(code: u16, k: u32, jt: u8, jf: u8) -> SockFilter {
+    SockFilter { code: BPF_JMP | code | BPF_K, jt, jf, k }
+}
+
+fn load(offset: u32) -> SockFilter {
+    stmt(BPF_LD | BPF_W | BPF_ABS, offset)
+}
+
+fn ret(value: u32) -> SockFilter {
+    stmt(BPF_RET | BPF_K, value)
+}
+
+// Jump targets inside a rule body, where failing any condition skips the
+// rest of the rule.
+#[derive(Clone, Copy)]
+enum Target {
+    Next(u8),
+    Fail,
+}
+
+struct Pending {
+    code: u16,
+    k: u32,
+    jt: Target,
+    jf: Target,
+}
+
+impl Pending {
+    fn stmt(filter: SockFilter) -> Pending {
+        Pending { code: filter.code, k: filter.k, jt: Target::Next(0), jf: Target::Next(0) }
+    }
+
+    fn jump(code: u16, k: u32, jt: Target, jf: Target) -> Pending {
+        Pending { code: BPF_JMP | code | BPF_K, k, jt, jf }
+    }
+}
+
+// The filter checks the architecture first, then jumps to the section
+// with the syscall rules of that architecture. Anything else is killed.
+pub fn compile(default_action: Action, architectures: &[Arch], rules: &[Rule]) -> Result<Vec<SockFilter>, Error> {
+    let mut sections: Vec<(u32, Vec<SockFilter>)> = Vec::new();
+    for arch in architectures {
+        if sections.iter().any(|(audit_arch, _)| *audit_arch == arch.audit_arch()) {
+            continue;
+        }
+        let section = match arch {
+            Arch::X86_64 | Arch::X32 => x86_64_section(default_action, architectures, rules)?,
+            _ => arch_section(*arch, default_action, rules)?,
+        };
+        sections.push((arch.audit_arch(), section));
+    }
+
+    let mut program = vec![load(ARCH_OFFSET)];
+    let mut start = 1 + sections.len() * 2 + 1;
+    for (audit_arch, section) in &sections {
+        let position = program.len();
+        program.push(jump(BPF_JEQ, *audit_arch, 0, 1));
+        program.push(jump(BPF_JA, (start - (position + 2)) as u32, 0, 0));
+        start += section.len();
+    }
+    program.push(ret(SECCOMP_RET_KILL_PROCESS));
+    for (_, section) in sections {
+        program.extend(section);
+    }
+
+    if program.len() > BPF_MAXINSNS {
+        Err(Error::from(format!("seccomp filter has {} instructions, more than the {} allowed", program.len(), BPF_MAXINSNS)))?;
+    }
+    Ok(program)
+}
+
+// Splits x86_64 and x32 syscalls, killing the ones from an architecture
+// that is not in the filter.
+fn x86_64_section(default_action: Action, architectures: &[Arch], rules: &[Rule]) -> Result<Vec<SockFilter>, Error> {
+    let native = match architectures.contains(&Arch::X86_64) {
+        true => arch_section(Arch::X86_64, default_action, rules)?,
+        false => vec![ret(SECCOMP_RET_KILL_PROCESS)],
+    };
+    let x32 = match architectures.contains(&Arch::X32) {
+        true => arch_section(Arch::X32, default_action, rules)?,
+        false => vec![ret(SECCOMP_RET_KILL_PROCESS)],
+    };
+
+    let mut section = vec![
+        load(NR_OFFSET),
+        jump(BPF_JGE, X32_SYSCALL_BIT, 0, 1),
+        jump(BPF_JA, native.len() as u32, 0, 0),
+    ];
+    section.extend(native);
+    section.extend(x32);
+    Ok(section)
+}
+
+// Rules are checked in order and the first match wins. Syscalls unknown to
+// the architecture are skipped, and so are rules with the default action.
+fn arch_section(arch: Arch, default_action: Action, rules: &[Rule]) -> Result<Vec<SockFilter>, Error> {
+    let mut section = vec![load(NR_OFFSET)];
+    let mut nr_loaded = true;
+
+    for rule in rules {
+        if rule.action == default_action {
+            continue;
+        }
+        let mut numbers: Vec<u32> = Vec::new();
+        for nr in rule.names.iter().filter_map(|name| arch.syscall_nr(name)) {
+            if !numbers.contains(&nr) {
+                numbers.push(nr);
+            }
+        }
+        if numbers.is_empty() {
+            continue;
+        }
+
+        if rule.conditions.is_empty() {
+            if !nr_loaded {
+                section.push(load(NR_OFFSET));
+                nr_loaded = true;
+            }
+            for chunk in numbers.chunks(MAX_CHUNK) {
+                for (index, nr) in chunk.iter().enumerate() {
+                    section.push(jump(BPF_JEQ, *nr, (chunk.len() - index) as u8, 0));
+                }
+                section.push(jump(BPF_JA, 1, 0, 0));
+                section.push(ret(rule.action.to_ret()));
+            }
+            continue;
+        }
+
+        for conditions in alternatives(&rule.conditions) {
+            let body = rule_body(arch, &conditions, rule.action)?;
+            for nr in &numbers {
+                if !nr_loaded {
+                    section.push(load(NR_OFFSET));
+                }
+                section.push(jump(BPF_JEQ, *nr, 0, body.len() as u8));
+                section.extend(body.iter().cloned());
+                nr_loaded = false;
+            }
+        }
+    }
+
+    section.push(ret(default_action.to_ret()));
+    Ok(section)
+}
+
+// Conditions of a rule must all match, except when several of them check
+// the same argument, which runc treats as alternatives.
+fn alternatives(conditions: &[Condition]) -> Vec<Vec<Condition>> {
+    let repeated = conditions.iter().enumerate()
+        .any(|(position, condition)| conditions[..position].iter().any(|other| other.index == condition.index));
+    match repeated {
+        true => conditions.iter().map(|condition| vec![condition.clone()]).collect(),
+        false => vec![conditions.to_vec()],
+    }
+}
+
+fn rule_body(arch: Arch, conditions: &[Condition], action: Action) -> Result<Vec<SockFilter>, Error> {
+    let mut pending: Vec<Pending> = Vec::new();
+    for condition in conditions {
+        pending.extend(compare(arch, condition));
+    }
+    pending.push(Pending::stmt(ret(action.to_ret())));
+
+    let len = pending.len();
+    if len > usize::from(u8::MAX) {
+        Err(Error::from("seccomp rule has too many argument conditions".to_string()))?;
+    }
+    let resolve = |target: Target, position: usize| match target {
+        Target::Next(offset) => offset,
+        Target::Fail => (len - position - 1) as u8,
+    };
+    Ok(pending.iter().enumerate().map(|(position, insn)| SockFilter {
+        code: insn.code,
+        jt: resolve(insn.jt, position),
+        jf: resolve(insn.jf, position),
+        k: insn.k,
+    }).collect())
+}
+
+// Arguments are 64 bits wide, so each comparison checks the high word
+// first and only looks at the low word when the high words are equal.
+fn compare(arch: Arch, condition: &Condition) -> Vec<Pending> {
+    use self::Target::Fail;
+    use self::Target::Next;
+
+    let low = ARGS_OFFSET + 8 * condition.index + LOW_WORD;
+    let high = ARGS_OFFSET + 8 * condition.index + (4 - LOW_WORD);
+    let (value_high, value_low) = ((condition.value >> 32) as u32, condition.value as u32);
+    let (datum_high, datum_low) = ((condition.value_two >> 32) as u32, condition.value_two as u32);
+
+    if arch.is_32bit() {
+        let check = match condition.comparison {
+            Comparison::Equal => Pending::jump(BPF_JEQ, value_low, Next(0), Fail),
+            Comparison::NotEqual => Pending::jump(BPF_JEQ, value_low, Fail, Next(0)),
+            Comparison::GreaterThan => Pending::jump(BPF_JGT, value_low, Next(0), Fail),
+            Comparison::GreaterOrEqual => Pending::jump(BPF_JGE, value_low, Next(0), Fail),
+            Comparison::LessThan => Pending::jump(BPF_JGE, value_low, Fail, Next(0)),
+            Comparison::LessOrEqual => Pending::jump(BPF_JGT, value_low, Fail, Next(0)),
+            Comparison::MaskedEqual => return vec![
+                Pending::stmt(load(low)),
+                Pending::stmt(stmt(BPF_ALU | BPF_AND | BPF_K, value_low)),
+                Pending::jump(BPF_JEQ, datum_low, Next(0), Fail),
+            ],
+        };
+        return vec![Pending::stmt(load(low)), check];
+    }
+
+    match condition.comparison {
+        Comparison::Equal => vec![
+            Pending::stmt(load(high)),
+            Pending::jump(BPF_JEQ, value_high, Next(0), Fail),
+            Pending::stmt(load(low)),
+            Pending::jump(BPF_JEQ, value_low, Next(0), Fail),
+        ],
+        Comparison::NotEqual => vec![
+            Pending::stmt(load(high)),
+            Pending::jump(BPF_JEQ, value_high, Next(0), Next(2)),
+            Pending::stmt(load(low)),
+            Pending::jump(BPF_JEQ, value_low, Fail, Next(0)),
+        ],
+        Comparison::GreaterThan | Comparison::GreaterOrEqual => vec![
+            Pending::stmt(load(high)),
+            Pending::jump(BPF_JGT, value_high, Next(3), Next(0)),
+            Pending::jump(BPF_JEQ, value_high, Next(0), Fail),
+            Pending::stmt(load(low)),
+            match condition.comparison {
+                Comparison::GreaterThan => Pending::jump(BPF_JGT, value_low, Next(0), Fail),
+                _ => Pending::jump(BPF_JGE, value_low, Next(0), Fail),
+            },
+        ],
+        Comparison::LessThan | Comparison::LessOrEqual => vec![
+            Pending::stmt(load(high)),
+            Pending::jump(BPF_JGT, value_high, Fail, Next(0)),
+            Pending::jump(BPF_JEQ, value_high, Next(0), Next(2)),
+            Pending::stmt(load(low)),
+            match condition.comparison {
+                Comparison::LessThan => Pending::jump(BPF_JGE, value_low, Fail, Next(0)),
+                _ => Pending::jump(BPF_JGT, value_low, Fail, Next(0)),
+            },
+        ],
+        Comparison::MaskedEqual => vec![
+            Pending::stmt(load(high)),
+            Pending::stmt(stmt(BPF_ALU | BPF_AND | BPF_K, value_high)),
+            Pending::jump(BPF_JEQ, datum_high, Next(0), Fail),
+            Pending::stmt(load(low)),
+            Pending::stmt(stmt(BPF_ALU | BPF_AND | BPF_K, value_low)),
+            Pending::jump(BPF_JEQ, datum_low, Next(0), Fail),
+        ],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::AUDIT_ARCH_AARCH64;
+    use super::super::AUDIT_ARCH_I386;
+    use super::super::AUDIT_ARCH_X86_64;
+    use super::super::SECCOMP_RET_ALLOW;
+
+    // Runs the filter against a seccomp_data built from the arguments,
+    // supporting only the instructions the compiler emits.
+    fn run(program: &[SockFilter], arch: u32, nr: u32, args: [u64; 6]) -> u32 {
+        let word = |offset: u32| -> u32 {
+            match offset {
+                NR_OFFSET => nr,
+                ARCH_OFFSET => arch,
+                _ => {
+                    let arg = args[((offset - ARGS_OFFSET) / 8) as usize];
+                    if (offset - ARGS_OFFSET) % 8 == LOW_WORD { arg as u32 } else { (arg >> 32) as u32 }
+                },
+            }
+        };
+        let mut accumulator = 0;
+        let mut pc = 0;
+        loop {
+            let insn = program[pc];
+            pc += 1;
+            match insn.code {
+                code if code == BPF_LD | BPF_W | BPF_ABS => accumulator = word(insn.k),
+                code if code == BPF_ALU | BPF_AND | BPF_K => accumulator &= insn.k,
+                code if code == BPF_RET | BPF_K => return insn.k,
+                code if code == BPF_JMP | BPF_JA => pc += insn.k as usize,
+                code => {
+                    let taken = match code & 0xf0 {
+                        BPF_JEQ => accumulator == insn.k,
+                        BPF_JGT => accumulator > insn.k,
+                        BPF_JGE => accumulator >= insn.k,
+                        _ => panic!("unexpected instruction {:?}", insn),
+                    };
+                    pc += usize::from(if taken { insn.jt } else { insn.jf });
+                },
+            }
+        }
+    }
+
+    fn names(list: &[&str]) -> Vec<String> {
+        list.iter().map(|name| name.to_string()).collect()
+    }
+
+    fn nr(arch: Arch, name: &str) -> u32 {
+        arch.syscall_nr(name).unwrap()
+    }
+
+    #[test]
+    fn compile_matches_syscalls_per_architecture() {
+        let eperm = Action::Errno(1).to_ret();
+        let rules = vec![Rule::new(&names(&["read", "write", "open"]), Action::Allow)];
+        let program = compile(Action::Errno(1), &[Arch::X86_64, Arch::X86, Arch::Aarch64], &rules).unwrap();
+
+        assert_eq!(run(&program, AUDIT_ARCH_X86_64, nr(Arch::X86_64, "write"), [0; 6]), SECCOMP_RET_ALLOW);
+        assert_eq!(run(&program, AUDIT_ARCH_X86_64, nr(Arch::X86_64, "close"), [0; 6]), eperm);
+        assert_eq!(run(&program, AUDIT_ARCH_I386, nr(Arch::X86, "open"), [0; 6]), SECCOMP_RET_ALLOW);
+        assert_eq!(run(&program, AUDIT_ARCH_I386, nr(Arch::X86_64, "open"), [0; 6]), eperm);
+        assert_eq!(run(&program, AUDIT_ARCH_AARCH64, nr(Arch::Aarch64, "read"), [0; 6]), SECCOMP_RET_ALLOW);
+    }
+
+    #[test]
+    fn compile_kills_unknown_architectures_and_x32_unless_enabled() {
+        let rules = vec![Rule::new(&names(&["read"]), Action::Allow)];
+        let native_only = compile(Action::Allow, &[Arch::X86_64], &rules).unwrap();
+        let with_x32 = compile(Action::Errno(1), &[Arch::X86_64, Arch::X32], &rules).unwrap();
+
+        assert_eq!(run(&native_only, AUDIT_ARCH_I386, 3, [0; 6]), SECCOMP_RET_KILL_PROCESS);
+        assert_eq!(run(&native_only, AUDIT_ARCH_X86_64, nr(Arch::X32, "read"), [0; 6]), SECCOMP_RET_KILL_PROCESS);
+        assert_eq!(run(&with_x32, AUDIT_ARCH_X86_64, nr(Arch::X32, "read"), [0; 6]), SECCOMP_RET_ALLOW);
+    }
+
+    #[test]
+    fn compile_handles_long_syscall_lists() {
+        let all: Vec<String> = super::super::syscalls::X86_64.iter().map(|(name, _)| name.to_string()).collect();
+        let rules = vec![Rule::new(&all, Action::Allow)];
+        let program = compile(Action::KillProcess, &[Arch::X86_64], &rules).unwrap();
+
+        for (_, nr) in super::super::syscalls::X86_64 {
+            assert_eq!(run(&program, AUDIT_ARCH_X86_64, *nr, [0; 6]), SECCOMP_RET_ALLOW);
+        }
+        assert_eq!(run(&program, AUDIT_ARCH_X86_64, 1000, [0; 6]), SECCOMP_RET_KILL_PROCESS);
+    }
+
+    #[test]
+    fn compile_compares_64_bit_arguments() {
+        let table = vec![
+            (Comparison::Equal, 0x1_0000_0005, vec![(0x1_0000_0005, true), (5, false), (0x1_0000_0006, false)]),
+            (Comparison::NotEqual, 0x1_0000_0005, vec![(0x1_0000_0005, false), (5, true), (0x2_0000_0005, true)]),
+            (Comparison::GreaterThan, 0x1_0000_0005, vec![(0x1_0000_0006, true), (0x2_0000_0000, true), (0x1_0000_0005, false), (0xffff_ffff, false)]),
+            (Comparison::GreaterOrEqual, 0x1_0000_0005, vec![(0x1_0000_0005, true), (0x1_0000_0004, false), (0x2_0000_0000, true)]),
+            (Comparison::LessThan, 0x1_0000_0005, vec![(0x1_0000_0004, true), (0xffff_ffff, true), (0x1_0000_0005, false), (0x2_0000_0000, false)]),
+            (Comparison::LessOrEqual, 0x1_0000_0005, vec![(0x1_0000_0005, true), (0x1_0000_0006, false), (3, true)]),
+        ];
+        for (comparison, value, cases) in table {
+            let mut rule = Rule::new(&names(&["personality"]), Action::Allow);
+            rule.add_condition(Condition::new(2, comparison, value, 0).unwrap());
+            let program = compile(Action::Errno(1), &[Arch::X86_64], &[rule]).unwrap();
+            for (arg, allowed) in cases {
+                let result = run(&program, AUDIT_ARCH_X86_64, nr(Arch::X86_64, "personality"), [0, 0, arg, 0, 0, 0]);
+                assert_eq!(result == SECCOMP_RET_ALLOW, allowed, "{:?} {:#x} with {:#x}", comparison, value, arg);
+            }
+        }
+    }
+
+    #[test]
+    fn compile_compares_masked_arguments() {
+        let mut rule = Rule::new(&names(&["clone"]), Action::Errno(1));
+        rule.add_condition(Condition::new(0, Comparison::MaskedEqual, 0x7e02_0000, 0).unwrap());
+        let program = compile(Action::Allow, &[Arch::X86_64], &[rule]).unwrap();
+        let clone = nr(Arch::X86_64, "clone");
+
+        assert_ne!(run(&program, AUDIT_ARCH_X86_64, clone, [0x0001_1200, 0, 0, 0, 0, 0]), SECCOMP_RET_ALLOW);
+        assert_eq!(run(&program, AUDIT_ARCH_X86_64, clone, [0x1000_0000, 0, 0, 0, 0, 0]), SECCOMP_RET_ALLOW);
+    }
+
+    #[test]
+    fn compile_treats_conditions_on_the_same_argument_as_alternatives() {
+        let mut rule = Rule::new(&names(&["personality"]), Action::Allow);
+        rule.add_condition(Condition::new(0, Comparison::Equal, 0, 0).unwrap());
+        rule.add_condition(Condition::new(0, Comparison::Equal, 8, 0).unwrap());
+        let mut both = Rule::new(&names(&["kill"]), Action::Allow);
+        both.add_condition(Condition::new(0, Comparison::Equal, 1, 0).unwrap());
+        both.add_condition(Condition::new(1, Comparison::Equal, 9, 0).unwrap());
+        let program = compile(Action::Errno(1), &[Arch::X86_64], &[rule, both]).unwrap();
+        let personality = nr(Arch::X86_64, "personality");
+        let kill = nr(Arch::X86_64, "kill");
+
+        assert_eq!(run(&program, AUDIT_ARCH_X86_64, personality, [8, 0, 0, 0, 0, 0]), SECCOMP_RET_ALLOW);
+        assert_ne!(run(&program, AUDIT_ARCH_X86_64, personality, [4, 0, 0, 0, 0, 0]), SECCOMP_RET_ALLOW);
+        assert_eq!(run(&program, AUDIT_ARCH_X86_64, kill, [1, 9, 0, 0, 0, 0]), SECCOMP_RET_ALLOW);
+        assert_ne!(run(&program, AUDIT_ARCH_X86_64, kill, [1, 15, 0, 0, 0, 0]), SECCOMP_RET_ALLOW);
+    }
+
+    #[test]
+    fn compile_only_compares_low_word_on_32_bit_architectures() {
+        let mut rule = Rule::new(&names(&["personality"]), Action::Allow);
+        rule.add_condition(Condition::new(0, Comparison::Equal, 0xffff_ffff_ffff_ffff, 0).unwrap());
+        let program = compile(Action::Errno(1), &[Arch::X86], &[rule]).unwrap();
+
+        assert_eq!(run(&program, AUDIT_ARCH_I386, nr(Arch::X86, "personality"), [0xffff_ffff, 0, 0, 0, 0, 0]), SECCOMP_RET_ALLOW);
+    }
+}
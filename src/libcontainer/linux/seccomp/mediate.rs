@@ -0,0 +1,246 @@
+use crate::Error;
+use super::notify::Notification;
+use super::notify::Response;
+use nix::sys::wait;
+use nix::sys::wait::WaitStatus;
+use nix::unistd;
+use nix::unistd::ForkResult;
+use std::ffi::CString;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::io::RawFd;
+use failure::ResultExt;
+
+// Mounts that take no new filesystem but rearrange existing ones.
+const MS_NOT_NEW: libc::c_ulong = libc::MS_BIND | libc::MS_MOVE | libc::MS_REMOUNT
+    | libc::MS_SHARED | libc::MS_PRIVATE | libc::MS_SLAVE | libc::MS_UNBINDABLE;
+
+// The arguments of the syscalls the agent carries out itself once a policy
+// allows them. Continuing them would run them with the privileges of the
+// caller, which is what the filter is there to make up for, and would let
+// the caller change pointed to arguments after they were checked.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Call {
+    Mknod { dirfd: Option<RawFd>, path: CString, mode: libc::mode_t, dev: u32 },
+    Mount { source: Option<CString>, target: CString, fs_type: Option<CString>, flags: libc::c_ulong, data: Option<CString> },
+    Other,
+}
+
+// A notified syscall as a policy sees it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Syscall {
+    name: String,
+    call: Call,
+}
+
+// The credentials the process makes files with.
+struct Owner {
+    uid: libc::uid_t,
+    gid: libc::gid_t,
+    umask: libc::mode_t,
+}
+
+impl Syscall {
+    pub(super) fn new(name: &str, call: Call) -> Syscall {
+        Syscall { name: name.to_string(), call }
+    }
+
+    pub fn read(notification: &Notification, notify_fd: RawFd) -> Result<Syscall, Error> {
+        let name = notification.syscall_name().unwrap_or("unknown");
+        let args = notification.args();
+        let string = |address: u64| notification.read_string(notify_fd, address);
+        let optional_string = |address: u64| match address {
+            0 => Ok(None),
+            _ => notification.read_string(notify_fd, address).map(Some),
+        };
+        let call = match name {
+            "mknod" => Call::Mknod { dirfd: None, path: string(args[0])?, mode: args[1] as libc::mode_t, dev: args[2] as u32 },
+            "mknodat" => {
+                let dirfd = match args[0] as i32 {
+                    libc::AT_FDCWD => None,
+                    fd => Some(fd),
+                };
+                Call::Mknod { dirfd, path: string(args[1])?, mode: args[2] as libc::mode_t, dev: args[3] as u32 }
+            },
+            "mount" => Call::Mount {
+                source: optional_string(args[0])?,
+                target: string(args[1])?,
+                fs_type: optional_string(args[2])?,
+                flags: args[3] as libc::c_ulong,
+                data: optional_string(args[4])?,
+            },
+            _ => Call::Other,
+        };
+        Ok(Syscall::new(name, call))
+    }
+
+    pub fn name(&self) -> &str { &self.name }
+
+    // The type, major and minor of the node mknod makes, for devices and
+    // fifos only.
+    pub fn device(&self) -> Option<(&'static str, u32, u32)> {
+        match &self.call {
+            Call::Mknod { mode, dev, .. } => {
+                let device_type = match mode & libc::S_IFMT {
+                    libc::S_IFCHR => "c",
+                    libc::S_IFBLK => "b",
+                    libc::S_IFIFO => "p",
+                    _ => return None,
+                };
+                // The kernel encoding of dev_t in the syscall.
+                let major = (dev & 0xfff00) >> 8;
+                let minor = (dev & 0xff) | ((dev >> 12) & 0xfff00);
+                Some((device_type, major, minor))
+            },
+            _ => None,
+        }
+    }
+
+    // The filesystem a mount creates, none for bind mounts, moves, remounts
+    // and propagation changes.
+    pub fn fs_type(&self) -> Option<&str> {
+        match &self.call {
+            Call::Mount { fs_type: Some(fs_type), flags, .. } if flags & MS_NOT_NEW == 0 => fs_type.to_str().ok(),
+            _ => None,
+        }
+    }
+
+    // Carries out an allowed syscall in the mount namespace, root and
+    // working directory of the process, with its umask and as its owner.
+    // Other syscalls are continued and run with the privileges of the
+    // process, so allowing them grants nothing it could not do already.
+    pub fn perform(&self, notification: &Notification, notify_fd: RawFd) -> Result<Response, Error> {
+        let dirfd = match &self.call {
+            Call::Mknod { dirfd, .. } => *dirfd,
+            Call::Mount { .. } => None,
+            Call::Other => return Ok(Response::Continue),
+        };
+        let pid = notification.pid();
+        let open = |path: String| File::open(&path).context(format!("cannot open {}", path));
+        let mount_ns = open(format!("/proc/{}/ns/mnt", pid))?;
+        let root = open(format!("/proc/{}/root", pid))?;
+        let cwd = match dirfd {
+            Some(fd) => open(format!("/proc/{}/fd/{}", pid, fd))?,
+            None => open(format!("/proc/{}/cwd", pid))?,
+        };
+        let owner = read_owner(pid)?;
+        // What was opened belongs to the process only if it is still waiting.
+        if !notification.is_valid(notify_fd) {
+            Err(Error::from(format!("process {} is gone", pid)))?;
+        }
+
+        match unistd::fork().context("cannot fork to carry out syscall".to_string())? {
+            ForkResult::Child => {
+                let errno = self.call_in(mount_ns.as_raw_fd(), root.as_raw_fd(), cwd.as_raw_fd(), &owner);
+                unsafe { libc::_exit(errno) }
+            },
+            ForkResult::Parent { child } => match wait::waitpid(child, None).context(format!("cannot wait for {} of process {}", self.name, pid))? {
+                WaitStatus::Exited(_, 0) => Ok(Response::Return(0)),
+                WaitStatus::Exited(_, errno) => Ok(Response::Errno(errno)),
+                status => Err(Error::from(format!("cannot carry out {} of process {}: {:?}", self.name, pid, status)))?,
+            },
+        }
+    }
+
+    // Runs in a forked child of a threaded agent, so only plain syscalls.
+    // Returns the errno, or 0 on success.
+    fn call_in(&self, mount_ns: RawFd, root: RawFd, cwd: RawFd, owner: &Owner) -> i32 {
+        let entered = unsafe {
+            libc::setns(mount_ns, libc::CLONE_NEWNS) == 0
+                && libc::fchdir(root) == 0
+                && libc::chroot(b".\0".as_ptr() as *const libc::c_char) == 0
+                && libc::fchdir(cwd) == 0
+        };
+        if !entered {
+            return last_errno();
+        }
+
+        let result = match &self.call {
+            Call::Mknod { path, mode, dev, .. } => unsafe {
+                libc::umask(owner.umask);
+                match libc::mknod(path.as_ptr(), *mode, libc::dev_t::from(*dev)) {
+                    0 => libc::fchownat(libc::AT_FDCWD, path.as_ptr(), owner.uid, owner.gid, libc::AT_SYMLINK_NOFOLLOW),
+                    result => result,
+                }
+            },
+            Call::Mount { source, target, fs_type, flags, data } => unsafe {
+                libc::mount(
+                    source.as_ref().map_or(std::ptr::null(), |source| source.as_ptr()),
+                    target.as_ptr(),
+                    fs_type.as_ref().map_or(std::ptr::null(), |fs_type| fs_type.as_ptr()),
+                    *flags,
+                    data.as_ref().map_or(std::ptr::null(), |data| data.as_ptr() as *const libc::c_void),
+                )
+            },
+            Call::Other => return libc::ENOSYS,
+        };
+        match result {
+            0 => 0,
+            _ => last_errno(),
+        }
+    }
+}
+
+fn last_errno() -> i32 {
+    io::Error::last_os_error().raw_os_error().unwrap_or(libc::EPERM)
+}
+
+// Files are made with the filesystem ids of the process, the last of the
+// Uid and Gid fields of its status.
+fn read_owner(pid: u32) -> Result<Owner, Error> {
+    let path = format!("/proc/{}/status", pid);
+    let status = fs::read_to_string(&path).context(format!("cannot read {}", path))?;
+    let field = |name: &str, index: usize, radix: u32| status.lines()
+        .find_map(|line| line.strip_prefix(name))
+        .and_then(|values| values.split_whitespace().nth(index))
+        .and_then(|value| u32::from_str_radix(value, radix).ok());
+
+    match (field("Uid:", 3, 10), field("Gid:", 3, 10), field("Umask:", 0, 8)) {
+        (Some(uid), Some(gid), Some(umask)) => Ok(Owner { uid, gid, umask: umask as libc::mode_t }),
+        _ => Err(Error::from("cannot parse process credentials".to_string())).context(path)?,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mount(fs_type: &str, flags: libc::c_ulong) -> Syscall {
+        let call = Call::Mount {
+            source: Some(CString::new("none").unwrap()),
+            target: CString::new("/mnt").unwrap(),
+            fs_type: Some(CString::new(fs_type).unwrap()),
+            flags,
+            data: None,
+        };
+        Syscall::new("mount", call)
+    }
+
+    #[test]
+    fn syscall_device_decodes_type_major_and_minor() {
+        let mknod = |mode: libc::mode_t, dev: u32| Syscall::new("mknod", Call::Mknod { dirfd: None, path: CString::new("dev").unwrap(), mode, dev });
+
+        assert_eq!(mknod(libc::S_IFCHR | 0o666, (1 << 8) | 3).device(), Some(("c", 1, 3)));
+        assert_eq!(mknod(libc::S_IFBLK | 0o600, (259 << 8) | (300 & 0xff) | ((300 & 0xfff00) << 12)).device(), Some(("b", 259, 300)));
+        assert_eq!(mknod(libc::S_IFIFO | 0o600, 0).device(), Some(("p", 0, 0)));
+        assert_eq!(mknod(libc::S_IFREG | 0o600, 0).device(), None);
+    }
+
+    #[test]
+    fn syscall_fs_type_is_only_set_for_new_mounts() {
+        assert_eq!(mount("tmpfs", libc::MS_NOSUID).fs_type(), Some("tmpfs"));
+        assert_eq!(mount("tmpfs", libc::MS_BIND | libc::MS_REC).fs_type(), None);
+        assert_eq!(mount("tmpfs", libc::MS_REMOUNT).fs_type(), None);
+        assert_eq!(Syscall::new("mkdir", Call::Other).fs_type(), None);
+    }
+
+    #[test]
+    fn read_owner_parses_process_status() {
+        let owner = read_owner(std::process::id()).unwrap();
+
+        assert_eq!((owner.uid, owner.gid), (unistd::geteuid().as_raw(), unistd::getegid().as_raw()));
+        assert_eq!(owner.umask & !0o777, 0);
+    }
+}
@@ -0,0 +1,485 @@
+pub mod bpf;
+pub mod agent;
+pub mod mediate;
+pub mod notify;
+pub mod record;
+mod syscalls;
+
+use crate::Error;
+use self::notify::Listener;
+use serde::Deserialize;
+use serde::Serialize;
+use std::io;
+use std::os::unix::io::RawFd;
+use std::str::FromStr;
+use failure::ResultExt;
+
+const SECCOMP_SET_MODE_FILTER: libc::c_uint = 1;
+const SECCOMP_FILTER_FLAG_TSYNC: u32 = 1;
+const SECCOMP_FILTER_FLAG_NEW_LISTENER: u32 = 8;
+
+const SECCOMP_RET_KILL_PROCESS: u32 = 0x8000_0000;
+const SECCOMP_RET_KILL_THREAD: u32 = 0x0000_0000;
+const SECCOMP_RET_TRAP: u32 = 0x0003_0000;
+const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+const SECCOMP_RET_USER_NOTIF: u32 = 0x7fc0_0000;
+const SECCOMP_RET_TRACE: u32 = 0x7ff0_0000;
+const SECCOMP_RET_LOG: u32 = 0x7ffc_0000;
+const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+const SECCOMP_RET_DATA: u32 = 0x0000_ffff;
+
+const AUDIT_ARCH_X86_64: u32 = 0xc000_003e;
+const AUDIT_ARCH_I386: u32 = 0x4000_0003;
+const AUDIT_ARCH_AARCH64: u32 = 0xc000_00b7;
+
+const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+// The init process passes the notify fd to the runtime with sendmsg after
+// the filter is in place, so that syscall must not wait for an agent.
+const RUNTIME_SYSCALLS: &[&str] = &["sendmsg"];
+
+const FILTER_FLAGS: &[(&str, u32)] = &[
+    ("SECCOMP_FILTER_FLAG_TSYNC", SECCOMP_FILTER_FLAG_TSYNC),
+    ("SECCOMP_FILTER_FLAG_LOG", 2),
+    ("SECCOMP_FILTER_FLAG_SPEC_ALLOW", 4),
+];
+
+// Architectures that are valid in a profile but can never run on the
+// architectures we build for, so they are accepted and left out.
+const FOREIGN_ARCHITECTURES: &[&str] = &[
+    "SCMP_ARCH_ARM",
+    "SCMP_ARCH_MIPS",
+    "SCMP_ARCH_MIPS64",
+    "SCMP_ARCH_MIPS64N32",
+    "SCMP_ARCH_MIPSEL",
+    "SCMP_ARCH_MIPSEL64",
+    "SCMP_ARCH_MIPSEL64N32",
+    "SCMP_ARCH_PPC",
+    "SCMP_ARCH_PPC64",
+    "SCMP_ARCH_PPC64LE",
+    "SCMP_ARCH_S390",
+    "SCMP_ARCH_S390X",
+    "SCMP_ARCH_PARISC",
+    "SCMP_ARCH_PARISC64",
+    "SCMP_ARCH_RISCV64",
+    "SCMP_ARCH_LOONGARCH64",
+];
+
+// Which profile a container runs with: the one in its config, the built-in
+// default when the config has none, no filter at all, or a recording filter
+// whose syscalls are written as a profile to the given path.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum Mode {
+    #[default]
+    Config,
+    Default,
+    Unconfined,
+    Record(String),
+}
+
+impl Mode {
+    pub fn is_config(&self) -> bool {
+        *self == Mode::Config
+    }
+}
+
+impl FromStr for Mode {
+    type Err = Error;
+
+    fn from_str(original: &str) -> Result<Mode, Error> {
+        match original {
+            "default" => Ok(Mode::Default),
+            "unconfined" => Ok(Mode::Unconfined),
+            _ => Err(Error::from("invalid seccomp mode".to_string())).context(original.to_string())?,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Action {
+    KillProcess,
+    KillThread,
+    Trap,
+    Errno(u32),
+    Trace(u32),
+    Allow,
+    Log,
+    Notify,
+}
+
+impl Action {
+    // Like runc, errno and trace default to EPERM when no errnoRet is set.
+    pub fn new(name: &str, errno_ret: Option<u32>) -> Result<Action, Error> {
+        let errno_ret = errno_ret.unwrap_or(libc::EPERM as u32);
+        match name {
+            "SCMP_ACT_KILL" | "SCMP_ACT_KILL_THREAD" => Ok(Action::KillThread),
+            "SCMP_ACT_KILL_PROCESS" => Ok(Action::KillProcess),
+            "SCMP_ACT_TRAP" => Ok(Action::Trap),
+            "SCMP_ACT_ERRNO" => Ok(Action::Errno(errno_ret)),
+            "SCMP_ACT_TRACE" => Ok(Action::Trace(errno_ret)),
+            "SCMP_ACT_ALLOW" => Ok(Action::Allow),
+            "SCMP_ACT_LOG" => Ok(Action::Log),
+            "SCMP_ACT_NOTIFY" => Ok(Action::Notify),
+            _ => Err(Error::from("invalid seccomp action".to_string())).context(name.to_string())?,
+        }
+    }
+
+    fn to_ret(self) -> u32 {
+        match self {
+            Action::KillProcess => SECCOMP_RET_KILL_PROCESS,
+            Action::KillThread => SECCOMP_RET_KILL_THREAD,
+            Action::Trap => SECCOMP_RET_TRAP,
+            Action::Errno(errno) => SECCOMP_RET_ERRNO | (errno & SECCOMP_RET_DATA),
+            Action::Trace(data) => SECCOMP_RET_TRACE | (data & SECCOMP_RET_DATA),
+            Action::Allow => SECCOMP_RET_ALLOW,
+            Action::Log => SECCOMP_RET_LOG,
+            Action::Notify => SECCOMP_RET_USER_NOTIF,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Arch {
+    X86_64,
+    X86,
+    X32,
+    Aarch64,
+}
+
+impl FromStr for Arch {
+    type Err = Error;
+
+    fn from_str(original: &str) -> Result<Arch, Error> {
+        match original {
+            "SCMP_ARCH_X86_64" => Ok(Arch::X86_64),
+            "SCMP_ARCH_X86" => Ok(Arch::X86),
+            "SCMP_ARCH_X32" => Ok(Arch::X32),
+            "SCMP_ARCH_AARCH64" => Ok(Arch::Aarch64),
+            _ => Err(Error::from("invalid seccomp architecture".to_string())).context(original.to_string())?,
+        }
+    }
+}
+
+impl Arch {
+    pub fn name(self) -> &'static str {
+        match self {
+            Arch::X86_64 => "SCMP_ARCH_X86_64",
+            Arch::X86 => "SCMP_ARCH_X86",
+            Arch::X32 => "SCMP_ARCH_X32",
+            Arch::Aarch64 => "SCMP_ARCH_AARCH64",
+        }
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    pub fn native() -> Arch {
+        Arch::X86_64
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    pub fn native() -> Arch {
+        Arch::Aarch64
+    }
+
+    // x32 shares the x86_64 audit arch, its syscalls are told apart by
+    // the x32 bit in the syscall number.
+    fn audit_arch(self) -> u32 {
+        match self {
+            Arch::X86_64 | Arch::X32 => AUDIT_ARCH_X86_64,
+            Arch::X86 => AUDIT_ARCH_I386,
+            Arch::Aarch64 => AUDIT_ARCH_AARCH64,
+        }
+    }
+
+    // Syscall arguments of 32 bit architectures only use the low word.
+    fn is_32bit(self) -> bool {
+        self == Arch::X86
+    }
+
+    pub fn syscall_nr(self, name: &str) -> Option<u32> {
+        self.table().iter().find(|(syscall, _)| *syscall == name).map(|(_, nr)| *nr)
+    }
+
+    pub fn syscall_name(self, nr: u32) -> Option<&'static str> {
+        self.table().iter().find(|(_, number)| *number == nr).map(|(name, _)| *name)
+    }
+
+    fn table(self) -> &'static [(&'static str, u32)] {
+        match self {
+            Arch::X86_64 => syscalls::X86_64,
+            Arch::X86 => syscalls::X86,
+            Arch::X32 => syscalls::X32,
+            Arch::Aarch64 => syscalls::AARCH64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Comparison {
+    NotEqual,
+    LessThan,
+    LessOrEqual,
+    Equal,
+    GreaterOrEqual,
+    GreaterThan,
+    MaskedEqual,
+}
+
+impl FromStr for Comparison {
+    type Err = Error;
+
+    fn from_str(original: &str) -> Result<Comparison, Error> {
+        match original {
+            "SCMP_CMP_NE" => Ok(Comparison::NotEqual),
+            "SCMP_CMP_LT" => Ok(Comparison::LessThan),
+            "SCMP_CMP_LE" => Ok(Comparison::LessOrEqual),
+            "SCMP_CMP_EQ" => Ok(Comparison::Equal),
+            "SCMP_CMP_GE" => Ok(Comparison::GreaterOrEqual),
+            "SCMP_CMP_GT" => Ok(Comparison::GreaterThan),
+            "SCMP_CMP_MASKED_EQ" => Ok(Comparison::MaskedEqual),
+            _ => Err(Error::from("invalid seccomp comparison".to_string())).context(original.to_string())?,
+        }
+    }
+}
+
+// For SCMP_CMP_MASKED_EQ `value` is the mask and `value_two` the datum the
+// masked argument is compared with.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Condition {
+    index: u32,
+    comparison: Comparison,
+    value: u64,
+    value_two: u64,
+}
+
+impl Condition {
+    pub fn new(index: u32, comparison: Comparison, value: u64, value_two: u64) -> Result<Condition, Error> {
+        if index > 5 {
+            Err(Error::from("seccomp argument index must be between 0 and 5".to_string())).context(index.to_string())?;
+        }
+        Ok(Condition { index, comparison, value, value_two })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    names: Vec<String>,
+    action: Action,
+    conditions: Vec<Condition>,
+}
+
+impl Rule {
+    pub fn new(names: &[String], action: Action) -> Rule {
+        Rule {
+            names: names.to_vec(),
+            action,
+            conditions: Vec::new(),
+        }
+    }
+
+    pub fn add_condition(&mut self, condition: Condition) {
+        self.conditions.push(condition);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Seccomp {
+    default_action: Action,
+    architectures: Vec<Arch>,
+    flags: u32,
+    rules: Vec<Rule>,
+    listener: Option<Listener>,
+}
+
+impl Seccomp {
+    // The native architecture is always filtered, as libseccomp does.
+    pub fn new(default_action: Action) -> Seccomp {
+        Seccomp {
+            default_action,
+            architectures: vec![Arch::native()],
+            flags: 0,
+            rules: Vec::new(),
+            listener: None,
+        }
+    }
+
+    pub fn add_architecture(&mut self, name: &str) -> Result<(), Error> {
+        if FOREIGN_ARCHITECTURES.contains(&name) {
+            return Ok(());
+        }
+        let arch = Arch::from_str(name)?;
+        if !self.architectures.contains(&arch) {
+            self.architectures.push(arch);
+        }
+        Ok(())
+    }
+
+    pub fn add_flag(&mut self, name: &str) -> Result<(), Error> {
+        match FILTER_FLAGS.iter().find(|(flag, _)| *flag == name) {
+            Some((_, value)) => self.flags |= value,
+            None => Err(Error::from("invalid seccomp flag".to_string())).context(name.to_string())?,
+        }
+        Ok(())
+    }
+
+    pub fn add_rule(&mut self, rule: Rule) {
+        self.rules.push(rule);
+    }
+
+    pub fn set_listener(&mut self, listener: Listener) {
+        self.listener = Some(listener);
+    }
+
+    // Only set when some rule notifies, since nobody would get the fd
+    // otherwise.
+    pub fn listener(&self) -> Option<&Listener> {
+        match self.notifies() {
+            true => self.listener.as_ref(),
+            false => None,
+        }
+    }
+
+    fn notifies(&self) -> bool {
+        self.default_action == Action::Notify || self.rules.iter().any(|rule| rule.action == Action::Notify)
+    }
+
+    fn allows(&self, name: &str) -> bool {
+        self.rules.iter().any(|rule| rule.action == Action::Allow && rule.conditions.is_empty() && rule.names.iter().any(|n| n == name))
+    }
+
+    pub fn compile(&self) -> Result<Vec<bpf::SockFilter>, Error> {
+        if self.default_action == Action::Notify {
+            if let Some(name) = RUNTIME_SYSCALLS.iter().find(|name| !self.allows(name)) {
+                Err(Error::from("SCMP_ACT_NOTIFY as default seccomp action requires allowing the syscalls the runtime needs".to_string())).context(name.to_string())?;
+            }
+        }
+        if self.notifies() && self.listener.is_none() {
+            Err(Error::from("seccomp listenerPath is required by SCMP_ACT_NOTIFY".to_string()))?;
+        }
+        // The kernel refuses a listener on a filter synced to all threads.
+        if self.notifies() && self.flags & SECCOMP_FILTER_FLAG_TSYNC != 0 {
+            Err(Error::from("seccomp flag SECCOMP_FILTER_FLAG_TSYNC cannot be used with SCMP_ACT_NOTIFY".to_string()))?;
+        }
+        for rule in self.rules.iter().filter(|rule| rule.action == Action::Notify) {
+            if let Some(name) = rule.names.iter().find(|name| RUNTIME_SYSCALLS.contains(&name.as_str())) {
+                Err(Error::from("SCMP_ACT_NOTIFY cannot be used for a syscall the runtime needs".to_string())).context(name.to_string())?;
+            }
+        }
+        bpf::compile(self.default_action, &self.architectures, &self.rules)
+    }
+
+    // Without no_new_privileges the kernel only accepts filters from
+    // processes with CAP_SYS_ADMIN. Filters with notify rules return the
+    // fd the agent receives notifications from.
+    pub fn install(&self) -> Result<Option<RawFd>, Error> {
+        let filter = self.compile()?;
+        let flags = match self.notifies() {
+            true => self.flags | SECCOMP_FILTER_FLAG_NEW_LISTENER,
+            false => self.flags,
+        };
+        let program = bpf::SockFprog {
+            len: filter.len() as libc::c_ushort,
+            filter: filter.as_ptr(),
+        };
+        let result = unsafe {
+            libc::syscall(libc::SYS_seccomp, SECCOMP_SET_MODE_FILTER, flags, &program as *const bpf::SockFprog)
+        };
+        if result < 0 {
+            let err = io::Error::last_os_error();
+            let message = match err.raw_os_error() {
+                Some(libc::EACCES) => "cannot install seccomp filter without noNewPrivileges or CAP_SYS_ADMIN",
+                _ => "cannot install seccomp filter",
+            };
+            Err(err).context(message.to_string())?;
+        }
+        match self.notifies() {
+            true => Ok(Some(result as RawFd)),
+            false => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nix::errno::Errno;
+    use nix::sys::wait;
+    use nix::sys::wait::WaitStatus;
+    use nix::unistd;
+    use nix::unistd::ForkResult;
+    use std::process;
+
+    #[test]
+    fn action_new_uses_errno_ret() {
+        assert_eq!(Action::new("SCMP_ACT_ERRNO", Some(38)).unwrap().to_ret(), 0x0005_0026);
+        assert_eq!(Action::new("SCMP_ACT_ERRNO", None).unwrap(), Action::Errno(1));
+        assert_eq!(Action::new("SCMP_ACT_KILL", None).unwrap(), Action::KillThread);
+        assert!(Action::new("SCMP_ACT_PANIC", None).is_err());
+    }
+
+    #[test]
+    fn arch_syscall_nr_uses_arch_table() {
+        assert_eq!(Arch::X86_64.syscall_nr("getcwd"), Some(79));
+        assert_eq!(Arch::X86.syscall_nr("getcwd"), Some(183));
+        assert_eq!(Arch::X32.syscall_nr("getcwd"), Some(0x4000_0000 + 79));
+        assert_eq!(Arch::Aarch64.syscall_nr("getcwd"), Some(17));
+        assert_eq!(Arch::Aarch64.syscall_nr("open"), None);
+    }
+
+    #[test]
+    fn seccomp_add_architecture_skips_foreign_architectures() {
+        let mut seccomp = Seccomp::new(Action::Allow);
+
+        seccomp.add_architecture("SCMP_ARCH_X86").unwrap();
+        seccomp.add_architecture("SCMP_ARCH_PPC64LE").unwrap();
+        seccomp.add_architecture("SCMP_ARCH_X86").unwrap();
+
+        assert_eq!(seccomp.architectures, vec![Arch::native(), Arch::X86]);
+        assert!(seccomp.add_architecture("SCMP_ARCH_VAX").is_err());
+    }
+
+    #[test]
+    fn condition_new_returns_error_with_invalid_index() {
+        let result = Condition::new(6, Comparison::Equal, 0, 0);
+        assert!(result.is_err(), "expect {:?} to be err", result);
+    }
+
+    #[test]
+    fn seccomp_compile_requires_runtime_syscalls_with_notify_default() {
+        let mut seccomp = Seccomp::new(Action::Notify);
+        seccomp.set_listener(Listener::new("/run/agent.sock", None));
+        assert!(seccomp.compile().is_err());
+
+        seccomp.add_rule(Rule::new(&["sendmsg".to_string()], Action::Allow));
+        assert!(seccomp.compile().is_ok());
+    }
+
+    #[test]
+    fn seccomp_compile_rejects_tsync_with_notify() {
+        let mut seccomp = Seccomp::new(Action::Allow);
+        seccomp.add_flag("SECCOMP_FILTER_FLAG_TSYNC").unwrap();
+        assert!(seccomp.compile().is_ok());
+
+        seccomp.add_rule(Rule::new(&["mount".to_string()], Action::Notify));
+        seccomp.set_listener(Listener::new("/run/agent.sock", None));
+        assert!(seccomp.compile().is_err());
+    }
+
+    #[test]
+    fn seccomp_install_applies_rules_to_the_process() {
+        let mut seccomp = Seccomp::new(Action::Allow);
+        seccomp.add_rule(Rule::new(&["getcwd".to_string()], Action::Errno(libc::ENOTTY as u32)));
+
+        let status = match unistd::fork().unwrap() {
+            ForkResult::Child => {
+                unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) };
+                let denied = seccomp.install().is_ok()
+                    && unistd::getcwd().unwrap_err().as_errno() == Some(Errno::ENOTTY)
+                    && unistd::getpid().as_raw() > 0;
+                process::exit(if denied { 0 } else { 1 });
+            },
+            ForkResult::Parent { child } => wait::waitpid(child, None).unwrap(),
+        };
+
+        assert!(status == WaitStatus::Exited(status.pid().unwrap(), 0), "expect child to exit with 0, got {:?}", status);
+    }
+}
@@ -0,0 +1,309 @@
+use crate::Error;
+use super::Arch;
+use super::AUDIT_ARCH_AARCH64;
+use super::AUDIT_ARCH_I386;
+use super::AUDIT_ARCH_X86_64;
+use super::X32_SYSCALL_BIT;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::json;
+use serde_json::Value;
+use nix::poll;
+use nix::poll::EventFlags;
+use nix::poll::PollFd;
+use nix::sys::socket;
+use nix::sys::socket::CmsgSpace;
+use nix::sys::socket::ControlMessage;
+use nix::sys::socket::MsgFlags;
+use nix::sys::uio::IoVec;
+use std::ffi::CString;
+use std::fs::File;
+use std::io;
+use std::os::unix::fs::FileExt;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::io::RawFd;
+use std::os::unix::net::UnixStream;
+use failure::ResultExt;
+
+const SECCOMP_IOCTL_NOTIF_RECV: libc::c_ulong = 0xc050_2100;
+const SECCOMP_IOCTL_NOTIF_SEND: libc::c_ulong = 0xc018_2101;
+const SECCOMP_IOCTL_NOTIF_ID_VALID: libc::c_ulong = 0x4008_2102;
+const SECCOMP_USER_NOTIF_FLAG_CONTINUE: u32 = 1;
+
+const MAX_MESSAGE_SIZE: usize = 64 * 1024;
+const MAX_STRING_SIZE: usize = libc::PATH_MAX as usize;
+const PAGE_SIZE: u64 = 4096;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+struct SeccompData {
+    nr: i32,
+    arch: u32,
+    instruction_pointer: u64,
+    args: [u64; 6],
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+struct SeccompNotif {
+    id: u64,
+    pid: u32,
+    flags: u32,
+    data: SeccompData,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+struct SeccompNotifResp {
+    id: u64,
+    val: i64,
+    error: i32,
+    flags: u32,
+}
+
+// Where the runtime sends the notify fd of a container, together with
+// the container process state.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Listener {
+    path: String,
+    metadata: Option<String>,
+}
+
+impl Listener {
+    pub fn new(path: &str, metadata: Option<&str>) -> Listener {
+        Listener {
+            path: path.to_string(),
+            metadata: metadata.map(String::from),
+        }
+    }
+
+    pub fn send(&self, notify_fd: RawFd, pid: i32, state: &str) -> Result<(), Error> {
+        let state: Value = serde_json::from_str(state).context("invalid container state".to_string())?;
+        let mut process_state = json!({
+            "ociVersion": state["ociVersion"],
+            "fds": ["seccompFd"],
+            "pid": pid,
+            "state": state,
+        });
+        if let Some(metadata) = &self.metadata {
+            process_state["metadata"] = json!(metadata);
+        }
+
+        let stream = UnixStream::connect(&self.path).context(format!("cannot connect to seccomp listener {}", self.path))?;
+        send_fd(stream.as_raw_fd(), notify_fd, process_state.to_string().as_bytes())
+            .context(format!("cannot send seccomp fd to {}", self.path))?;
+        Ok(())
+    }
+}
+
+pub fn send_fd(socket: RawFd, fd: RawFd, message: &[u8]) -> Result<(), Error> {
+    let fds = [fd];
+    socket::sendmsg(socket, &[IoVec::from_slice(message)], &[ControlMessage::ScmRights(&fds)], MsgFlags::empty(), None)?;
+    Ok(())
+}
+
+// Returns the message and the fd sent along with it, if any. Both are empty
+// once the other end is closed.
+pub fn receive_fd(socket: RawFd) -> Result<(Vec<u8>, Option<RawFd>), Error> {
+    let mut buffer = vec![0; MAX_MESSAGE_SIZE];
+    let mut cmsg_space: CmsgSpace<[RawFd; 1]> = CmsgSpace::new();
+    let message = socket::recvmsg(socket, &[IoVec::from_mut_slice(&mut buffer)], Some(&mut cmsg_space), MsgFlags::MSG_CMSG_CLOEXEC)?;
+
+    let mut fd = None;
+    for cmsg in message.cmsgs() {
+        if let ControlMessage::ScmRights(fds) = cmsg {
+            fd = fds.first().cloned();
+        }
+    }
+    buffer.truncate(message.bytes);
+    Ok((buffer, fd))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Notification {
+    id: u64,
+    pid: u32,
+    arch: u32,
+    nr: i32,
+    args: [u64; 6],
+}
+
+impl Notification {
+    pub fn pid(&self) -> u32 { self.pid }
+    pub fn args(&self) -> &[u64; 6] { &self.args }
+
+    pub fn arch(&self) -> Option<Arch> {
+        arch(self.arch, self.nr as u32)
+    }
+
+    pub fn syscall_name(&self) -> Option<&'static str> {
+        syscall_name(self.arch, self.nr as u32)
+    }
+
+    // Waits for the next notification. Returns None once every process
+    // using the filter is gone.
+    pub fn receive(notify_fd: RawFd) -> Result<Option<Notification>, Error> {
+        loop {
+            let mut fds = [PollFd::new(notify_fd, EventFlags::POLLIN)];
+            poll::poll(&mut fds, -1).context("cannot wait for seccomp notifications".to_string())?;
+            let revents = fds[0].revents().unwrap_or_else(EventFlags::empty);
+            if revents.contains(EventFlags::POLLIN) {
+                break;
+            }
+            if revents.intersects(EventFlags::POLLHUP | EventFlags::POLLERR | EventFlags::POLLNVAL) {
+                return Ok(None);
+            }
+        }
+
+        let mut notif = SeccompNotif::default();
+        if unsafe { libc::ioctl(notify_fd, SECCOMP_IOCTL_NOTIF_RECV, &mut notif as *mut SeccompNotif) } != 0 {
+            let err = io::Error::last_os_error();
+            // The process was killed between the poll and the ioctl.
+            if err.raw_os_error() == Some(libc::ENOENT) {
+                return Notification::receive(notify_fd);
+            }
+            Err(err).context("cannot receive seccomp notification".to_string())?;
+        }
+
+        Ok(Some(Notification {
+            id: notif.id,
+            pid: notif.pid,
+            arch: notif.data.arch,
+            nr: notif.data.nr,
+            args: notif.data.args,
+        }))
+    }
+
+    // Only while the notification is pending is the pid sure to be the
+    // process that made the syscall, and not one that reused it.
+    pub fn is_valid(&self, notify_fd: RawFd) -> bool {
+        let mut id = self.id;
+        unsafe { libc::ioctl(notify_fd, SECCOMP_IOCTL_NOTIF_ID_VALID, &mut id as *mut u64) == 0 }
+    }
+
+    // Reads a string the process passed by pointer. It is read once, the
+    // process can change its memory afterwards but what was read is what
+    // the agent checks and uses.
+    pub fn read_string(&self, notify_fd: RawFd, address: u64) -> Result<CString, Error> {
+        let mem = File::open(format!("/proc/{}/mem", self.pid)).context(format!("cannot open memory of process {}", self.pid))?;
+        let mut string = Vec::new();
+        let mut offset = address;
+        loop {
+            // Reads stop at page boundaries, the next page may not be mapped.
+            let mut buffer = vec![0; (PAGE_SIZE - offset % PAGE_SIZE) as usize];
+            let count = mem.read_at(&mut buffer, offset).context(format!("cannot read memory of process {} at {:#x}", self.pid, address))?;
+            buffer.truncate(count);
+            if let Some(end) = buffer.iter().position(|byte| *byte == 0) {
+                string.extend_from_slice(&buffer[..end]);
+                break;
+            }
+            string.extend_from_slice(&buffer);
+            if count == 0 || string.len() >= MAX_STRING_SIZE {
+                Err(Error::from("syscall string argument is not terminated".to_string())).context(format!("{:#x}", address))?;
+            }
+            offset += count as u64;
+        }
+        if !self.is_valid(notify_fd) {
+            Err(Error::from(format!("process {} is gone", self.pid)))?;
+        }
+        Ok(CString::new(string).expect("string read up to its terminator"))
+    }
+
+    pub fn respond(&self, notify_fd: RawFd, response: Response) -> Result<(), Error> {
+        let mut resp = SeccompNotifResp { id: self.id, ..Default::default() };
+        match response {
+            Response::Continue => resp.flags = SECCOMP_USER_NOTIF_FLAG_CONTINUE,
+            Response::Errno(errno) => resp.error = -errno,
+            Response::Return(value) => resp.val = value,
+        }
+        if unsafe { libc::ioctl(notify_fd, SECCOMP_IOCTL_NOTIF_SEND, &mut resp as *mut SeccompNotifResp) } != 0 {
+            let err = io::Error::last_os_error();
+            // Not an error: the syscall was interrupted or the process died.
+            if err.raw_os_error() == Some(libc::ENOENT) {
+                return Ok(());
+            }
+            Err(err).context(format!("cannot respond to seccomp notification {}", self.id))?;
+        }
+        Ok(())
+    }
+}
+
+// How a notified syscall is resolved: run it as the process asked, fail
+// with an errno, or skip it and return a value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Response {
+    Continue,
+    Errno(i32),
+    Return(i64),
+}
+
+fn arch(audit_arch: u32, nr: u32) -> Option<Arch> {
+    match audit_arch {
+        AUDIT_ARCH_X86_64 if nr & X32_SYSCALL_BIT != 0 => Some(Arch::X32),
+        AUDIT_ARCH_X86_64 => Some(Arch::X86_64),
+        AUDIT_ARCH_I386 => Some(Arch::X86),
+        AUDIT_ARCH_AARCH64 => Some(Arch::Aarch64),
+        _ => None,
+    }
+}
+
+pub fn syscall_name(audit_arch: u32, nr: u32) -> Option<&'static str> {
+    arch(audit_arch, nr).and_then(|arch| arch.syscall_name(nr))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Read;
+    use std::io::Write;
+    use std::os::unix::io::FromRawFd;
+    use std::os::unix::net::UnixListener;
+    use tempfile::tempdir;
+
+    #[test]
+    fn send_and_receive_fd_over_socket() {
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let (reader, writer) = nix::unistd::pipe().unwrap();
+
+        send_fd(sender.as_raw_fd(), writer, b"hello").unwrap();
+        let (message, fd) = receive_fd(receiver.as_raw_fd()).unwrap();
+        nix::unistd::close(writer).unwrap();
+        let mut received = unsafe { File::from_raw_fd(fd.unwrap()) };
+        received.write_all(b"through").unwrap();
+        drop(received);
+        let mut contents = String::new();
+        unsafe { File::from_raw_fd(reader) }.read_to_string(&mut contents).unwrap();
+
+        assert_eq!(message, b"hello");
+        assert_eq!(contents, "through");
+    }
+
+    #[test]
+    fn listener_send_includes_container_process_state() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("agent.sock");
+        let agent = UnixListener::bind(&path).unwrap();
+        let listener = Listener::new(path.to_str().unwrap(), Some("tenant=a"));
+        let state = r#"{"ociVersion":"1.0.2","id":"c1","status":"created","pid":42,"bundle":"/b"}"#;
+
+        listener.send(0, 42, state).unwrap();
+        let (stream, _) = agent.accept().unwrap();
+        let (message, fd) = receive_fd(stream.as_raw_fd()).unwrap();
+        let process_state: Value = serde_json::from_slice(&message).unwrap();
+
+        assert!(fd.is_some());
+        assert_eq!(process_state["fds"], json!(["seccompFd"]));
+        assert_eq!(process_state["metadata"], json!("tenant=a"));
+        assert_eq!(process_state["state"]["id"], json!("c1"));
+        assert_eq!(process_state["ociVersion"], json!("1.0.2"));
+    }
+
+    #[test]
+    fn syscall_name_uses_the_table_of_the_arch() {
+        assert_eq!(syscall_name(AUDIT_ARCH_X86_64, 165), Some("mount"));
+        assert_eq!(syscall_name(AUDIT_ARCH_X86_64, 0x4000_0000 + 165), Some("mount"));
+        assert_eq!(syscall_name(AUDIT_ARCH_I386, 21), Some("mount"));
+        assert_eq!(syscall_name(0x1234, 0), None);
+    }
+}
@@ -0,0 +1,188 @@
+use crate::Error;
+use crate::config::linux::Seccomp as SeccompConfig;
+use super::Action;
+use super::Arch;
+use super::Rule;
+use super::Seccomp;
+use super::RUNTIME_SYSCALLS;
+use super::notify;
+use super::notify::Listener;
+use super::notify::Notification;
+use super::notify::Response;
+use nix::unistd;
+use tempfile::TempDir;
+use std::collections::BTreeSet;
+use std::fs;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::UnixListener;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+use failure::ResultExt;
+
+const SOCKET_NAME: &str = "recorder.sock";
+
+type Syscalls = Arc<Mutex<BTreeSet<(Arch, &'static str)>>>;
+
+// Lets every syscall of a container through while remembering it, so the
+// syscalls a workload needs can be written as an allowlist profile. The
+// container gets a filter that notifies everything, and the recorder is the
+// agent listening for it.
+#[derive(Debug)]
+pub struct Recorder {
+    socket_dir: TempDir,
+    listener: Option<UnixListener>,
+    syscalls: Syscalls,
+}
+
+impl Recorder {
+    pub fn listen() -> Result<Recorder, Error> {
+        let socket_dir = tempfile::Builder::new().prefix("plankton-seccomp").tempdir().context("cannot create seccomp recorder dir".to_string())?;
+        let socket_path = socket_dir.path().join(SOCKET_NAME);
+        let listener = UnixListener::bind(&socket_path).context(format!("cannot listen on {:?}", socket_path))?;
+
+        Ok(Recorder {
+            socket_dir,
+            listener: Some(listener),
+            syscalls: Syscalls::default(),
+        })
+    }
+
+    // Must be called once the init process is cloned: a thread running
+    // while the runtime clones may leave a lock taken in the child.
+    pub fn start(&mut self) {
+        if let Some(listener) = self.listener.take() {
+            let recorded = Arc::clone(&self.syscalls);
+            thread::spawn(move || {
+                if let Err(err) = record(&listener, &recorded) {
+                    eprintln!("Warning: seccomp recorder: {}", err);
+                }
+            });
+        }
+    }
+
+    pub fn seccomp(&self) -> Seccomp {
+        let mut seccomp = Seccomp::new(Action::Notify);
+        for arch in compat_architectures() {
+            if !seccomp.architectures.contains(arch) {
+                seccomp.architectures.push(*arch);
+            }
+        }
+        let runtime_syscalls: Vec<String> = RUNTIME_SYSCALLS.iter().map(|name| name.to_string()).collect();
+        seccomp.add_rule(Rule::new(&runtime_syscalls, Action::Allow));
+
+        let socket_path = self.socket_dir.path().join(SOCKET_NAME);
+        seccomp.set_listener(Listener::new(&socket_path.to_string_lossy(), None));
+        seccomp
+    }
+
+    // The runtime syscalls never reach the recorder, but the workload may
+    // use them as well, so they are always allowed.
+    pub fn profile(&self) -> SeccompConfig {
+        let mut architectures = BTreeSet::new();
+        let mut names: BTreeSet<&str> = RUNTIME_SYSCALLS.iter().cloned().collect();
+        architectures.insert(Arch::native());
+        if let Ok(syscalls) = self.syscalls.lock() {
+            for (arch, name) in syscalls.iter() {
+                architectures.insert(*arch);
+                names.insert(name);
+            }
+        }
+
+        let architectures = architectures.iter().map(|arch| arch.name().to_string()).collect();
+        let names = names.iter().map(|name| name.to_string()).collect();
+        SeccompConfig::allowlist(architectures, names)
+    }
+
+    pub fn write_profile(&self, path: &str) -> Result<(), Error> {
+        let json = serde_json::to_string_pretty(&self.profile()).context("cannot serialize seccomp profile".to_string())?;
+        fs::write(path, json).context(format!("cannot write seccomp profile to {}", path))?;
+        Ok(())
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn compat_architectures() -> &'static [Arch] {
+    &[Arch::X86, Arch::X32]
+}
+
+#[cfg(target_arch = "aarch64")]
+fn compat_architectures() -> &'static [Arch] {
+    &[]
+}
+
+// Nothing is received when the container fails before installing the
+// filter. Syscalls missing from our tables cannot be named in a profile and
+// are let through without being recorded.
+fn record(listener: &UnixListener, syscalls: &Syscalls) -> Result<(), Error> {
+    let (stream, _) = listener.accept().context("cannot accept seccomp fd".to_string())?;
+    let notify_fd = match notify::receive_fd(stream.as_raw_fd())? {
+        (_, Some(fd)) => fd,
+        (_, None) => return Ok(()),
+    };
+
+    while let Some(notification) = Notification::receive(notify_fd)? {
+        if let (Some(arch), Some(name)) = (notification.arch(), notification.syscall_name()) {
+            if let Ok(mut syscalls) = syscalls.lock() {
+                syscalls.insert((arch, name));
+            }
+        }
+        notification.respond(notify_fd, Response::Continue)?;
+    }
+    let _ = unistd::close(notify_fd);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nix::sys::wait;
+    use nix::sys::wait::WaitStatus;
+    use nix::unistd::ForkResult;
+    use std::os::unix::net::UnixStream;
+    use std::process;
+
+    #[test]
+    fn recorder_profile_always_allows_runtime_syscalls() {
+        let recorder = Recorder::listen().unwrap();
+        let profile = recorder.profile();
+
+        assert_eq!(profile.default_action(), "SCMP_ACT_ERRNO");
+        assert_eq!(profile.architectures(), &[Arch::native().name()]);
+        assert_eq!(profile.syscalls()[0].names(), &["sendmsg"]);
+        assert!(recorder.seccomp().compile().is_ok());
+    }
+
+    #[test]
+    fn recorder_records_syscalls_of_the_filtered_process() {
+        let mut recorder = Recorder::listen().unwrap();
+        let seccomp = recorder.seccomp();
+        let (runtime, init) = UnixStream::pair().unwrap();
+
+        let status = match unistd::fork().unwrap() {
+            ForkResult::Child => {
+                unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) };
+                let sent = match seccomp.install() {
+                    Ok(Some(notify_fd)) => notify::send_fd(init.as_raw_fd(), notify_fd, b"seccomp").is_ok(),
+                    _ => false,
+                };
+                unsafe { libc::getppid() };
+                process::exit(if sent { 0 } else { 1 });
+            },
+            ForkResult::Parent { child } => {
+                recorder.start();
+                let (_, notify_fd) = notify::receive_fd(runtime.as_raw_fd()).unwrap();
+                let state = r#"{"ociVersion":"1.0.2","id":"recorded"}"#;
+                seccomp.listener().unwrap().send(notify_fd.unwrap(), child.as_raw(), state).unwrap();
+                unistd::close(notify_fd.unwrap()).unwrap();
+                wait::waitpid(child, None).unwrap()
+            },
+        };
+
+        assert!(status == WaitStatus::Exited(status.pid().unwrap(), 0), "expect child to exit with 0, got {:?}", status);
+        let profile = recorder.profile();
+        let names = profile.syscalls()[0].names();
+        assert!(names.contains(&"getppid".to_string()), "expect getppid in {:?}", names);
+        assert!(names.contains(&"sendmsg".to_string()), "expect sendmsg in {:?}", names);
+    }
+}
@@ -0,0 +1,150 @@
+use crate::Error;
+use crate::libcontainer::linux::namespace::NamespaceList;
+use crate::libcontainer::linux::namespace::NamespaceType;
+use serde::Deserialize;
+use serde::Serialize;
+use std::fs;
+use std::path::PathBuf;
+use failure::ResultExt;
+
+const PROC_SYS: &str = "/proc/sys";
+
+const IPC_SYSCTLS: &[&str] = &[
+    "kernel.msgmax",
+    "kernel.msgmnb",
+    "kernel.msgmni",
+    "kernel.sem",
+    "kernel.shmall",
+    "kernel.shmmax",
+    "kernel.shmmni",
+    "kernel.shm_rmid_forced",
+];
+
+const UTS_SYSCTLS: &[&str] = &[
+    "kernel.domainname",
+    "kernel.hostname",
+];
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Sysctl {
+    key: String,
+    components: Vec<String>,
+    value: String,
+}
+
+impl Sysctl {
+    pub fn new(key: &str, value: &str, namespaces: &NamespaceList) -> Result<Sysctl, Error> {
+        // Keys may use '/' as separator so that interface names containing
+        // dots can be addressed, as in net/ipv4/conf/eth0.100/forwarding.
+        let separator = if key.contains('/') { '/' } else { '.' };
+        let components: Vec<String> = key.split(separator).map(String::from).collect();
+        if components.iter().any(|component| component.is_empty() || component == "." || component == "..") {
+            Err(Error::from("invalid sysctl key".to_string())).context(key.to_string())?;
+        }
+
+        let ns_type = match required_namespace(&components.join(".")) {
+            Some(ns_type) => ns_type,
+            None => Err(Error::from("sysctl is not namespaced and would change the host".to_string())).context(key.to_string())?,
+        };
+        if !namespaces.is_private(&ns_type) {
+            Err(Error::from(format!("sysctl requires a private {} namespace", ns_type.to_string()))).context(key.to_string())?;
+        }
+
+        Ok(Sysctl {
+            key: key.to_string(),
+            components,
+            value: value.to_string(),
+        })
+    }
+
+    pub fn set(&self) -> Result<(), Error> {
+        fs::write(self.path(), &self.value).context(format!("cannot set sysctl {} to {:?}", self.key, self.value))?;
+        Ok(())
+    }
+
+    fn path(&self) -> PathBuf {
+        let mut path = PathBuf::from(PROC_SYS);
+        path.extend(&self.components);
+        path
+    }
+}
+
+fn required_namespace(key: &str) -> Option<NamespaceType> {
+    if IPC_SYSCTLS.contains(&key) || key.starts_with("fs.mqueue.") {
+        return Some(NamespaceType::IPC);
+    }
+    if UTS_SYSCTLS.contains(&key) {
+        return Some(NamespaceType::UTS);
+    }
+    if key.starts_with("net.") {
+        return Some(NamespaceType::NETWORK);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libcontainer::linux::namespace::Namespace;
+
+    fn namespaces(types: Vec<NamespaceType>) -> NamespaceList {
+        let mut namespaces = NamespaceList::empty();
+        for ns_type in types {
+            namespaces.insert(Namespace::new(ns_type, None)).unwrap();
+        }
+        namespaces
+    }
+
+    #[test]
+    fn sysctl_new_requires_the_matching_private_namespace() {
+        let table = vec![
+            ("net.core.somaxconn", NamespaceType::NETWORK),
+            ("net.ipv4.ip_unprivileged_port_start", NamespaceType::NETWORK),
+            ("kernel.msgmax", NamespaceType::IPC),
+            ("fs.mqueue.queues_max", NamespaceType::IPC),
+            ("kernel.hostname", NamespaceType::UTS),
+        ];
+
+        for (key, ns_type) in table {
+            let without_namespace = Sysctl::new(key, "1", &namespaces(vec![]));
+            assert!(without_namespace.is_err(), "expect {:?} to be err for {}", without_namespace, key);
+
+            let with_namespace = Sysctl::new(key, "1", &namespaces(vec![ns_type]));
+            assert!(with_namespace.is_ok(), "expect {:?} to be ok for {}", with_namespace, key);
+        }
+    }
+
+    #[test]
+    fn sysctl_new_rejects_host_sysctls() {
+        let all = namespaces(vec![NamespaceType::NETWORK, NamespaceType::IPC, NamespaceType::UTS]);
+        for key in &["kernel.panic", "vm.swappiness", "fs.file-max", "kernel.msg"] {
+            let result = Sysctl::new(key, "1", &all);
+            assert!(result.is_err(), "expect {:?} to be err for {}", result, key);
+        }
+    }
+
+    #[test]
+    fn sysctl_new_rejects_joined_runtime_namespace() {
+        let mut host_network = NamespaceList::empty();
+        host_network.insert(Namespace::new(NamespaceType::NETWORK, Some("/proc/self/ns/net".to_string()))).unwrap();
+
+        let result = Sysctl::new("net.core.somaxconn", "1024", &host_network);
+        assert!(result.is_err(), "expect {:?} to be err", result);
+    }
+
+    #[test]
+    fn sysctl_new_rejects_path_traversal() {
+        let network = namespaces(vec![NamespaceType::NETWORK]);
+        for key in &["net..core", "net/../../kernel/panic", "net.core."] {
+            let result = Sysctl::new(key, "1", &network);
+            assert!(result.is_err(), "expect {:?} to be err for {}", result, key);
+        }
+    }
+
+    #[test]
+    fn sysctl_path_accepts_slash_separated_keys() {
+        let network = namespaces(vec![NamespaceType::NETWORK]);
+        let sysctl = Sysctl::new("net/ipv4/conf/eth0.100/forwarding", "1", &network).unwrap();
+        assert_eq!(sysctl.path(), PathBuf::from("/proc/sys/net/ipv4/conf/eth0.100/forwarding"));
+    }
+}
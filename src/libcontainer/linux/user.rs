@@ -6,11 +6,12 @@ pub struct User {
     uid: i64,
     gid: i64,
     additional_gids: Option<Vec<i64>>,
+    umask: Option<u32>,
 }
 
 impl User {
     pub fn new(uid: i64, gid: i64, additional_gids: Option<Vec<i64>>) -> User {
-        User { uid, gid, additional_gids }
+        User { uid, gid, additional_gids, umask: None }
     }
 
     pub fn root() -> User {
@@ -18,15 +19,31 @@ impl User {
             uid: 0,
             gid: 0,
             additional_gids: None,
+            umask: None,
         }
     }
 
+    pub fn set_umask(&mut self, umask: u32) {
+        self.umask = Some(umask & 0o777);
+    }
+
     pub fn uid(&self) -> i64 {
         self.uid
     }
 
     pub fn gid(&self) -> i64 {
-        self.uid
+        self.gid
+    }
+
+    pub fn additional_gids(&self) -> &[i64] {
+        match &self.additional_gids {
+            Some(gids) => gids,
+            None => &[],
+        }
+    }
+
+    pub fn umask(&self) -> Option<u32> {
+        self.umask
     }
 }
 
@@ -37,9 +54,10 @@ mod tests {
 
     #[test]
     fn user_root_returns_a_user_with_given_parameters() {
-        let user = User::new(100, 100, Some(vec![200, 300, 400]));
-        assert_eq!(user.uid, 100);
-        assert_eq!(user.uid, 100);
+        let user = User::new(100, 101, Some(vec![200, 300, 400]));
+        assert_eq!(user.uid(), 100);
+        assert_eq!(user.gid(), 101);
+        assert_eq!(user.additional_gids(), &[200, 300, 400]);
         assert!(user.additional_gids.is_some());
         assert_eq!(user.additional_gids.unwrap(), vec![200, 300, 400]);
     }
@@ -56,6 +74,16 @@ mod tests {
         assert_eq!(user.gid, 0);
     }
 
+    #[test]
+    fn user_set_umask_keeps_only_permission_bits() {
+        let mut user = User::root();
+        assert!(user.umask().is_none());
+
+        user.set_umask(0o7022);
+
+        assert_eq!(user.umask(), Some(0o022));
+    }
+
     #[test]
     fn user_root_returns_a_user_with_none_additional_gids() {
         let user = User::root();
@@ -23,5 +23,25 @@ fn main() -> Result<(), Error> {
         cli::query(matches)?;
     }
 
+    if let Some(matches) = matches.subcommand_matches("stats") {
+        cli::stats(matches)?;
+    }
+
+    if let Some(matches) = matches.subcommand_matches("events") {
+        cli::events(matches)?;
+    }
+
+    if let Some(matches) = matches.subcommand_matches("spec") {
+        cli::spec(matches)?;
+    }
+
+    if let Some(matches) = matches.subcommand_matches("seccomp-agent") {
+        cli::seccomp_agent(matches)?;
+    }
+
+    if let Some(matches) = matches.subcommand_matches("gc") {
+        cli::gc(matches)?;
+    }
+
     Ok(())
 }
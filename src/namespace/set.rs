@@ -1,5 +1,5 @@
 use crate::Error;
-use crate::spec::NamespaceSpec;
+use crate::config;
 use failure::ResultExt;
 use std::collections::HashMap;
 use std::path::PathBuf;
@@ -20,24 +20,18 @@ impl NamespaceSet {
         }
     }
 
-    pub fn from_spec<N: NamespaceSpec>(ns_spec: Option<&Vec<N>>) -> Result<NamespaceSet, Error> {
-        if ns_spec.is_none() {
-            return Ok(NamespaceSet::empty());
-        }
-        let ns_list = ns_spec.unwrap();
+    pub fn from_spec(ns_list: &[config::Namespace]) -> Result<NamespaceSet, Error> {
         let mut to_create: Vec<Namespace> = Vec::new();
         let mut to_enter: HashMap<Namespace, PathBuf> = HashMap::new();
         for spec_ns in ns_list.iter() {
-            let t = spec_ns.get_type_clone();
-            let p = spec_ns.get_path_clone();
-            let ns = Namespace::try_from(t.as_ref())?;
+            let t = spec_ns.ns_type();
+            let ns = Namespace::try_from(t)?;
             if to_create.contains(&ns) || to_enter.contains_key(&ns) {
-                Err(Error::from("duplicated namespace type")).context(t)?;
+                Err(Error::from("duplicated namespace type")).context(t.to_string())?;
             }
-            if p.is_none() {
-                to_create.push(ns);
-            } else {
-                to_enter.insert(ns, p.unwrap());
+            match spec_ns.path() {
+                Some(p) => { to_enter.insert(ns, PathBuf::from(p)); },
+                None => to_create.push(ns),
             }
         }
         Ok(NamespaceSet{to_create, to_enter})
@@ -51,18 +45,11 @@ impl NamespaceSet {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use serde_json::json;
     use std::path::PathBuf;
 
-    struct FakeNamespace{
-        fake_type: String,
-        fake_path: Option<PathBuf>,
-    }
-
-    impl NamespaceSpec for FakeNamespace {
-        fn get_type(&self) -> &String { &self.fake_type }
-        fn get_type_clone(&self) -> String { self.fake_type.clone() }
-        fn get_path(&self) -> Option<&PathBuf> { self.fake_path.as_ref() }
-        fn get_path_clone(&self) -> Option<PathBuf> { self.get_path().cloned() }
+    fn namespace(ns_type: &str, path: Option<&str>) -> config::Namespace {
+        serde_json::from_value(json!({"type": ns_type, "path": path})).unwrap()
     }
 
     #[test]
@@ -74,11 +61,11 @@ mod tests {
 
     #[test]
     fn from_spec_with_none_paths() {
-        let namespaces = Some(vec![
-            FakeNamespace{fake_type: "cgroup".to_string(), fake_path: None},
-            FakeNamespace{fake_type: "ipc".to_string(), fake_path: None},
-        ]);
-        let result = NamespaceSet::from_spec(namespaces.as_ref());
+        let namespaces = vec![
+            namespace("cgroup", None),
+            namespace("ipc", None),
+        ];
+        let result = NamespaceSet::from_spec(&namespaces);
         let expected_to_create = vec![Namespace::CGROUP, Namespace::IPC];
         let expected_to_enter = HashMap::<Namespace, PathBuf>::new();
         assert!(result.is_ok(), "expected {:?} to be err", result);
@@ -89,12 +76,12 @@ mod tests {
 
     #[test]
     fn from_spec_with_some_paths() {
-        let namespaces = Some(vec![
-            FakeNamespace{fake_type: "pid".to_string(), fake_path: None},
-            FakeNamespace{fake_type: "user".to_string(), fake_path: Some(PathBuf::from("/proc/1234/ns/user"))},
-            FakeNamespace{fake_type: "mount".to_string(), fake_path: None},
-        ]);
-        let result = NamespaceSet::from_spec(namespaces.as_ref());
+        let namespaces = vec![
+            namespace("pid", None),
+            namespace("user", Some("/proc/1234/ns/user")),
+            namespace("mount", None),
+        ];
+        let result = NamespaceSet::from_spec(&namespaces);
         let expected_to_create = vec![
             Namespace::PID,
             Namespace::MOUNT,
@@ -110,41 +97,41 @@ mod tests {
 
     #[test]
     fn from_spec_with_invalid_type() {
-        let namespaces = Some(vec![
-            FakeNamespace{fake_type: "invalid".to_string(), fake_path: None},
-            FakeNamespace{fake_type: "user".to_string(), fake_path: Some(PathBuf::from("/proc/1234/ns/user"))},
-            FakeNamespace{fake_type: "mount".to_string(), fake_path: None},
-        ]);
-        let result = NamespaceSet::from_spec(namespaces.as_ref());
+        let namespaces = vec![
+            namespace("invalid", None),
+            namespace("user", Some("/proc/1234/ns/user")),
+            namespace("mount", None),
+        ];
+        let result = NamespaceSet::from_spec(&namespaces);
         assert!(result.is_err(), "expected {:?} to be err", result);
     }
 
     #[test]
     fn from_spec_with_duplicated_types() {
-        let namespaces = Some(vec![
-            FakeNamespace{fake_type: "pid".to_string(), fake_path: None},
-            FakeNamespace{fake_type: "pid".to_string(), fake_path: Some(PathBuf::from("/proc/1234/ns/pid"))},
-        ]);
-        let result = NamespaceSet::from_spec(namespaces.as_ref());
+        let namespaces = vec![
+            namespace("pid", None),
+            namespace("pid", Some("/proc/1234/ns/pid")),
+        ];
+        let result = NamespaceSet::from_spec(&namespaces);
         assert!(result.is_err(), "expected {:?} to be err", result);
     }
 
     #[test]
     fn from_spec_with_duplicated_types_and_no_path() {
-        let namespaces = Some(vec![
-            FakeNamespace{fake_type: "pid".to_string(), fake_path: None},
-            FakeNamespace{fake_type: "pid".to_string(), fake_path: None},
-        ]);
-        let result = NamespaceSet::from_spec(namespaces.as_ref());
+        let namespaces = vec![
+            namespace("pid", None),
+            namespace("pid", None),
+        ];
+        let result = NamespaceSet::from_spec(&namespaces);
         assert!(result.is_err(), "expected {:?} to be err", result);
     }
 
     #[test]
     fn contains() {
-        let namespaces = Some(vec![
-            FakeNamespace{fake_type: "pid".to_string(), fake_path: None},
-        ]);
-        let set = NamespaceSet::from_spec(namespaces.as_ref()).unwrap();
+        let namespaces = vec![
+            namespace("pid", None),
+        ];
+        let set = NamespaceSet::from_spec(&namespaces).unwrap();
         assert!(set.contains(&Namespace::PID));
         assert!(!set.contains(&Namespace::UTS));
     }
@@ -1,10 +1,10 @@
 pub mod platform;
 
 use crate::Error;
-use crate::spec::ProcessSpec;
+use crate::config;
 
 pub trait ProcessCreate {
-    fn from_spec<P: ProcessSpec>(spec: &P) -> Result<Self, Error> where Self: Sized;
+    fn from_spec(spec: &config::Process) -> Result<Self, Error> where Self: Sized;
 }
 
 pub trait ProcessRun {
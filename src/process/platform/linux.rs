@@ -1,16 +1,14 @@
 use crate::Error;
+use crate::config;
 use crate::namespace::NamespaceSet;
 use crate::process::ProcessCreate;
-use crate::spec::ProcessSpec;
-use crate::spec::LinuxSpec;
-use crate::spec::ConsoleSizeSpec;
 use failure::ResultExt;
 use std::ffi::CString;
 use std::ffi::OsString;
 use std::path::PathBuf;
 
 pub trait LinuxProcess {
-    fn namespaces<L: LinuxSpec>(&mut self, spec: Option<&L>) -> Result<(), Error>;
+    fn namespaces(&mut self, spec: Option<&config::Linux>) -> Result<(), Error>;
 }
 
 #[derive(Debug, PartialEq)]
@@ -18,25 +16,24 @@ pub struct Process {
     program: Option<CString>,
     args: Option<Vec<CString>>,
     attach_terminal: bool,
-    console_height: Option<u16>,
-    console_width: Option<u16>,
+    console_height: Option<u32>,
+    console_width: Option<u32>,
     cwd: PathBuf,
     envs: Vec<(OsString, OsString)>,
     namespaces: NamespaceSet,
 }
 
 impl LinuxProcess for Process {
-    fn namespaces<L: LinuxSpec>(&mut self, spec: Option<&L>) -> Result<(), Error> {
-        if spec.is_some() {
-            let ns = spec.unwrap().get_namespaces();
-            self.namespaces = NamespaceSet::from_spec(ns)?;
+    fn namespaces(&mut self, spec: Option<&config::Linux>) -> Result<(), Error> {
+        if let Some(linux) = spec {
+            self.namespaces = NamespaceSet::from_spec(linux.namespaces())?;
         }
         Ok(())
     }
 }
 
 impl ProcessCreate for Process {
-    fn from_spec<P: ProcessSpec>(spec: &P) -> Result<Process, Error> {
+    fn from_spec(spec: &config::Process) -> Result<Process, Error> {
         let mut process = Process {
             program: None,
             args: None,
@@ -47,22 +44,21 @@ impl ProcessCreate for Process {
             envs: Vec::new(),
             namespaces: NamespaceSet::empty(),
         };
-        process.args(spec.get_args_clone())?;
-        process.attach_terminal(spec.get_terminal());
-        process.cwd(spec.get_cwd_clone())?;
-        process.console_size(spec.get_console_size_clone());
-        process.envs(spec.get_env_clone())?;
+        process.args(spec.args())?;
+        process.attach_terminal(spec.terminal());
+        process.cwd(PathBuf::from(spec.cwd()))?;
+        process.console_size(spec.console_size());
+        process.envs(spec.env())?;
         Ok(process)
     }
 }
 
 impl Process {
-    fn args(&mut self, args: Option<Vec<String>>) -> Result<(), Error> {
-        if args.is_some() {
-            let args = args.unwrap();
+    fn args(&mut self, args: &[String]) -> Result<(), Error> {
+        if !args.is_empty() {
             let mut cstring_args = Vec::<CString>::new();
             for arg in args {
-                let cstring_arg = CString::new(arg).context(format!("invalid argument on args list"))?;
+                let cstring_arg = CString::new(arg.as_str()).context("invalid argument on args list".to_string())?;
                 cstring_args.push(cstring_arg);
             }
             self.program = cstring_args.first().cloned();
@@ -71,15 +67,14 @@ impl Process {
         Ok(())
     }
 
-    fn attach_terminal(&mut self, attach: Option<&bool>) {
-        self.attach_terminal = attach.is_some() && *attach.unwrap();
+    fn attach_terminal(&mut self, attach: bool) {
+        self.attach_terminal = attach;
     }
 
-    fn console_size<S: ConsoleSizeSpec>(&mut self, size: Option<S>) {
-        if self.attach_terminal && size.is_some() {
-            let s = size.unwrap();
-            self.console_width = Some(s.get_width());
-            self.console_height = Some(s.get_height());
+    fn console_size(&mut self, size: Option<&config::process::ConsoleSize>) {
+        if let (true, Some(size)) = (self.attach_terminal, size) {
+            self.console_width = Some(size.width());
+            self.console_height = Some(size.height());
         }
     }
 
@@ -91,11 +86,9 @@ impl Process {
         Ok(())
     }
 
-    pub fn envs(&mut self, envs: Option<Vec<String>>) -> Result<(), Error> {
-        if envs.is_some() {
-            for env in envs.unwrap() {
-                self.put_env(&env)?;
-            }
+    pub fn envs(&mut self, envs: &[String]) -> Result<(), Error> {
+        for env in envs {
+            self.put_env(env)?;
         }
         Ok(())
     }
@@ -120,54 +113,15 @@ impl Process {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::spec::ProcessSpec;
-    use crate::spec::ConsoleSizeSpec;
+    use serde_json::json;
+    use serde_json::Value;
     use std::ffi::OsString;
     use std::path::PathBuf;
 
-    #[derive(Debug)]
-    struct FakeProcess {
-        fake_terminal: Option<bool>,
-        fake_console_size: Option<FakeConsoleSize>,
-        fake_cwd: PathBuf,
-        fake_env: Option<Vec<String>>,
-        fake_args: Option<Vec<String>>,
-    }
-
-    #[derive(Clone, Debug)]
-    struct FakeConsoleSize {
-        fake_width: u16,
-        fake_height: u16,
-    }
-
-    impl ProcessSpec for FakeProcess {
-        type ConsoleSize = FakeConsoleSize;
-
-        fn get_terminal(&self) -> Option<&bool> { self.fake_terminal.as_ref() }
-        fn get_terminal_clone(&self) -> Option<bool> { self.get_terminal().cloned() }
-        fn get_console_size(&self) -> Option<&Self::ConsoleSize> { self.fake_console_size.as_ref() }
-        fn get_console_size_clone(&self) -> Option<Self::ConsoleSize> { self.get_console_size().cloned() }
-        fn get_cwd(&self) -> &PathBuf { &self.fake_cwd }
-        fn get_cwd_clone(&self) -> PathBuf { self.fake_cwd.clone() }
-        fn get_env(&self) -> Option<&Vec<String>> { self.fake_env.as_ref() }
-        fn get_env_clone(&self) -> Option<Vec<String>> { self.get_env().cloned() }
-        fn get_args(&self) -> Option<&Vec<String>> { self.fake_args.as_ref() }
-        fn get_args_clone(&self) -> Option<Vec<String>> { self.get_args().cloned() }
-    }
-
-    impl ConsoleSizeSpec for FakeConsoleSize {
-        fn get_height(&self) -> u16 { self.fake_width }
-        fn get_width(&self) -> u16 { self.fake_height }
-    }
-
-    fn empty_spec() -> FakeProcess {
-        FakeProcess {
-            fake_terminal: None,
-            fake_console_size: None,
-            fake_cwd: PathBuf::from("/"),
-            fake_env: None,
-            fake_args: None,
-        }
+    fn spec_with(field: &str, value: Value) -> config::Process {
+        let mut spec = json!({"cwd": "/"});
+        spec[field] = value;
+        serde_json::from_value(spec).unwrap()
     }
 
     fn to_cstring(s: &str) -> CString {
@@ -209,8 +163,7 @@ mod tests {
             ),
         ];
         for (spec_value, expected_args, is_ok) in table {
-            let mut spec = empty_spec();
-            spec.fake_args = spec_value;
+            let spec = spec_with("args", json!(spec_value));
             let result = Process::from_spec(&spec);
             assert_eq!(result.is_ok(), is_ok, "expected result.is_ok() to be {:?}, but got {:?}", is_ok, &result);
             if result.is_ok() {
@@ -228,13 +181,27 @@ mod tests {
             (None, false),
         ];
         for (spec_value, expected) in table {
-            let mut spec = empty_spec();
-            spec.fake_terminal = spec_value;
+            let spec = spec_with("terminal", json!(spec_value));
             let proc = Process::from_spec(&spec).unwrap();
             assert_eq!(proc.attach_terminal, expected, "expected process attach_terminal to be {:?} but got {:?}", proc.attach_terminal, expected)
         }
     }
 
+    #[test]
+    fn from_spec_console_size() {
+        let table = vec![
+            (json!({"cwd": "/", "terminal": true, "consoleSize": {"height": 25, "width": 80}}), Some(25), Some(80)),
+            (json!({"cwd": "/", "terminal": false, "consoleSize": {"height": 25, "width": 80}}), None, None),
+            (json!({"cwd": "/", "terminal": true}), None, None),
+        ];
+        for (spec_value, expected_height, expected_width) in table {
+            let spec: config::Process = serde_json::from_value(spec_value).unwrap();
+            let proc = Process::from_spec(&spec).unwrap();
+            assert_eq!(proc.console_height, expected_height, "expected process console_height to be {:?} but got {:?}", expected_height, proc.console_height);
+            assert_eq!(proc.console_width, expected_width, "expected process console_width to be {:?} but got {:?}", expected_width, proc.console_width);
+        }
+    }
+
     #[test]
     fn from_spec_working_dir() {
         let table = vec![
@@ -260,8 +227,7 @@ mod tests {
             )
         ];
         for (spec_value, expected, is_ok) in table {
-            let mut spec = empty_spec();
-            spec.fake_cwd = spec_value;
+            let spec = spec_with("cwd", json!(spec_value));
             let result = Process::from_spec(&spec);
             assert_eq!(result.is_ok(), is_ok, "expected result.is_ok() to be {:?}, but got {:?}", is_ok, &result);
             if result.is_ok() {
@@ -316,10 +282,9 @@ mod tests {
             ),
         ];
         for (spec_value, expected, is_ok) in table {
-            let mut spec = empty_spec();
-            spec.fake_env = spec_value;
+            let spec = spec_with("env", json!(spec_value));
             let result = Process::from_spec(&spec);
-            assert_eq!(result.is_ok(), is_ok, "expected result.is_ok() to be {:?}, but got {:?}: spec value: {:?}", is_ok, &result, &spec.fake_env);
+            assert_eq!(result.is_ok(), is_ok, "expected result.is_ok() to be {:?}, but got {:?}: spec value: {:?}", is_ok, &result, spec.env());
             if result.is_ok() {
                 let proc = result.unwrap();
                 assert_eq!(proc.envs, expected, "expected process envs to be {:?} but got {:?}", expected, proc.envs);
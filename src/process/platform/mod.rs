@@ -3,12 +3,12 @@ pub mod linux;
 use crate::Error;
 use crate::process::ProcessCreate;
 use crate::process::ProcessRun;
-use crate::spec::ProcessSpec;
+use crate::config;
 
 pub struct Process{}
 
 impl ProcessCreate for Process {
-    fn from_spec<P: ProcessSpec>(_spec: &P) -> Result<Process, Error> {
+    fn from_spec(_spec: &config::Process) -> Result<Process, Error> {
         Err(Error::from("platform not supported"))
     }
 }